@@ -1,12 +1,46 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20220101_000001_create_table;
+mod m20230801_000002_add_user_roles;
+mod m20230802_000003_create_refresh_tokens;
+mod m20230803_000004_create_email_verification_tokens;
+mod m20230804_000005_create_password_reset_tokens;
+mod m20230805_000006_add_user_suspension;
+mod m20230806_000007_create_login_attempts;
+mod m20230807_000008_add_totp;
+mod m20230808_000009_add_book_search;
+mod m20230809_000010_create_copies;
+mod m20230810_000011_add_reservation_status;
+mod m20230811_000012_add_loan_policies;
+mod m20230812_000013_add_hold_queue;
+mod m20230813_000014_create_ledger_entries;
+mod m20230814_000015_create_job_runs;
+mod m20230815_000016_create_notifications;
+mod m20230816_000017_add_active_reservation_index;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20220101_000001_create_table::Migration)]
+        vec![
+            Box::new(m20220101_000001_create_table::Migration),
+            Box::new(m20230801_000002_add_user_roles::Migration),
+            Box::new(m20230802_000003_create_refresh_tokens::Migration),
+            Box::new(m20230803_000004_create_email_verification_tokens::Migration),
+            Box::new(m20230804_000005_create_password_reset_tokens::Migration),
+            Box::new(m20230805_000006_add_user_suspension::Migration),
+            Box::new(m20230806_000007_create_login_attempts::Migration),
+            Box::new(m20230807_000008_add_totp::Migration),
+            Box::new(m20230808_000009_add_book_search::Migration),
+            Box::new(m20230809_000010_create_copies::Migration),
+            Box::new(m20230810_000011_add_reservation_status::Migration),
+            Box::new(m20230811_000012_add_loan_policies::Migration),
+            Box::new(m20230812_000013_add_hold_queue::Migration),
+            Box::new(m20230813_000014_create_ledger_entries::Migration),
+            Box::new(m20230814_000015_create_job_runs::Migration),
+            Box::new(m20230815_000016_create_notifications::Migration),
+            Box::new(m20230816_000017_add_active_reservation_index::Migration),
+        ]
     }
 }
@@ -126,6 +126,7 @@ impl MigrationTrait for Migration {
 
         // Construct a `Statement` if the SQL contains value bindings
         // password = admin
+        // Stored as a legacy MD5 digest, upgraded to Argon2id on the first successful login
         db.execute_unprepared(
             "
             INSERT INTO public.users 
@@ -0,0 +1,128 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RefreshTokens::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RefreshTokens::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .extra("DEFAULT uuid_generate_v4()".to_owned()),
+                    )
+                    .col(ColumnDef::new(RefreshTokens::UserId).uuid().not_null())
+                    .col(ColumnDef::new(RefreshTokens::FamilyId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(RefreshTokens::TokenHash)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(RefreshTokens::ExpiresAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(RefreshTokens::RevokedAt).timestamp())
+                    .col(ColumnDef::new(RefreshTokens::ReplacedBy).uuid())
+                    .col(
+                        ColumnDef::new(RefreshTokens::CreatedAt)
+                            .timestamp()
+                            .extra("DEFAULT NOW()".to_owned()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-RefreshTokens-Users_id-Users-id")
+                            .from(RefreshTokens::Table, RefreshTokens::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-RefreshTokens-FamilyId")
+                    .table(RefreshTokens::Table)
+                    .col(RefreshTokens::FamilyId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(RevokedTokens::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RevokedTokens::Jti)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(RevokedTokens::ExpiresAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RevokedTokens::CreatedAt)
+                            .timestamp()
+                            .extra("DEFAULT NOW()".to_owned()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RevokedTokens::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(RefreshTokens::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Users {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum RefreshTokens {
+    Table,
+    Id,
+    UserId,
+    FamilyId,
+    TokenHash,
+    ExpiresAt,
+    RevokedAt,
+    ReplacedBy,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+enum RevokedTokens {
+    Table,
+    Jti,
+    ExpiresAt,
+    CreatedAt,
+}
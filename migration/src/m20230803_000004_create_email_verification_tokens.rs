@@ -0,0 +1,88 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(EmailVerificationTokens::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(EmailVerificationTokens::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .extra("DEFAULT uuid_generate_v4()".to_owned()),
+                    )
+                    .col(
+                        ColumnDef::new(EmailVerificationTokens::UserId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(EmailVerificationTokens::TokenHash)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(EmailVerificationTokens::ExpiresAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(EmailVerificationTokens::UsedAt).timestamp())
+                    .col(
+                        ColumnDef::new(EmailVerificationTokens::CreatedAt)
+                            .timestamp()
+                            .extra("DEFAULT NOW()".to_owned()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-EmailVerificationTokens-Users_id-Users-id")
+                            .from(
+                                EmailVerificationTokens::Table,
+                                EmailVerificationTokens::UserId,
+                            )
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(EmailVerificationTokens::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Users {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum EmailVerificationTokens {
+    Table,
+    Id,
+    UserId,
+    TokenHash,
+    ExpiresAt,
+    UsedAt,
+    CreatedAt,
+}
@@ -0,0 +1,100 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PasswordResetTokens::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PasswordResetTokens::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .extra("DEFAULT uuid_generate_v4()".to_owned()),
+                    )
+                    .col(
+                        ColumnDef::new(PasswordResetTokens::UserId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PasswordResetTokens::TokenHash)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(PasswordResetTokens::ExpiresAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(PasswordResetTokens::UsedAt).timestamp())
+                    .col(
+                        ColumnDef::new(PasswordResetTokens::CreatedAt)
+                            .timestamp()
+                            .extra("DEFAULT NOW()".to_owned()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-PasswordResetTokens-Users_id-Users-id")
+                            .from(PasswordResetTokens::Table, PasswordResetTokens::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(ColumnDef::new(Users::PasswordChangedAt).timestamp())
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .drop_column(Users::PasswordChangedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(PasswordResetTokens::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Users {
+    Table,
+    Id,
+    PasswordChangedAt,
+}
+
+#[derive(Iden)]
+enum PasswordResetTokens {
+    Table,
+    Id,
+    UserId,
+    TokenHash,
+    ExpiresAt,
+    UsedAt,
+    CreatedAt,
+}
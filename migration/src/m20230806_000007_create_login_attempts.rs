@@ -0,0 +1,55 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(LoginAttempts::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(LoginAttempts::Key)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(LoginAttempts::Failures)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(LoginAttempts::LastFailureAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(LoginAttempts::BlockedUntil).timestamp())
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(LoginAttempts::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum LoginAttempts {
+    Table,
+    Key,
+    Failures,
+    LastFailureAt,
+    BlockedUntil,
+}
@@ -0,0 +1,125 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(ColumnDef::new(Users::TotpSecret).string())
+                    .add_column(
+                        ColumnDef::new(Users::TotpEnabled)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .add_column(ColumnDef::new(Users::TotpLastStep).big_integer())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RefreshTokens::Table)
+                    .add_column(
+                        ColumnDef::new(RefreshTokens::Mfa)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(RecoveryCodes::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RecoveryCodes::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .extra("DEFAULT uuid_generate_v4()".to_owned()),
+                    )
+                    .col(ColumnDef::new(RecoveryCodes::UserId).uuid().not_null())
+                    .col(ColumnDef::new(RecoveryCodes::CodeHash).string().not_null())
+                    .col(ColumnDef::new(RecoveryCodes::UsedAt).timestamp())
+                    .col(
+                        ColumnDef::new(RecoveryCodes::CreatedAt)
+                            .timestamp()
+                            .extra("DEFAULT NOW()".to_owned()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-RecoveryCodes-Users_id-Users-id")
+                            .from(RecoveryCodes::Table, RecoveryCodes::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RecoveryCodes::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RefreshTokens::Table)
+                    .drop_column(RefreshTokens::Mfa)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .drop_column(Users::TotpSecret)
+                    .drop_column(Users::TotpEnabled)
+                    .drop_column(Users::TotpLastStep)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Users {
+    Table,
+    Id,
+    TotpSecret,
+    TotpEnabled,
+    TotpLastStep,
+}
+
+#[derive(Iden)]
+enum RefreshTokens {
+    Table,
+    Mfa,
+}
+
+#[derive(Iden)]
+enum RecoveryCodes {
+    Table,
+    Id,
+    UserId,
+    CodeHash,
+    UsedAt,
+    CreatedAt,
+}
@@ -0,0 +1,51 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // English stemming on unaccented words, so "Garcia Marquez" finds "García Márquez"
+        db.execute_unprepared(
+            "
+            CREATE EXTENSION IF NOT EXISTS unaccent;
+            CREATE TEXT SEARCH CONFIGURATION public.book_search (COPY = pg_catalog.english);
+            ALTER TEXT SEARCH CONFIGURATION public.book_search
+                ALTER MAPPING FOR hword, hword_part, word WITH unaccent, english_stem;
+            ",
+        )
+        .await?;
+
+        // Title matches weigh more than author matches when ranking
+        db.execute_unprepared(
+            "
+            ALTER TABLE public.books ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (
+                setweight(to_tsvector('public.book_search', coalesce(title, '')), 'A') ||
+                setweight(to_tsvector('public.book_search', coalesce(author, '')), 'B')
+            ) STORED;
+            CREATE INDEX \"idx-Books-SearchVector\" ON public.books USING GIN (search_vector);
+            ",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+            DROP INDEX IF EXISTS public.\"idx-Books-SearchVector\";
+            ALTER TABLE public.books DROP COLUMN IF EXISTS search_vector;
+            DROP TEXT SEARCH CONFIGURATION IF EXISTS public.book_search;
+            ",
+        )
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Copies::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Copies::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .extra("DEFAULT uuid_generate_v4()".to_owned()),
+                    )
+                    .col(ColumnDef::new(Copies::BookId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(Copies::Barcode)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Copies::Condition)
+                            .string_len(16)
+                            .not_null()
+                            .default("good"),
+                    )
+                    .col(ColumnDef::new(Copies::ShelfLocation).string())
+                    .col(
+                        ColumnDef::new(Copies::Status)
+                            .string_len(16)
+                            .not_null()
+                            .default("available"),
+                    )
+                    .col(
+                        ColumnDef::new(Copies::CreatedAt)
+                            .timestamp()
+                            .extra("DEFAULT NOW()".to_owned()),
+                    )
+                    .col(ColumnDef::new(Copies::UpdatedAt).timestamp())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-Copies-Books_id-Books-id")
+                            .from(Copies::Table, Copies::BookId)
+                            .to(Books::Table, Books::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-Copies-BookId")
+                    .table(Copies::Table)
+                    .col(Copies::BookId)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Every existing book becomes a single copy, barcoded with the book id
+        db.execute_unprepared(
+            "
+            INSERT INTO public.copies (book_id, barcode, status)
+                SELECT id, id::text, CASE WHEN available THEN 'available' ELSE 'on_loan' END
+                FROM public.books;
+            ",
+        )
+        .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Reservations::Table)
+                    .add_column(ColumnDef::new(Reservations::CopyId).uuid())
+                    .to_owned(),
+            )
+            .await?;
+
+        db.execute_unprepared(
+            "
+            UPDATE public.reservations r SET copy_id = c.id
+                FROM public.copies c WHERE c.book_id = r.book_id;
+            ALTER TABLE public.reservations ALTER COLUMN copy_id SET NOT NULL;
+            ",
+        )
+        .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk-Reservations-Copies_id-Copies-id")
+                    .from(Reservations::Table, Reservations::CopyId)
+                    .to(Copies::Table, Copies::Id)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Titles may repeat (editions, translations), and availability now comes from copies
+        db.execute_unprepared(
+            "ALTER TABLE public.books DROP CONSTRAINT IF EXISTS books_title_key;",
+        )
+        .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Books::Table)
+                    .drop_column(Books::Available)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Books::Table)
+                    .add_column(
+                        ColumnDef::new(Books::Available)
+                            .boolean()
+                            .not_null()
+                            .default(true),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        db.execute_unprepared(
+            "
+            UPDATE public.books b SET available = EXISTS (
+                SELECT 1 FROM public.copies c WHERE c.book_id = b.id AND c.status = 'available'
+            );
+            ALTER TABLE public.books ADD CONSTRAINT books_title_key UNIQUE (title);
+            ",
+        )
+        .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Reservations::Table)
+                    .drop_column(Reservations::CopyId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Copies::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Books {
+    Table,
+    Id,
+    Available,
+}
+
+#[derive(Iden)]
+enum Reservations {
+    Table,
+    CopyId,
+}
+
+#[derive(Iden)]
+enum Copies {
+    Table,
+    Id,
+    BookId,
+    Barcode,
+    Condition,
+    ShelfLocation,
+    Status,
+    CreatedAt,
+    UpdatedAt,
+}
@@ -0,0 +1,147 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Reservations::Table)
+                    .add_column(
+                        ColumnDef::new(Reservations::Status)
+                            .string_len(20)
+                            .not_null()
+                            .default("requested"),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Past return dates were returns, everything else is still out
+        db.execute_unprepared(
+            "
+            UPDATE public.reservations SET status = CASE
+                WHEN return_date IS NOT NULL AND return_date <= NOW() THEN 'returned'
+                ELSE 'checked_out'
+            END;
+            ",
+        )
+        .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-Reservations-Status")
+                    .table(Reservations::Table)
+                    .col(Reservations::Status)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ReservationEvents::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ReservationEvents::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .extra("DEFAULT uuid_generate_v4()".to_owned()),
+                    )
+                    .col(
+                        ColumnDef::new(ReservationEvents::ReservationId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ReservationEvents::FromStatus).string_len(20))
+                    .col(
+                        ColumnDef::new(ReservationEvents::ToStatus)
+                            .string_len(20)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ReservationEvents::ActorId).uuid())
+                    .col(
+                        ColumnDef::new(ReservationEvents::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .extra("DEFAULT NOW()".to_owned()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-ReservationEvents-Reservations_id-Reservations-id")
+                            .from(ReservationEvents::Table, ReservationEvents::ReservationId)
+                            .to(Reservations::Table, Reservations::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-ReservationEvents-Users_id-Users-id")
+                            .from(ReservationEvents::Table, ReservationEvents::ActorId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-ReservationEvents-ReservationId")
+                    .table(ReservationEvents::Table)
+                    .col(ReservationEvents::ReservationId)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ReservationEvents::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Reservations::Table)
+                    .drop_column(Reservations::Status)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Users {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Reservations {
+    Table,
+    Id,
+    Status,
+}
+
+#[derive(Iden)]
+enum ReservationEvents {
+    Table,
+    Id,
+    ReservationId,
+    FromStatus,
+    ToStatus,
+    ActorId,
+    CreatedAt,
+}
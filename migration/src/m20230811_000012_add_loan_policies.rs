@@ -0,0 +1,98 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Books::Table)
+                    .add_column(ColumnDef::new(Books::Category).string_len(64))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Reservations::Table)
+                    .add_column(ColumnDef::new(Reservations::DueDate).timestamp())
+                    .add_column(
+                        ColumnDef::new(Reservations::Renewals)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Open loans were due on the return date their client sent, which now
+        // only records actual returns
+        db.execute_unprepared(
+            "
+            UPDATE public.reservations SET
+                due_date = COALESCE(
+                    return_date,
+                    COALESCE(reservation_date, created_at, NOW()) + INTERVAL '14 days'
+                ),
+                return_date = NULL
+            WHERE status IN ('checked_out', 'overdue');
+            ",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+            UPDATE public.reservations SET return_date = due_date
+            WHERE status IN ('checked_out', 'overdue');
+            ",
+        )
+        .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Reservations::Table)
+                    .drop_column(Reservations::DueDate)
+                    .drop_column(Reservations::Renewals)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Books::Table)
+                    .drop_column(Books::Category)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Books {
+    Table,
+    Category,
+}
+
+#[derive(Iden)]
+enum Reservations {
+    Table,
+    DueDate,
+    Renewals,
+}
@@ -0,0 +1,88 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Holds wait in line without a copy until one is handed to them
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Reservations::Table)
+                    .modify_column(ColumnDef::new(Reservations::CopyId).uuid().null())
+                    .add_column(ColumnDef::new(Reservations::PickupExpiresAt).timestamp())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-Reservations-BookId-Status-CreatedAt")
+                    .table(Reservations::Table)
+                    .col(Reservations::BookId)
+                    .col(Reservations::Status)
+                    .col(Reservations::CreatedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        // A patron holds at most one place in the line of a book
+        db.execute_unprepared(
+            r#"
+            CREATE UNIQUE INDEX "uq-Reservations-UserId-BookId-Requested"
+                ON public.reservations (user_id, book_id) WHERE status = 'requested';
+            "#,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            r#"
+            DROP INDEX IF EXISTS "uq-Reservations-UserId-BookId-Requested";
+            DELETE FROM public.reservations WHERE copy_id IS NULL;
+            "#,
+        )
+        .await?;
+
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-Reservations-BookId-Status-CreatedAt")
+                    .table(Reservations::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Reservations::Table)
+                    .modify_column(ColumnDef::new(Reservations::CopyId).uuid().not_null())
+                    .drop_column(Reservations::PickupExpiresAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Reservations {
+    Table,
+    BookId,
+    CopyId,
+    Status,
+    PickupExpiresAt,
+    CreatedAt,
+}
@@ -0,0 +1,122 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(LedgerEntries::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(LedgerEntries::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .extra("DEFAULT uuid_generate_v4()".to_owned()),
+                    )
+                    .col(ColumnDef::new(LedgerEntries::UserId).uuid().not_null())
+                    .col(ColumnDef::new(LedgerEntries::ReservationId).uuid())
+                    .col(
+                        ColumnDef::new(LedgerEntries::Kind)
+                            .string_len(16)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(LedgerEntries::AmountCents)
+                            .big_integer()
+                            .not_null()
+                            .extra("CHECK (amount_cents > 0)".to_owned()),
+                    )
+                    .col(ColumnDef::new(LedgerEntries::Note).string())
+                    .col(ColumnDef::new(LedgerEntries::ActorId).uuid())
+                    .col(
+                        ColumnDef::new(LedgerEntries::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .extra("DEFAULT NOW()".to_owned()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-LedgerEntries-Users_id-Users-id")
+                            .from(LedgerEntries::Table, LedgerEntries::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-LedgerEntries-Reservations_id-Reservations-id")
+                            .from(LedgerEntries::Table, LedgerEntries::ReservationId)
+                            .to(Reservations::Table, Reservations::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-LedgerEntries-Users_id-Actors-id")
+                            .from(LedgerEntries::Table, LedgerEntries::ActorId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-LedgerEntries-UserId")
+                    .table(LedgerEntries::Table)
+                    .col(LedgerEntries::UserId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-LedgerEntries-ReservationId")
+                    .table(LedgerEntries::Table)
+                    .col(LedgerEntries::ReservationId)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(LedgerEntries::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Users {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Reservations {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum LedgerEntries {
+    Table,
+    Id,
+    UserId,
+    ReservationId,
+    Kind,
+    AmountCents,
+    Note,
+    ActorId,
+    CreatedAt,
+}
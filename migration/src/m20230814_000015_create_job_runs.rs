@@ -0,0 +1,111 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(JobRuns::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(JobRuns::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .extra("DEFAULT uuid_generate_v4()".to_owned()),
+                    )
+                    .col(ColumnDef::new(JobRuns::Job).string_len(32).not_null())
+                    .col(ColumnDef::new(JobRuns::Trigger).string_len(16).not_null())
+                    .col(ColumnDef::new(JobRuns::Status).string_len(16).not_null())
+                    .col(ColumnDef::new(JobRuns::Affected).big_integer())
+                    .col(ColumnDef::new(JobRuns::Error).text())
+                    .col(ColumnDef::new(JobRuns::TriggeredBy).uuid())
+                    .col(
+                        ColumnDef::new(JobRuns::StartedAt)
+                            .timestamp()
+                            .not_null()
+                            .extra("DEFAULT NOW()".to_owned()),
+                    )
+                    .col(ColumnDef::new(JobRuns::FinishedAt).timestamp())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-JobRuns-Users_id-Users-id")
+                            .from(JobRuns::Table, JobRuns::TriggeredBy)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-JobRuns-Job-StartedAt")
+                    .table(JobRuns::Table)
+                    .col(JobRuns::Job)
+                    .col(JobRuns::StartedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Due-date reminders go out once per due date
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Reservations::Table)
+                    .add_column(ColumnDef::new(Reservations::RemindedAt).timestamp())
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Reservations::Table)
+                    .drop_column(Reservations::RemindedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(JobRuns::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Users {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Reservations {
+    Table,
+    RemindedAt,
+}
+
+#[derive(Iden)]
+enum JobRuns {
+    Table,
+    Id,
+    Job,
+    Trigger,
+    Status,
+    Affected,
+    Error,
+    TriggeredBy,
+    StartedAt,
+    FinishedAt,
+}
@@ -0,0 +1,194 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Notification preferences
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(
+                        ColumnDef::new(Users::Locale)
+                            .string_len(8)
+                            .not_null()
+                            .default("en"),
+                    )
+                    .add_column(
+                        ColumnDef::new(Users::NotifyEmail)
+                            .boolean()
+                            .not_null()
+                            .default(true),
+                    )
+                    .add_column(
+                        ColumnDef::new(Users::NotifyWebhook)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .add_column(
+                        ColumnDef::new(Users::NotifyDueDates)
+                            .boolean()
+                            .not_null()
+                            .default(true),
+                    )
+                    .add_column(
+                        ColumnDef::new(Users::NotifyHolds)
+                            .boolean()
+                            .not_null()
+                            .default(true),
+                    )
+                    .add_column(
+                        ColumnDef::new(Users::NotifyFines)
+                            .boolean()
+                            .not_null()
+                            .default(true),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Notifications::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Notifications::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .extra("DEFAULT uuid_generate_v4()".to_owned()),
+                    )
+                    .col(ColumnDef::new(Notifications::UserId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(Notifications::Kind)
+                            .string_len(32)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(Notifications::Channel)
+                            .string_len(16)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(Notifications::Recipient).string().not_null())
+                    .col(ColumnDef::new(Notifications::Subject).string().not_null())
+                    .col(ColumnDef::new(Notifications::Body).text().not_null())
+                    .col(ColumnDef::new(Notifications::Data).json_binary().not_null())
+                    .col(
+                        ColumnDef::new(Notifications::Status)
+                            .string_len(16)
+                            .not_null()
+                            .default("pending"),
+                    )
+                    .col(
+                        ColumnDef::new(Notifications::Attempts)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(Notifications::NextAttemptAt)
+                            .timestamp()
+                            .not_null()
+                            .extra("DEFAULT NOW()".to_owned()),
+                    )
+                    .col(ColumnDef::new(Notifications::LastError).text())
+                    .col(
+                        ColumnDef::new(Notifications::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .extra("DEFAULT NOW()".to_owned()),
+                    )
+                    .col(ColumnDef::new(Notifications::SentAt).timestamp())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-Notifications-Users_id-Users-id")
+                            .from(Notifications::Table, Notifications::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-Notifications-Status-NextAttemptAt")
+                    .table(Notifications::Table)
+                    .col(Notifications::Status)
+                    .col(Notifications::NextAttemptAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-Notifications-UserId")
+                    .table(Notifications::Table)
+                    .col(Notifications::UserId)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Notifications::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .drop_column(Users::Locale)
+                    .drop_column(Users::NotifyEmail)
+                    .drop_column(Users::NotifyWebhook)
+                    .drop_column(Users::NotifyDueDates)
+                    .drop_column(Users::NotifyHolds)
+                    .drop_column(Users::NotifyFines)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Users {
+    Table,
+    Id,
+    Locale,
+    NotifyEmail,
+    NotifyWebhook,
+    NotifyDueDates,
+    NotifyHolds,
+    NotifyFines,
+}
+
+#[derive(Iden)]
+enum Notifications {
+    Table,
+    Id,
+    UserId,
+    Kind,
+    Channel,
+    Recipient,
+    Subject,
+    Body,
+    Data,
+    Status,
+    Attempts,
+    NextAttemptAt,
+    LastError,
+    CreatedAt,
+    SentAt,
+}
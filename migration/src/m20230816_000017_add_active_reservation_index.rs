@@ -0,0 +1,35 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // A copy is held or lent by at most one reservation at a time
+        db.execute_unprepared(
+            "
+            CREATE UNIQUE INDEX \"idx-Reservations-CopyId-Active\" ON public.reservations (copy_id)
+                WHERE status IN ('ready_for_pickup', 'checked_out', 'overdue');
+            ",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+            DROP INDEX IF EXISTS public.\"idx-Reservations-CopyId-Active\";
+            ",
+        )
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,913 @@
+use crate::models::users::Role;
+use chrono::Duration;
+use serde::Deserialize;
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use url::Url;
+
+/// Settings of the whole application, read once at startup.
+///
+/// Values come from the built-in defaults, then the optional TOML file named
+/// by `CONFIG_FILE` (default `config.toml`), then environment variables.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub jwt: JwtConfig,
+    pub accounts: AccountConfig,
+    pub password: PasswordConfig,
+    pub mailer: MailerConfig,
+    pub lockout: LockoutConfig,
+    pub loans: LoanConfig,
+    pub notifications: NotificationConfig,
+    pub scheduler: SchedulerConfig,
+    pub logging: LoggingConfig,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    /// Worker threads; defaults to the number of CPUs.
+    pub workers: Option<usize>,
+    /// Public base URL, used for links sent by email.
+    pub app_url: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            host: "0.0.0.0".to_string(),
+            port: 8000,
+            workers: None,
+            app_url: "http://localhost:8000".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub connect_timeout_seconds: u64,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            url: String::new(),
+            max_connections: 10,
+            min_connections: 1,
+            connect_timeout_seconds: 8,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JwtConfig {
+    /// Directory holding the `<kid>.pem` signing keys.
+    pub keys_dir: String,
+    pub access_token_minutes: i64,
+    pub refresh_token_minutes: i64,
+    /// Lifetime of the token bridging the password and second factor steps.
+    pub mfa_pending_minutes: i64,
+}
+
+impl Default for JwtConfig {
+    fn default() -> Self {
+        JwtConfig {
+            keys_dir: "keys".to_string(),
+            access_token_minutes: 30,
+            refresh_token_minutes: 60 * 24 * 30,
+            mfa_pending_minutes: 5,
+        }
+    }
+}
+
+impl JwtConfig {
+    pub fn access_token_lifetime(&self) -> Duration {
+        Duration::minutes(self.access_token_minutes)
+    }
+
+    pub fn refresh_token_lifetime(&self) -> Duration {
+        Duration::minutes(self.refresh_token_minutes)
+    }
+
+    pub fn mfa_pending_lifetime(&self) -> Duration {
+        Duration::minutes(self.mfa_pending_minutes)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AccountConfig {
+    pub email_verification_minutes: i64,
+    /// Minimum time between two verification emails to the same account.
+    pub verification_resend_seconds: i64,
+    pub password_reset_minutes: i64,
+    /// Minimum time between two password reset emails to the same account.
+    pub password_reset_resend_seconds: i64,
+}
+
+impl Default for AccountConfig {
+    fn default() -> Self {
+        AccountConfig {
+            email_verification_minutes: 60 * 24,
+            verification_resend_seconds: 60,
+            password_reset_minutes: 60,
+            password_reset_resend_seconds: 60,
+        }
+    }
+}
+
+impl AccountConfig {
+    pub fn email_verification_lifetime(&self) -> Duration {
+        Duration::minutes(self.email_verification_minutes)
+    }
+
+    pub fn verification_resend_interval(&self) -> Duration {
+        Duration::seconds(self.verification_resend_seconds)
+    }
+
+    pub fn password_reset_lifetime(&self) -> Duration {
+        Duration::minutes(self.password_reset_minutes)
+    }
+
+    pub fn password_reset_resend_interval(&self) -> Duration {
+        Duration::seconds(self.password_reset_resend_seconds)
+    }
+}
+
+/// Argon2id cost parameters for new password hashes, and the strength rules
+/// new passwords must meet.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasswordConfig {
+    pub memory_cost: u32,
+    pub time_cost: u32,
+    pub parallelism: u32,
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_uppercase: bool,
+    pub require_lowercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+}
+
+impl Default for PasswordConfig {
+    fn default() -> Self {
+        PasswordConfig {
+            memory_cost: argon2::Params::DEFAULT_M_COST,
+            time_cost: argon2::Params::DEFAULT_T_COST,
+            parallelism: argon2::Params::DEFAULT_P_COST,
+            min_length: 8,
+            max_length: 128,
+            require_uppercase: false,
+            require_lowercase: false,
+            require_digit: false,
+            require_symbol: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MailerKind {
+    #[default]
+    Log,
+    File,
+    Smtp,
+}
+
+impl FromStr for MailerKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "log" => Ok(MailerKind::Log),
+            "file" => Ok(MailerKind::File),
+            "smtp" => Ok(MailerKind::Smtp),
+            other => Err(format!(
+                "unknown mailer {}, expected log, file or smtp",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MailerConfig {
+    pub kind: MailerKind,
+    /// Where the `file` mailer drops its emails.
+    pub dir: String,
+    /// Sender of every email, e.g. `BookBorrow <noreply@example.com>`.
+    pub from: String,
+    pub smtp: SmtpConfig,
+}
+
+impl Default for MailerConfig {
+    fn default() -> Self {
+        MailerConfig {
+            kind: MailerKind::Log,
+            dir: "mail".to_string(),
+            from: "BookBorrow <noreply@localhost>".to_string(),
+            smtp: SmtpConfig::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Plain text, for a relay on the same host or network.
+    None,
+    /// Upgrade the connection with `STARTTLS`, usually on port 587.
+    #[default]
+    StartTls,
+    /// TLS from the first byte, usually on port 465.
+    Tls,
+}
+
+impl FromStr for SmtpTls {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "none" => Ok(SmtpTls::None),
+            "starttls" => Ok(SmtpTls::StartTls),
+            "tls" => Ok(SmtpTls::Tls),
+            other => Err(format!(
+                "unknown TLS mode {}, expected none, starttls or tls",
+                other
+            )),
+        }
+    }
+}
+
+/// Server used by the `smtp` mailer.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub timeout_seconds: u64,
+}
+
+impl Default for SmtpConfig {
+    fn default() -> Self {
+        SmtpConfig {
+            host: String::new(),
+            port: 587,
+            tls: SmtpTls::StartTls,
+            username: None,
+            password: None,
+            timeout_seconds: 30,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttemptStoreKind {
+    #[default]
+    Memory,
+    Postgres,
+}
+
+impl FromStr for AttemptStoreKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "memory" => Ok(AttemptStoreKind::Memory),
+            "postgres" => Ok(AttemptStoreKind::Postgres),
+            other => Err(format!(
+                "unknown store {}, expected memory or postgres",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LockoutPolicyConfig {
+    pub free_attempts: u32,
+    pub backoff_base_seconds: i64,
+    pub backoff_max_seconds: i64,
+    pub threshold: u32,
+    pub lockout_seconds: i64,
+    pub window_seconds: i64,
+}
+
+impl LockoutPolicyConfig {
+    fn with_threshold(threshold: u32) -> Self {
+        LockoutPolicyConfig {
+            free_attempts: 3,
+            backoff_base_seconds: 1,
+            backoff_max_seconds: 300,
+            threshold,
+            lockout_seconds: 900,
+            window_seconds: 3600,
+        }
+    }
+}
+
+impl Default for LockoutPolicyConfig {
+    fn default() -> Self {
+        LockoutPolicyConfig::with_threshold(10)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LockoutConfig {
+    pub store: AttemptStoreKind,
+    pub account: LockoutPolicyConfig,
+    pub ip: LockoutPolicyConfig,
+}
+
+impl Default for LockoutConfig {
+    fn default() -> Self {
+        LockoutConfig {
+            store: AttemptStoreKind::Memory,
+            account: LockoutPolicyConfig::with_threshold(10),
+            ip: LockoutPolicyConfig::with_threshold(50),
+        }
+    }
+}
+
+/// Default lending rules.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoanConfig {
+    pub loan_period_days: i64,
+    pub max_renewals: u32,
+    pub max_concurrent_loans: u32,
+    /// Days after the due date before a loan counts as overdue.
+    pub grace_period_days: i64,
+    /// Fine per overdue day past the grace period, in cents.
+    pub daily_fine_cents: i64,
+    /// Cap on the fine of a single loan, in cents.
+    pub max_fine_cents: i64,
+    /// Checkouts are refused while a patron owes more than this, in cents.
+    pub max_balance_cents: i64,
+    /// Days a patron has to collect a copy set aside for their hold.
+    pub hold_pickup_days: i64,
+    /// Overrides for borrower roles and book categories, as `[[loans.rules]]` tables.
+    pub rules: Vec<LoanRule>,
+}
+
+/// Lending rules for a borrower role, a book category, or both; unset values
+/// keep the defaults. When several rules match, the one naming both wins
+/// over one naming either, and later rules win over earlier ones.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoanRule {
+    pub role: Option<Role>,
+    pub category: Option<String>,
+    pub loan_period_days: Option<i64>,
+    pub max_renewals: Option<u32>,
+    pub max_concurrent_loans: Option<u32>,
+    pub grace_period_days: Option<i64>,
+    pub daily_fine_cents: Option<i64>,
+    pub max_fine_cents: Option<i64>,
+}
+
+impl Default for LoanConfig {
+    fn default() -> Self {
+        LoanConfig {
+            loan_period_days: 14,
+            max_renewals: 2,
+            max_concurrent_loans: 5,
+            grace_period_days: 0,
+            daily_fine_cents: 25,
+            max_fine_cents: 1000,
+            max_balance_cents: 1000,
+            hold_pickup_days: 3,
+            rules: Vec::new(),
+        }
+    }
+}
+
+/// Delivery of the notification outbox.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationConfig {
+    /// Where notifications of users who opted into webhooks are posted.
+    pub webhook_url: Option<String>,
+    /// Signs webhook payloads with HMAC-SHA256 when set.
+    pub webhook_secret: Option<String>,
+    pub webhook_timeout_seconds: u64,
+    /// Attempts before a notification is given up on.
+    pub max_attempts: u32,
+    /// Wait after the first failed attempt, doubled after each further one.
+    pub retry_base_seconds: i64,
+    pub retry_max_seconds: i64,
+    /// Notifications delivered per scheduler run.
+    pub batch_size: u64,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        NotificationConfig {
+            webhook_url: None,
+            webhook_secret: None,
+            webhook_timeout_seconds: 10,
+            max_attempts: 6,
+            retry_base_seconds: 60,
+            retry_max_seconds: 6 * 60 * 60,
+            batch_size: 50,
+        }
+    }
+}
+
+impl NotificationConfig {
+    /// Wait before the next attempt of a notification that failed `attempts` times.
+    pub fn retry_delay(&self, attempts: u32) -> Duration {
+        let factor = 1i64
+            .checked_shl(attempts.saturating_sub(1))
+            .unwrap_or(i64::MAX);
+        Duration::seconds(
+            self.retry_base_seconds
+                .saturating_mul(factor)
+                .min(self.retry_max_seconds),
+        )
+    }
+}
+
+/// Background jobs run in every instance; each run is taken by only one of them.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchedulerConfig {
+    /// Whether jobs run periodically; admins can still run them by hand.
+    pub enabled: bool,
+    pub overdue_interval_seconds: u64,
+    pub holds_interval_seconds: u64,
+    pub tokens_interval_seconds: u64,
+    pub reminders_interval_seconds: u64,
+    pub notifications_interval_seconds: u64,
+    /// How long before the due date borrowers are reminded.
+    pub reminder_days_before: i64,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        SchedulerConfig {
+            enabled: true,
+            overdue_interval_seconds: 15 * 60,
+            holds_interval_seconds: 15 * 60,
+            tokens_interval_seconds: 60 * 60,
+            reminders_interval_seconds: 60 * 60,
+            notifications_interval_seconds: 60,
+            reminder_days_before: 2,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// Filter in `env_logger` syntax, e.g. `info` or `info,sqlx=warn`.
+    pub level: String,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            level: "info".to_string(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, std::io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, err) => write!(f, "{}: {}", path.display(), err),
+            ConfigError::Parse(path, err) => write!(f, "{}: {}", path.display(), err),
+            ConfigError::Invalid(errors) => write!(f, "{}", errors.join("\n")),
+        }
+    }
+}
+
+/// Applies environment variables on top of the file, collecting bad values.
+#[derive(Default)]
+struct EnvOverrides {
+    errors: Vec<String>,
+}
+
+impl EnvOverrides {
+    fn parse<T>(&mut self, name: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let value = env::var(name).ok()?;
+        match value.parse::<T>() {
+            Ok(parsed) => Some(parsed),
+            Err(err) => {
+                self.errors
+                    .push(format!("{}: invalid value {:?} ({})", name, value, err));
+                None
+            }
+        }
+    }
+
+    fn set<T>(&mut self, name: &str, target: &mut T)
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        if let Some(value) = self.parse(name) {
+            *target = value;
+        }
+    }
+
+    fn set_optional<T>(&mut self, name: &str, target: &mut Option<T>)
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        if let Some(value) = self.parse(name) {
+            *target = Some(value);
+        }
+    }
+
+    fn lockout_policy(&mut self, prefix: &str, policy: &mut LockoutPolicyConfig) {
+        let name = |suffix: &str| format!("{}_{}", prefix, suffix);
+        self.set(&name("FREE_ATTEMPTS"), &mut policy.free_attempts);
+        self.set(
+            &name("BACKOFF_BASE_SECONDS"),
+            &mut policy.backoff_base_seconds,
+        );
+        self.set(
+            &name("BACKOFF_MAX_SECONDS"),
+            &mut policy.backoff_max_seconds,
+        );
+        self.set(&name("LOCKOUT_THRESHOLD"), &mut policy.threshold);
+        self.set(&name("LOCKOUT_SECONDS"), &mut policy.lockout_seconds);
+        self.set(&name("WINDOW_SECONDS"), &mut policy.window_seconds);
+    }
+}
+
+impl Config {
+    /// Assemble and validate the configuration, failing with every problem found.
+    pub fn load() -> Result<Config, ConfigError> {
+        let path = env::var("CONFIG_FILE").ok();
+        let mut config = match &path {
+            Some(path) => Config::from_file(Path::new(path))?,
+            None if Path::new("config.toml").is_file() => {
+                Config::from_file(Path::new("config.toml"))?
+            }
+            None => Config::default(),
+        };
+        let mut overrides = EnvOverrides::default();
+        config.apply_env(&mut overrides);
+        let mut errors = overrides.errors;
+        errors.extend(config.validate());
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError::Invalid(errors))
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config, ConfigError> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| ConfigError::Read(path.to_path_buf(), err))?;
+        toml::from_str(content.as_str()).map_err(|err| ConfigError::Parse(path.to_path_buf(), err))
+    }
+
+    fn apply_env(&mut self, env: &mut EnvOverrides) {
+        env.set("HOST", &mut self.server.host);
+        env.set("PORT", &mut self.server.port);
+        env.set_optional("WORKERS", &mut self.server.workers);
+        env.set("APP_URL", &mut self.server.app_url);
+
+        env.set("DATABASE_URL", &mut self.database.url);
+        env.set(
+            "DATABASE_MAX_CONNECTIONS",
+            &mut self.database.max_connections,
+        );
+        env.set(
+            "DATABASE_MIN_CONNECTIONS",
+            &mut self.database.min_connections,
+        );
+        env.set(
+            "DATABASE_CONNECT_TIMEOUT",
+            &mut self.database.connect_timeout_seconds,
+        );
+
+        env.set("JWT_KEYS_DIR", &mut self.jwt.keys_dir);
+        env.set("JWT_TIMEOUT", &mut self.jwt.access_token_minutes);
+        env.set("JWT_REFRESH_TIMEOUT", &mut self.jwt.refresh_token_minutes);
+        env.set("MFA_PENDING_TIMEOUT", &mut self.jwt.mfa_pending_minutes);
+
+        env.set(
+            "EMAIL_VERIFICATION_TIMEOUT",
+            &mut self.accounts.email_verification_minutes,
+        );
+        env.set(
+            "EMAIL_VERIFICATION_RESEND_INTERVAL",
+            &mut self.accounts.verification_resend_seconds,
+        );
+        env.set(
+            "PASSWORD_RESET_TIMEOUT",
+            &mut self.accounts.password_reset_minutes,
+        );
+        env.set(
+            "PASSWORD_RESET_RESEND_INTERVAL",
+            &mut self.accounts.password_reset_resend_seconds,
+        );
+
+        env.set("ARGON2_MEMORY_COST", &mut self.password.memory_cost);
+        env.set("ARGON2_TIME_COST", &mut self.password.time_cost);
+        env.set("ARGON2_PARALLELISM", &mut self.password.parallelism);
+        env.set("PASSWORD_MIN_LENGTH", &mut self.password.min_length);
+        env.set("PASSWORD_MAX_LENGTH", &mut self.password.max_length);
+        env.set(
+            "PASSWORD_REQUIRE_UPPERCASE",
+            &mut self.password.require_uppercase,
+        );
+        env.set(
+            "PASSWORD_REQUIRE_LOWERCASE",
+            &mut self.password.require_lowercase,
+        );
+        env.set("PASSWORD_REQUIRE_DIGIT", &mut self.password.require_digit);
+        env.set("PASSWORD_REQUIRE_SYMBOL", &mut self.password.require_symbol);
+
+        env.set("MAILER", &mut self.mailer.kind);
+        env.set("MAILER_DIR", &mut self.mailer.dir);
+        env.set("MAILER_FROM", &mut self.mailer.from);
+        env.set("SMTP_HOST", &mut self.mailer.smtp.host);
+        env.set("SMTP_PORT", &mut self.mailer.smtp.port);
+        env.set("SMTP_TLS", &mut self.mailer.smtp.tls);
+        env.set_optional("SMTP_USERNAME", &mut self.mailer.smtp.username);
+        env.set_optional("SMTP_PASSWORD", &mut self.mailer.smtp.password);
+        env.set("SMTP_TIMEOUT", &mut self.mailer.smtp.timeout_seconds);
+
+        env.set("LOGIN_ATTEMPT_STORE", &mut self.lockout.store);
+        env.lockout_policy("LOGIN_ACCOUNT", &mut self.lockout.account);
+        env.lockout_policy("LOGIN_IP", &mut self.lockout.ip);
+
+        env.set("LOAN_PERIOD_DAYS", &mut self.loans.loan_period_days);
+        env.set("LOAN_MAX_RENEWALS", &mut self.loans.max_renewals);
+        env.set("LOAN_MAX_CONCURRENT", &mut self.loans.max_concurrent_loans);
+        env.set("LOAN_GRACE_DAYS", &mut self.loans.grace_period_days);
+        env.set("LOAN_DAILY_FINE_CENTS", &mut self.loans.daily_fine_cents);
+        env.set("LOAN_MAX_FINE_CENTS", &mut self.loans.max_fine_cents);
+        env.set("LOAN_MAX_BALANCE_CENTS", &mut self.loans.max_balance_cents);
+        env.set("LOAN_HOLD_PICKUP_DAYS", &mut self.loans.hold_pickup_days);
+
+        env.set_optional(
+            "NOTIFICATIONS_WEBHOOK_URL",
+            &mut self.notifications.webhook_url,
+        );
+        env.set_optional(
+            "NOTIFICATIONS_WEBHOOK_SECRET",
+            &mut self.notifications.webhook_secret,
+        );
+        env.set(
+            "NOTIFICATIONS_WEBHOOK_TIMEOUT",
+            &mut self.notifications.webhook_timeout_seconds,
+        );
+        env.set(
+            "NOTIFICATIONS_MAX_ATTEMPTS",
+            &mut self.notifications.max_attempts,
+        );
+        env.set(
+            "NOTIFICATIONS_RETRY_BASE_SECONDS",
+            &mut self.notifications.retry_base_seconds,
+        );
+        env.set(
+            "NOTIFICATIONS_RETRY_MAX_SECONDS",
+            &mut self.notifications.retry_max_seconds,
+        );
+        env.set(
+            "NOTIFICATIONS_BATCH_SIZE",
+            &mut self.notifications.batch_size,
+        );
+
+        env.set("SCHEDULER_ENABLED", &mut self.scheduler.enabled);
+        env.set(
+            "SCHEDULER_OVERDUE_INTERVAL",
+            &mut self.scheduler.overdue_interval_seconds,
+        );
+        env.set(
+            "SCHEDULER_HOLDS_INTERVAL",
+            &mut self.scheduler.holds_interval_seconds,
+        );
+        env.set(
+            "SCHEDULER_TOKENS_INTERVAL",
+            &mut self.scheduler.tokens_interval_seconds,
+        );
+        env.set(
+            "SCHEDULER_REMINDERS_INTERVAL",
+            &mut self.scheduler.reminders_interval_seconds,
+        );
+        env.set(
+            "SCHEDULER_NOTIFICATIONS_INTERVAL",
+            &mut self.scheduler.notifications_interval_seconds,
+        );
+        env.set(
+            "REMINDER_DAYS_BEFORE",
+            &mut self.scheduler.reminder_days_before,
+        );
+
+        env.set("LOG_LEVEL", &mut self.logging.level);
+    }
+
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mut check = |valid: bool, message: &str| {
+            if !valid {
+                errors.push(message.to_string());
+            }
+        };
+        check(
+            !self.database.url.is_empty(),
+            "database.url (DATABASE_URL) must be set",
+        );
+        check(
+            self.database.max_connections > 0,
+            "database.max_connections must be positive",
+        );
+        check(
+            self.database.min_connections <= self.database.max_connections,
+            "database.min_connections can't exceed database.max_connections",
+        );
+        check(
+            self.server.workers != Some(0),
+            "server.workers must be positive",
+        );
+        check(
+            self.jwt.access_token_minutes > 0,
+            "jwt.access_token_minutes (JWT_TIMEOUT) must be positive",
+        );
+        check(
+            self.jwt.refresh_token_minutes > self.jwt.access_token_minutes,
+            "jwt.refresh_token_minutes must exceed jwt.access_token_minutes",
+        );
+        check(
+            self.jwt.mfa_pending_minutes > 0,
+            "jwt.mfa_pending_minutes must be positive",
+        );
+        check(
+            self.accounts.email_verification_minutes > 0,
+            "accounts.email_verification_minutes must be positive",
+        );
+        check(
+            self.accounts.verification_resend_seconds >= 0,
+            "accounts.verification_resend_seconds can't be negative",
+        );
+        check(
+            self.accounts.password_reset_minutes > 0,
+            "accounts.password_reset_minutes must be positive",
+        );
+        check(
+            self.accounts.password_reset_resend_seconds >= 0,
+            "accounts.password_reset_resend_seconds can't be negative",
+        );
+        check(
+            argon2::Params::new(
+                self.password.memory_cost,
+                self.password.time_cost,
+                self.password.parallelism,
+                None,
+            )
+            .is_ok(),
+            "password: invalid Argon2 parameters",
+        );
+        check(
+            self.password.min_length > 0 && self.password.min_length <= self.password.max_length,
+            "password.min_length must be positive and can't exceed password.max_length",
+        );
+        for (name, policy) in [
+            ("lockout.account", &self.lockout.account),
+            ("lockout.ip", &self.lockout.ip),
+        ] {
+            check(
+                policy.threshold > 0 && policy.window_seconds > 0,
+                format!("{}: threshold and window_seconds must be positive", name).as_str(),
+            );
+            check(
+                policy.backoff_base_seconds > 0
+                    && policy.backoff_max_seconds > 0
+                    && policy.lockout_seconds > 0,
+                format!(
+                    "{}: backoff_base_seconds, backoff_max_seconds and lockout_seconds must be positive",
+                    name
+                )
+                .as_str(),
+            );
+            check(
+                policy.backoff_base_seconds <= policy.backoff_max_seconds,
+                format!(
+                    "{}: backoff_base_seconds can't exceed backoff_max_seconds",
+                    name
+                )
+                .as_str(),
+            );
+        }
+        check(
+            self.loans.loan_period_days > 0,
+            "loans.loan_period_days must be positive",
+        );
+        check(
+            self.loans.max_concurrent_loans > 0,
+            "loans.max_concurrent_loans must be positive",
+        );
+        check(
+            self.loans.grace_period_days >= 0,
+            "loans.grace_period_days can't be negative",
+        );
+        check(
+            self.loans.daily_fine_cents >= 0 && self.loans.max_fine_cents >= 0,
+            "loans.daily_fine_cents and loans.max_fine_cents can't be negative",
+        );
+        check(
+            self.loans.max_balance_cents >= 0,
+            "loans.max_balance_cents can't be negative",
+        );
+        check(
+            self.loans.hold_pickup_days > 0,
+            "loans.hold_pickup_days must be positive",
+        );
+        check(
+            self.mailer.kind != MailerKind::Smtp || !self.mailer.smtp.host.is_empty(),
+            "mailer.smtp.host (SMTP_HOST) must be set for the smtp mailer",
+        );
+        check(
+            self.mailer.smtp.username.is_some() == self.mailer.smtp.password.is_some(),
+            "mailer.smtp: set both username and password, or neither",
+        );
+        check(
+            match &self.notifications.webhook_url {
+                Some(url) => {
+                    matches!(Url::parse(url), Ok(url) if matches!(url.scheme(), "http" | "https"))
+                }
+                None => true,
+            },
+            "notifications.webhook_url must be an http or https URL",
+        );
+        check(
+            self.notifications.max_attempts > 0 && self.notifications.batch_size > 0,
+            "notifications.max_attempts and notifications.batch_size must be positive",
+        );
+        check(
+            self.notifications.retry_base_seconds > 0
+                && self.notifications.retry_max_seconds >= self.notifications.retry_base_seconds,
+            "notifications.retry_base_seconds must be positive and can't exceed retry_max_seconds",
+        );
+        check(
+            self.scheduler.overdue_interval_seconds > 0
+                && self.scheduler.holds_interval_seconds > 0
+                && self.scheduler.tokens_interval_seconds > 0
+                && self.scheduler.reminders_interval_seconds > 0
+                && self.scheduler.notifications_interval_seconds > 0,
+            "scheduler: job intervals must be positive",
+        );
+        check(
+            self.scheduler.reminder_days_before > 0,
+            "scheduler.reminder_days_before must be positive",
+        );
+        for (index, rule) in self.loans.rules.iter().enumerate() {
+            check(
+                rule.role.is_some() || rule.category.is_some(),
+                format!("loans.rules[{}]: set a role, a category or both", index).as_str(),
+            );
+            check(
+                !matches!(rule.loan_period_days, Some(days) if days <= 0)
+                    && rule.max_concurrent_loans != Some(0)
+                    && !matches!(rule.grace_period_days, Some(days) if days < 0)
+                    && !matches!(rule.daily_fine_cents, Some(cents) if cents < 0)
+                    && !matches!(rule.max_fine_cents, Some(cents) if cents < 0),
+                format!("loans.rules[{}]: invalid limits", index).as_str(),
+            );
+        }
+        errors
+    }
+
+    /// Absolute link to a path of this application, as included in emails.
+    pub fn app_link(&self, path: &str) -> String {
+        format!("{}{}", self.server.app_url.trim_end_matches('/'), path)
+    }
+}
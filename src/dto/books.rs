@@ -0,0 +1,174 @@
+use crate::models::books::{
+    ActiveModel as ActiveModelBook, Column as ColumnBook, Entity as EntityBook, Model as ModelBook,
+};
+use crate::services::availability::{has_available_copy, Availability};
+use crate::services::search::prefix_query;
+use crate::utils::pagination::contains;
+use crate::utils::validation::{not_blank, publication_year};
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::sea_query::{extension::postgres::PgExpr, Expr, Func};
+use sea_orm::{ColumnTrait, Condition, QueryFilter, Select, Set};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+/// Payload for creating or replacing a book.
+#[derive(Debug, Deserialize, Validate)]
+pub struct BookRequest {
+    #[validate(
+        custom = "not_blank",
+        length(max = 255, message = "Must be at most 255 characters.")
+    )]
+    pub title: String,
+    #[validate(
+        custom = "not_blank",
+        length(max = 255, message = "Must be at most 255 characters.")
+    )]
+    pub author: String,
+    #[validate(custom = "publication_year")]
+    pub year_of_publication: i32,
+    #[validate(
+        custom = "not_blank",
+        length(max = 64, message = "Must be at most 64 characters.")
+    )]
+    pub category: Option<String>,
+}
+
+impl From<BookRequest> for ActiveModelBook {
+    fn from(request: BookRequest) -> Self {
+        ActiveModelBook {
+            id: Set(Uuid::new_v4()),
+            title: Set(request.title),
+            author: Set(request.author),
+            year_of_publication: Set(request.year_of_publication),
+            category: Set(request.category),
+            created_at: Set(Some(Utc::now().naive_utc())),
+            updated_at: Set(None),
+        }
+    }
+}
+
+impl BookRequest {
+    /// Copies the request over an existing book.
+    pub fn apply_to(self, model: &mut ActiveModelBook) {
+        model.title = Set(self.title);
+        model.author = Set(self.author);
+        model.year_of_publication = Set(self.year_of_publication);
+        model.category = Set(self.category);
+        model.updated_at = Set(Some(Utc::now().naive_utc()));
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BookResponse {
+    pub id: Uuid,
+    pub title: String,
+    pub author: String,
+    pub year_of_publication: i32,
+    pub category: Option<String>,
+    /// Whether at least one copy is on the shelf.
+    pub available: bool,
+    #[serde(flatten)]
+    pub availability: Availability,
+    pub created_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+impl BookResponse {
+    pub fn new(book: ModelBook, availability: Availability) -> Self {
+        BookResponse {
+            id: book.id,
+            title: book.title,
+            author: book.author,
+            year_of_publication: book.year_of_publication,
+            category: book.category,
+            available: availability.is_available(),
+            availability,
+            created_at: book.created_at,
+            updated_at: book.updated_at,
+        }
+    }
+}
+
+/// Filters for the book listing.
+#[derive(Debug, Deserialize, Validate)]
+#[validate(schema(function = "year_range"))]
+pub struct BookFilter {
+    /// Case-insensitive substring of the author.
+    pub author: Option<String>,
+    pub year_from: Option<i32>,
+    pub year_to: Option<i32>,
+    /// Exact category, ignoring case.
+    pub category: Option<String>,
+    /// Books with (or without) a copy on the shelf.
+    pub available: Option<bool>,
+}
+
+fn year_range(filter: &BookFilter) -> Result<(), ValidationError> {
+    if let (Some(from), Some(to)) = (filter.year_from, filter.year_to) {
+        if from > to {
+            let mut err = ValidationError::new("year_range");
+            err.message = Some("year_from can't be after year_to.".into());
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+impl BookFilter {
+    pub fn apply(&self, select: Select<EntityBook>) -> Select<EntityBook> {
+        let mut condition = Condition::all();
+        if let Some(author) = &self.author {
+            condition = condition.add(Expr::col(ColumnBook::Author).ilike(contains(author)));
+        }
+        if let Some(category) = &self.category {
+            condition = condition.add(
+                Expr::expr(Func::lower(Expr::col(ColumnBook::Category)))
+                    .eq(category.to_lowercase()),
+            );
+        }
+        if let Some(from) = self.year_from {
+            condition = condition.add(ColumnBook::YearOfPublication.gte(from));
+        }
+        if let Some(to) = self.year_to {
+            condition = condition.add(ColumnBook::YearOfPublication.lte(to));
+        }
+        match self.available {
+            Some(true) => condition = condition.add(has_available_copy(ColumnBook::Id)),
+            Some(false) => condition = condition.add(has_available_copy(ColumnBook::Id).not()),
+            None => {}
+        }
+        select.filter(condition)
+    }
+}
+
+/// Columns the book listing may be sorted by.
+pub const BOOK_SORTABLE: &[(&str, ColumnBook)] = &[
+    ("title", ColumnBook::Title),
+    ("author", ColumnBook::Author),
+    ("year_of_publication", ColumnBook::YearOfPublication),
+    ("created_at", ColumnBook::CreatedAt),
+];
+
+/// Catalog search over titles and authors.
+#[derive(Debug, Deserialize, Validate)]
+pub struct SearchQuery {
+    #[validate(
+        custom = "searchable",
+        length(max = 200, message = "Must be at most 200 characters.")
+    )]
+    pub q: String,
+    #[validate(range(min = 1, message = "Must be at least 1."))]
+    pub page: Option<u64>,
+    #[validate(range(min = 1, max = 100, message = "Must be between 1 and 100."))]
+    pub per_page: Option<u64>,
+}
+
+fn searchable(q: &str) -> Result<(), ValidationError> {
+    if prefix_query(q).is_none() {
+        let mut err = ValidationError::new("searchable");
+        err.message = Some("Must contain at least one word.".into());
+        return Err(err);
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use crate::models::copies::{
+    ActiveModel as ActiveModelCopy, CopyCondition, CopyStatus, Model as ModelCopy,
+};
+use crate::utils::validation::not_blank;
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::Set;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+/// Payload for adding or replacing a copy of a book.
+#[derive(Debug, Deserialize, Validate)]
+pub struct CopyRequest {
+    #[validate(
+        custom = "not_blank",
+        length(max = 64, message = "Must be at most 64 characters.")
+    )]
+    pub barcode: String,
+    #[serde(default)]
+    pub condition: CopyCondition,
+    #[validate(length(max = 64, message = "Must be at most 64 characters."))]
+    pub shelf_location: Option<String>,
+    /// Left as stored when missing.
+    pub status: Option<CopyStatus>,
+}
+
+impl CopyRequest {
+    pub fn into_active_model(self, book_id: Uuid) -> ActiveModelCopy {
+        ActiveModelCopy {
+            id: Set(Uuid::new_v4()),
+            book_id: Set(book_id),
+            barcode: Set(self.barcode),
+            condition: Set(self.condition),
+            shelf_location: Set(self.shelf_location),
+            status: Set(self.status.unwrap_or_default()),
+            created_at: Set(Some(Utc::now().naive_utc())),
+            updated_at: Set(None),
+        }
+    }
+
+    /// Copies the request over an existing copy.
+    pub fn apply_to(self, model: &mut ActiveModelCopy) {
+        model.barcode = Set(self.barcode);
+        model.condition = Set(self.condition);
+        model.shelf_location = Set(self.shelf_location);
+        if let Some(status) = self.status {
+            model.status = Set(status);
+        }
+        model.updated_at = Set(Some(Utc::now().naive_utc()));
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CopyResponse {
+    pub id: Uuid,
+    pub book_id: Uuid,
+    pub barcode: String,
+    pub condition: CopyCondition,
+    pub shelf_location: Option<String>,
+    pub status: CopyStatus,
+    pub created_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+impl From<ModelCopy> for CopyResponse {
+    fn from(copy: ModelCopy) -> Self {
+        CopyResponse {
+            id: copy.id,
+            book_id: copy.book_id,
+            barcode: copy.barcode,
+            condition: copy.condition,
+            shelf_location: copy.shelf_location,
+            status: copy.status,
+            created_at: copy.created_at,
+            updated_at: copy.updated_at,
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use crate::dto::reservations::ReservationResponse;
+use crate::models::reservations::Model as ModelReservation;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct HoldsQuery {
+    /// Whose holds to list; staff only, defaults to the caller.
+    pub user_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HoldResponse {
+    #[serde(flatten)]
+    pub reservation: ReservationResponse,
+    /// 1-based place in the book's line while waiting for a copy.
+    pub position: Option<u64>,
+}
+
+impl HoldResponse {
+    pub fn new(hold: ModelReservation, position: Option<u64>) -> Self {
+        HoldResponse {
+            reservation: ReservationResponse::from(hold),
+            position,
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use crate::models::job_runs::{
+    Column as ColumnJobRun, Entity as EntityJobRun, Job, JobStatus, JobTrigger,
+    Model as ModelJobRun,
+};
+use chrono::NaiveDateTime;
+use sea_orm::{ColumnTrait, Condition, QueryFilter, Select};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize)]
+pub struct JobRunResponse {
+    pub id: Uuid,
+    pub job: Job,
+    pub trigger: JobTrigger,
+    pub status: JobStatus,
+    pub affected: Option<i64>,
+    pub error: Option<String>,
+    pub triggered_by: Option<Uuid>,
+    pub started_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+impl From<ModelJobRun> for JobRunResponse {
+    fn from(run: ModelJobRun) -> Self {
+        JobRunResponse {
+            id: run.id,
+            job: run.job,
+            trigger: run.trigger,
+            status: run.status,
+            affected: run.affected,
+            error: run.error,
+            triggered_by: run.triggered_by,
+            started_at: run.started_at,
+            finished_at: run.finished_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JobRunFilter {
+    pub job: Option<Job>,
+    pub status: Option<JobStatus>,
+}
+
+impl JobRunFilter {
+    pub fn apply(&self, select: Select<EntityJobRun>) -> Select<EntityJobRun> {
+        let mut condition = Condition::all();
+        if let Some(job) = self.job {
+            condition = condition.add(ColumnJobRun::Job.eq(job));
+        }
+        if let Some(status) = self.status {
+            condition = condition.add(ColumnJobRun::Status.eq(status));
+        }
+        select.filter(condition)
+    }
+}
+
+/// Columns the job run listing may be sorted by.
+pub const JOB_RUN_SORTABLE: &[(&str, ColumnJobRun)] = &[
+    ("started_at", ColumnJobRun::StartedAt),
+    ("finished_at", ColumnJobRun::FinishedAt),
+];
@@ -0,0 +1,61 @@
+use crate::models::ledger_entries::{
+    Column as ColumnLedgerEntry, LedgerKind, Model as ModelLedgerEntry,
+};
+use crate::services::fines::Balance;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+/// Payload for recording a payment or a waiver.
+#[derive(Debug, Deserialize, Validate)]
+pub struct LedgerEntryRequest {
+    #[validate(range(min = 1, message = "Must be at least 1."))]
+    pub amount_cents: i64,
+    /// The loan whose fine is settled, if any.
+    pub reservation_id: Option<Uuid>,
+    #[validate(length(max = 255, message = "Must be at most 255 characters."))]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LedgerEntryResponse {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub reservation_id: Option<Uuid>,
+    pub kind: LedgerKind,
+    pub amount_cents: i64,
+    pub note: Option<String>,
+    pub actor_id: Option<Uuid>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<ModelLedgerEntry> for LedgerEntryResponse {
+    fn from(entry: ModelLedgerEntry) -> Self {
+        LedgerEntryResponse {
+            id: entry.id,
+            user_id: entry.user_id,
+            reservation_id: entry.reservation_id,
+            kind: entry.kind,
+            amount_cents: entry.amount_cents,
+            note: entry.note,
+            actor_id: entry.actor_id,
+            created_at: entry.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BalanceResponse {
+    pub user_id: Uuid,
+    #[serde(flatten)]
+    pub balance: Balance,
+    /// Whether the balance is too high for new checkouts.
+    pub blocked: bool,
+}
+
+/// Columns the ledger may be sorted by.
+pub const LEDGER_SORTABLE: &[(&str, ColumnLedgerEntry)] = &[
+    ("created_at", ColumnLedgerEntry::CreatedAt),
+    ("amount_cents", ColumnLedgerEntry::AmountCents),
+];
@@ -0,0 +1,8 @@
+pub mod books;
+pub mod copies;
+pub mod holds;
+pub mod jobs;
+pub mod ledger;
+pub mod notifications;
+pub mod reservations;
+pub mod users;
@@ -0,0 +1,143 @@
+use crate::models::notifications::{
+    Channel, Column as ColumnNotification, Entity as EntityNotification,
+    Model as ModelNotification, NotificationKind, NotificationStatus,
+};
+use crate::models::users::{ActiveModel as ActiveModelUser, Model as ModelUser};
+use crate::utils::validation::supported_locale;
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::{ColumnTrait, Condition, QueryFilter, Select, Set};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+/// Payload for changing notification preferences; fields left out keep their value.
+#[derive(Debug, Deserialize, Validate)]
+pub struct NotificationPreferencesRequest {
+    #[validate(custom = "supported_locale")]
+    pub locale: Option<String>,
+    pub email: Option<bool>,
+    /// Only available when the library configured a webhook.
+    pub webhook: Option<bool>,
+    /// Reminders before due dates and overdue notices.
+    pub due_dates: Option<bool>,
+    pub holds: Option<bool>,
+    pub fines: Option<bool>,
+}
+
+impl NotificationPreferencesRequest {
+    /// Copies the preferences that were given, keeping the others.
+    pub fn apply_to(self, model: &mut ActiveModelUser) {
+        if let Some(locale) = self.locale {
+            model.locale = Set(locale);
+        }
+        if let Some(email) = self.email {
+            model.notify_email = Set(email);
+        }
+        if let Some(webhook) = self.webhook {
+            model.notify_webhook = Set(webhook);
+        }
+        if let Some(due_dates) = self.due_dates {
+            model.notify_due_dates = Set(due_dates);
+        }
+        if let Some(holds) = self.holds {
+            model.notify_holds = Set(holds);
+        }
+        if let Some(fines) = self.fines {
+            model.notify_fines = Set(fines);
+        }
+        model.updated_at = Set(Some(Utc::now().naive_utc()));
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct NotificationPreferencesResponse {
+    pub locale: String,
+    pub email: bool,
+    pub webhook: bool,
+    pub due_dates: bool,
+    pub holds: bool,
+    pub fines: bool,
+}
+
+impl From<ModelUser> for NotificationPreferencesResponse {
+    fn from(user: ModelUser) -> Self {
+        NotificationPreferencesResponse {
+            locale: user.locale,
+            email: user.notify_email,
+            webhook: user.notify_webhook,
+            due_dates: user.notify_due_dates,
+            holds: user.notify_holds,
+            fines: user.notify_fines,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct NotificationResponse {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub kind: NotificationKind,
+    pub channel: Channel,
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+    pub status: NotificationStatus,
+    pub attempts: i32,
+    pub next_attempt_at: NaiveDateTime,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub sent_at: Option<NaiveDateTime>,
+}
+
+impl From<ModelNotification> for NotificationResponse {
+    fn from(notification: ModelNotification) -> Self {
+        NotificationResponse {
+            id: notification.id,
+            user_id: notification.user_id,
+            kind: notification.kind,
+            channel: notification.channel,
+            recipient: notification.recipient,
+            subject: notification.subject,
+            body: notification.body,
+            status: notification.status,
+            attempts: notification.attempts,
+            next_attempt_at: notification.next_attempt_at,
+            last_error: notification.last_error,
+            created_at: notification.created_at,
+            sent_at: notification.sent_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NotificationFilter {
+    pub user_id: Option<Uuid>,
+    pub kind: Option<NotificationKind>,
+    pub channel: Option<Channel>,
+    pub status: Option<NotificationStatus>,
+}
+
+impl NotificationFilter {
+    pub fn apply(&self, select: Select<EntityNotification>) -> Select<EntityNotification> {
+        let mut condition = Condition::all();
+        if let Some(user_id) = self.user_id {
+            condition = condition.add(ColumnNotification::UserId.eq(user_id));
+        }
+        if let Some(kind) = self.kind {
+            condition = condition.add(ColumnNotification::Kind.eq(kind));
+        }
+        if let Some(channel) = self.channel {
+            condition = condition.add(ColumnNotification::Channel.eq(channel));
+        }
+        if let Some(status) = self.status {
+            condition = condition.add(ColumnNotification::Status.eq(status));
+        }
+        select.filter(condition)
+    }
+}
+
+/// Columns the notification listing may be sorted by.
+pub const NOTIFICATION_SORTABLE: &[(&str, ColumnNotification)] = &[
+    ("created_at", ColumnNotification::CreatedAt),
+    ("next_attempt_at", ColumnNotification::NextAttemptAt),
+];
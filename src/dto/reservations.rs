@@ -0,0 +1,168 @@
+use crate::models::reservation_events::Model as ModelReservationEvent;
+use crate::models::reservations::{
+    ActiveModel as ActiveModelReservation, Column as ColumnReservation,
+    Entity as EntityReservation, Model as ModelReservation, ReservationStatus,
+};
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::{ColumnTrait, Condition, QueryFilter, Select, Set};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+/// Payload for reserving a copy.
+///
+/// Name either a specific `copy_id` or a `book_id`, in which case any copy of
+/// the book on the shelf is set aside, or a place in its hold queue taken
+/// when none is.
+#[derive(Debug, Deserialize, Validate)]
+#[validate(schema(function = "copy_or_book"))]
+pub struct ReservationRequest {
+    pub user_id: Uuid,
+    pub copy_id: Option<Uuid>,
+    pub book_id: Option<Uuid>,
+}
+
+fn copy_or_book(request: &ReservationRequest) -> Result<(), ValidationError> {
+    if request.copy_id.is_some() == request.book_id.is_some() {
+        let mut err = ValidationError::new("copy_or_book");
+        err.message = Some("Provide exactly one of copy_id and book_id.".into());
+        return Err(err);
+    }
+    Ok(())
+}
+
+impl ReservationRequest {
+    /// Build the reservation for the copy picked for it, if any.
+    pub fn into_active_model(
+        self,
+        copy_id: Option<Uuid>,
+        book_id: Uuid,
+        status: ReservationStatus,
+    ) -> ActiveModelReservation {
+        ActiveModelReservation {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(self.user_id),
+            book_id: Set(book_id),
+            copy_id: Set(copy_id),
+            reservation_date: Set(Some(Utc::now().naive_utc())),
+            return_date: Set(None),
+            status: Set(status),
+            due_date: Set(None),
+            renewals: Set(0),
+            pickup_expires_at: Set(None),
+            reminded_at: Set(None),
+            created_at: Set(Some(Utc::now().naive_utc())),
+            updated_at: Set(None),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReservationResponse {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub book_id: Uuid,
+    pub copy_id: Option<Uuid>,
+    pub reservation_date: Option<NaiveDateTime>,
+    pub return_date: Option<NaiveDateTime>,
+    pub status: ReservationStatus,
+    pub due_date: Option<NaiveDateTime>,
+    pub renewals: i32,
+    pub pickup_expires_at: Option<NaiveDateTime>,
+    pub created_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+impl From<ModelReservation> for ReservationResponse {
+    fn from(reservation: ModelReservation) -> Self {
+        ReservationResponse {
+            id: reservation.id,
+            user_id: reservation.user_id,
+            book_id: reservation.book_id,
+            copy_id: reservation.copy_id,
+            reservation_date: reservation.reservation_date,
+            return_date: reservation.return_date,
+            status: reservation.status,
+            due_date: reservation.due_date,
+            renewals: reservation.renewals,
+            pickup_expires_at: reservation.pickup_expires_at,
+            created_at: reservation.created_at,
+            updated_at: reservation.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReservationEventResponse {
+    pub from_status: Option<ReservationStatus>,
+    pub to_status: ReservationStatus,
+    pub actor_id: Option<Uuid>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<ModelReservationEvent> for ReservationEventResponse {
+    fn from(event: ModelReservationEvent) -> Self {
+        ReservationEventResponse {
+            from_status: event.from_status,
+            to_status: event.to_status,
+            actor_id: event.actor_id,
+            created_at: event.created_at,
+        }
+    }
+}
+
+/// Filters for the reservation listing; the date range applies to `reservation_date`.
+#[derive(Debug, Deserialize, Validate)]
+#[validate(schema(function = "date_range"))]
+pub struct ReservationFilter {
+    pub user_id: Option<Uuid>,
+    pub book_id: Option<Uuid>,
+    pub copy_id: Option<Uuid>,
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+    pub status: Option<ReservationStatus>,
+}
+
+fn date_range(filter: &ReservationFilter) -> Result<(), ValidationError> {
+    if let (Some(from), Some(to)) = (filter.from, filter.to) {
+        if from > to {
+            let mut err = ValidationError::new("date_range");
+            err.message = Some("from can't be after to.".into());
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+impl ReservationFilter {
+    pub fn apply(&self, select: Select<EntityReservation>) -> Select<EntityReservation> {
+        let mut condition = Condition::all();
+        if let Some(user_id) = self.user_id {
+            condition = condition.add(ColumnReservation::UserId.eq(user_id));
+        }
+        if let Some(book_id) = self.book_id {
+            condition = condition.add(ColumnReservation::BookId.eq(book_id));
+        }
+        if let Some(copy_id) = self.copy_id {
+            condition = condition.add(ColumnReservation::CopyId.eq(copy_id));
+        }
+        if let Some(from) = self.from {
+            condition = condition.add(ColumnReservation::ReservationDate.gte(from));
+        }
+        if let Some(to) = self.to {
+            condition = condition.add(ColumnReservation::ReservationDate.lte(to));
+        }
+        if let Some(status) = self.status {
+            condition = condition.add(ColumnReservation::Status.eq(status));
+        }
+        select.filter(condition)
+    }
+}
+
+/// Columns the reservation listing may be sorted by.
+pub const RESERVATION_SORTABLE: &[(&str, ColumnReservation)] = &[
+    ("reservation_date", ColumnReservation::ReservationDate),
+    ("return_date", ColumnReservation::ReturnDate),
+    ("due_date", ColumnReservation::DueDate),
+    ("created_at", ColumnReservation::CreatedAt),
+];
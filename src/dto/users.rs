@@ -0,0 +1,128 @@
+use crate::config::PasswordConfig;
+use crate::models::users::{ActiveModel as ActiveModelUser, Model as ModelUser, Role};
+use crate::services::notifications::DEFAULT_LOCALE;
+use crate::utils::validation::password_strength;
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::Set;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+/// Payload for an admin creating an account.
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateUserRequest {
+    #[validate(email(message = "Must be a valid email address."))]
+    pub email: String,
+    #[validate(custom(function = "password_strength", arg = "&'v_a PasswordConfig"))]
+    pub password: String,
+    #[serde(default)]
+    pub active: bool,
+    #[validate(length(max = 255, message = "Must be at most 255 characters."))]
+    pub name: Option<String>,
+    #[validate(length(max = 32, message = "Must be at most 32 characters."))]
+    pub phone: Option<String>,
+    #[validate(length(max = 255, message = "Must be at most 255 characters."))]
+    pub address: Option<String>,
+    #[serde(default)]
+    pub role: Role,
+}
+
+impl CreateUserRequest {
+    /// Build the new account, storing `password_hash` in place of the plain password.
+    pub fn into_active_model(self, password_hash: String) -> ActiveModelUser {
+        ActiveModelUser {
+            id: Set(Uuid::new_v4()),
+            email: Set(self.email),
+            password: Set(password_hash),
+            active: Set(self.active),
+            name: Set(self.name),
+            phone: Set(self.phone),
+            address: Set(self.address),
+            role: Set(self.role),
+            password_changed_at: Set(None),
+            suspended_at: Set(None),
+            suspension_reason: Set(None),
+            totp_secret: Set(None),
+            totp_enabled: Set(false),
+            totp_last_step: Set(None),
+            locale: Set(DEFAULT_LOCALE.to_string()),
+            notify_email: Set(true),
+            notify_webhook: Set(false),
+            notify_due_dates: Set(true),
+            notify_holds: Set(true),
+            notify_fines: Set(true),
+            created_at: Set(Some(Utc::now().naive_utc())),
+            updated_at: Set(None),
+        }
+    }
+}
+
+/// Payload for updating an account's profile; fields left out keep their value.
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateUserRequest {
+    #[validate(custom(function = "password_strength", arg = "&'v_a PasswordConfig"))]
+    pub password: Option<String>,
+    /// Required with `password`, unless an admin is changing it.
+    pub current_password: Option<String>,
+    #[validate(length(max = 255, message = "Must be at most 255 characters."))]
+    pub name: Option<String>,
+    #[validate(length(max = 32, message = "Must be at most 32 characters."))]
+    pub phone: Option<String>,
+    #[validate(length(max = 255, message = "Must be at most 255 characters."))]
+    pub address: Option<String>,
+    /// Only honoured for admins.
+    pub role: Option<Role>,
+}
+
+impl UpdateUserRequest {
+    /// Copies the fields users may change themselves; activation goes through
+    /// email verification or the admin suspend/reactivate endpoints.
+    pub fn apply_to(self, model: &mut ActiveModelUser) {
+        if let Some(name) = self.name {
+            model.name = Set(Some(name));
+        }
+        if let Some(phone) = self.phone {
+            model.phone = Set(Some(phone));
+        }
+        if let Some(address) = self.address {
+            model.address = Set(Some(address));
+        }
+        model.updated_at = Set(Some(Utc::now().naive_utc()));
+    }
+}
+
+/// An account as shown to clients; credentials and second-factor secrets stay on the server.
+#[derive(Debug, Serialize)]
+pub struct UserResponse {
+    pub id: Uuid,
+    pub email: String,
+    pub active: bool,
+    pub name: Option<String>,
+    pub phone: Option<String>,
+    pub address: Option<String>,
+    pub role: Role,
+    pub suspended_at: Option<NaiveDateTime>,
+    pub suspension_reason: Option<String>,
+    pub totp_enabled: bool,
+    pub created_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+impl From<ModelUser> for UserResponse {
+    fn from(user: ModelUser) -> Self {
+        UserResponse {
+            id: user.id,
+            email: user.email,
+            active: user.active,
+            name: user.name,
+            phone: user.phone,
+            address: user.address,
+            role: user.role,
+            suspended_at: user.suspended_at,
+            suspension_reason: user.suspension_reason,
+            totp_enabled: user.totp_enabled,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+        }
+    }
+}
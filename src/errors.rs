@@ -0,0 +1,362 @@
+use crate::services::circulation::CirculationError;
+use crate::services::fines::format_cents;
+use crate::services::mfa::MfaError;
+use crate::services::password_reset::ResetError;
+use crate::services::sessions::RefreshError;
+use crate::services::verification::VerificationError;
+use actix_web::{
+    http::{header, StatusCode},
+    HttpResponse, ResponseError,
+};
+use log::warn;
+use sea_orm::{ActiveEnum, DbErr, RuntimeErr, SqlxError};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use validator::{ValidationErrors, ValidationErrorsKind};
+
+/// Error returned by handlers, rendered as an RFC 7807 problem document.
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    /// The payload broke one or more validation rules.
+    Validation(ValidationErrors),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    /// Too many attempts; clients may retry after the given number of seconds.
+    TooManyRequests(i64),
+    /// A unique constraint rejected the write, with the constraint name if known.
+    UniqueViolation(Option<String>),
+    /// A foreign key constraint rejected the write, with the constraint name if known.
+    ForeignKeyViolation(Option<String>),
+    /// The database can't be reached right now.
+    Unavailable(String),
+    /// Anything else; the message is logged but never sent to clients.
+    Internal(String),
+}
+
+#[derive(Serialize)]
+struct Problem {
+    #[serde(rename = "type")]
+    problem_type: String,
+    title: &'static str,
+    status: u16,
+    detail: String,
+    code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    errors: Option<BTreeMap<String, Vec<FieldError>>>,
+}
+
+#[derive(Serialize)]
+struct FieldError {
+    code: String,
+    message: String,
+}
+
+/// Flatten nested validation errors into `field.path -> errors`; struct-level
+/// errors are reported under `request`.
+fn field_errors(
+    errors: &ValidationErrors,
+    prefix: &str,
+    out: &mut BTreeMap<String, Vec<FieldError>>,
+) {
+    for (field, kind) in errors.errors() {
+        let name = match (*field, prefix) {
+            ("__all__", "") => "request".to_string(),
+            ("__all__", _) => prefix.to_string(),
+            (_, "") => field.to_string(),
+            _ => format!("{}.{}", prefix, field),
+        };
+        match kind {
+            ValidationErrorsKind::Field(list) => {
+                out.entry(name).or_default().extend(list.iter().map(|err| {
+                    FieldError {
+                        code: err.code.to_string(),
+                        message: err
+                            .message
+                            .as_ref()
+                            .map(|message| message.to_string())
+                            .unwrap_or_else(|| format!("Invalid value ({}).", err.code)),
+                    }
+                }))
+            }
+            ValidationErrorsKind::Struct(nested) => field_errors(nested, name.as_str(), out),
+            ValidationErrorsKind::List(items) => {
+                for (index, nested) in items {
+                    field_errors(nested, format!("{}[{}]", name, index).as_str(), out);
+                }
+            }
+        }
+    }
+}
+
+impl ApiError {
+    /// Stable, machine-readable identifier of the error kind.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Validation(_) => "validation_failed",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::TooManyRequests(_) => "too_many_requests",
+            ApiError::UniqueViolation(_) => "unique_violation",
+            ApiError::ForeignKeyViolation(_) => "foreign_key_violation",
+            ApiError::Unavailable(_) => "service_unavailable",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "Bad request",
+            ApiError::Validation(_) => "Validation failed",
+            ApiError::Unauthorized(_) => "Unauthorized",
+            ApiError::Forbidden(_) => "Forbidden",
+            ApiError::NotFound(_) => "Not found",
+            ApiError::Conflict(_) => "Conflict",
+            ApiError::TooManyRequests(_) => "Too many requests",
+            ApiError::UniqueViolation(_) => "Already exists",
+            ApiError::ForeignKeyViolation(_) => "Invalid reference",
+            ApiError::Unavailable(_) => "Service unavailable",
+            ApiError::Internal(_) => "Internal server error",
+        }
+    }
+
+    fn detail(&self) -> String {
+        match self {
+            ApiError::BadRequest(detail)
+            | ApiError::Unauthorized(detail)
+            | ApiError::Forbidden(detail)
+            | ApiError::NotFound(detail)
+            | ApiError::Conflict(detail) => detail.to_owned(),
+            ApiError::Validation(_) => "The request has invalid fields.".to_string(),
+            ApiError::TooManyRequests(seconds) => {
+                format!("Too many failed attempts, retry in {} seconds.", seconds)
+            }
+            ApiError::UniqueViolation(_) => {
+                "A record with the same values already exists.".to_string()
+            }
+            ApiError::ForeignKeyViolation(_) => {
+                "The record references, or is referenced by, another record.".to_string()
+            }
+            ApiError::Unavailable(_) => "The database is unavailable, try again later.".to_string(),
+            ApiError::Internal(_) => "An unexpected error occurred.".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::UniqueViolation(Some(constraint))
+            | ApiError::ForeignKeyViolation(Some(constraint)) => {
+                write!(f, "{}: {}", self.code(), constraint)
+            }
+            ApiError::Unavailable(message) | ApiError::Internal(message) => {
+                write!(f, "{}: {}", self.code(), message)
+            }
+            _ => write!(f, "{}: {}", self.code(), self.detail()),
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_)
+            | ApiError::UniqueViolation(_)
+            | ApiError::ForeignKeyViolation(_) => StatusCode::CONFLICT,
+            ApiError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        if status.is_server_error() {
+            warn!("Request failed: {}", self);
+        }
+        let mut response = HttpResponse::build(status);
+        if let ApiError::TooManyRequests(seconds) = self {
+            response.insert_header((header::RETRY_AFTER, seconds.to_string()));
+        }
+        response
+            .content_type("application/problem+json")
+            .json(Problem {
+                problem_type: format!("urn:bookborrow:problem:{}", self.code()),
+                title: self.title(),
+                status: status.as_u16(),
+                detail: self.detail(),
+                code: self.code(),
+                errors: match self {
+                    ApiError::Validation(errors) => {
+                        let mut fields = BTreeMap::new();
+                        field_errors(errors, "", &mut fields);
+                        Some(fields)
+                    }
+                    _ => None,
+                },
+            })
+    }
+}
+
+/// Map a SQLx failure by its Postgres SQLSTATE.
+fn from_sqlx(err: &SqlxError) -> Option<ApiError> {
+    match err {
+        SqlxError::Database(db_err) => {
+            let constraint = db_err.constraint().map(str::to_string);
+            match db_err.code().as_deref() {
+                Some("23505") => Some(ApiError::UniqueViolation(constraint)),
+                Some("23503") => Some(ApiError::ForeignKeyViolation(constraint)),
+                Some("23502") | Some("23514") | Some("22P02") => Some(ApiError::BadRequest(
+                    "The record has missing or invalid values.".to_string(),
+                )),
+                _ => None,
+            }
+        }
+        SqlxError::PoolTimedOut | SqlxError::PoolClosed | SqlxError::Io(_) => {
+            Some(ApiError::Unavailable(err.to_string()))
+        }
+        _ => None,
+    }
+}
+
+impl From<DbErr> for ApiError {
+    fn from(err: DbErr) -> Self {
+        match &err {
+            DbErr::RecordNotFound(_) | DbErr::RecordNotUpdated => {
+                ApiError::NotFound("Record not found.".to_string())
+            }
+            DbErr::ConnectionAcquire | DbErr::Conn(_) => ApiError::Unavailable(err.to_string()),
+            DbErr::Exec(RuntimeErr::SqlxError(sqlx_err))
+            | DbErr::Query(RuntimeErr::SqlxError(sqlx_err)) => {
+                from_sqlx(sqlx_err).unwrap_or_else(|| ApiError::Internal(err.to_string()))
+            }
+            _ => ApiError::Internal(err.to_string()),
+        }
+    }
+}
+
+impl From<argon2::password_hash::Error> for ApiError {
+    fn from(err: argon2::password_hash::Error) -> Self {
+        ApiError::Internal(format!("Unable to hash password: {}", err))
+    }
+}
+
+impl From<MfaError> for ApiError {
+    fn from(err: MfaError) -> Self {
+        match err {
+            MfaError::NotEnrolled => {
+                ApiError::BadRequest("Start the enrollment first.".to_string())
+            }
+            MfaError::AlreadyEnabled => {
+                ApiError::Conflict("Two-factor authentication is already enabled.".to_string())
+            }
+            MfaError::InvalidCode => ApiError::BadRequest("Invalid code.".to_string()),
+            MfaError::Totp(err) => ApiError::Internal(format!("Unable to build TOTP: {}", err)),
+            MfaError::Db(err) => err.into(),
+        }
+    }
+}
+
+impl From<VerificationError> for ApiError {
+    fn from(err: VerificationError) -> Self {
+        match err {
+            VerificationError::Invalid => {
+                ApiError::BadRequest("Invalid verification token.".to_string())
+            }
+            VerificationError::Expired => {
+                ApiError::BadRequest("Verification token expired.".to_string())
+            }
+            VerificationError::Suspended => {
+                ApiError::Forbidden("Account is suspended.".to_string())
+            }
+            VerificationError::Db(err) => err.into(),
+        }
+    }
+}
+
+impl From<ResetError> for ApiError {
+    fn from(err: ResetError) -> Self {
+        match err {
+            ResetError::Invalid => ApiError::BadRequest("Invalid reset token.".to_string()),
+            ResetError::Expired => ApiError::BadRequest("Reset token expired.".to_string()),
+            ResetError::Hash(err) => err.into(),
+            ResetError::Db(err) => err.into(),
+        }
+    }
+}
+
+impl From<RefreshError> for ApiError {
+    fn from(err: RefreshError) -> Self {
+        match err {
+            RefreshError::Invalid | RefreshError::Reused => {
+                ApiError::Unauthorized("Invalid refresh token.".to_string())
+            }
+            RefreshError::Expired => ApiError::Unauthorized("Refresh token expired.".to_string()),
+            RefreshError::Inactive => ApiError::Forbidden("Account is not active.".to_string()),
+            RefreshError::Db(err) => err.into(),
+        }
+    }
+}
+
+impl From<CirculationError> for ApiError {
+    fn from(err: CirculationError) -> Self {
+        match err {
+            CirculationError::ReservationNotFound => {
+                ApiError::NotFound("Reservation not found.".to_string())
+            }
+            CirculationError::UserNotFound => ApiError::NotFound("User not found.".to_string()),
+            CirculationError::CopyNotFound => ApiError::NotFound("Copy not found.".to_string()),
+            CirculationError::BookNotFound => ApiError::NotFound("Book not found.".to_string()),
+            CirculationError::CopyUnavailable => {
+                ApiError::Conflict("Copy is not available.".to_string())
+            }
+            CirculationError::NoCopyAvailable => {
+                ApiError::Conflict("No copy of this book is available.".to_string())
+            }
+            CirculationError::InvalidTransition(from, to) => ApiError::Conflict(format!(
+                "A reservation can't go from {} to {}.",
+                from.to_value(),
+                to.to_value()
+            )),
+            CirculationError::LoanLimitReached(limit) => ApiError::Conflict(format!(
+                "The borrower already holds the maximum of {} copies.",
+                limit
+            )),
+            CirculationError::NotRenewable(status) => ApiError::Conflict(format!(
+                "Only checked out loans can be renewed, this one is {}.",
+                status.to_value()
+            )),
+            CirculationError::RenewalLimitReached(limit) => ApiError::Conflict(format!(
+                "The loan can't be renewed again, the limit is {} renewals.",
+                limit
+            )),
+            CirculationError::HoldsQueued => ApiError::Conflict(
+                "Other borrowers are waiting for this book, it can't be renewed.".to_string(),
+            ),
+            CirculationError::BalanceTooHigh(balance, limit) => ApiError::Forbidden(format!(
+                "The borrower owes {}, more than the {} allowed for checkouts.",
+                format_cents(balance),
+                format_cents(limit)
+            )),
+            CirculationError::Db(err) => err.into(),
+        }
+    }
+}
+
+impl From<ValidationErrors> for ApiError {
+    fn from(errors: ValidationErrors) -> Self {
+        ApiError::Validation(errors)
+    }
+}
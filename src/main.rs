@@ -1,44 +1,90 @@
+mod config;
 mod constants;
+mod dto;
+mod errors;
 mod middleware;
 mod models;
 mod routes;
+mod services;
 mod utils;
 
 use actix_web::middleware::Logger;
 use actix_web::{middleware::Compress, web::Data, App, HttpServer};
 
+use config::Config;
 use dotenvy::dotenv;
+use log::info;
 use migration::{Migrator, MigratorTrait};
 use routes::register::configure;
-use sea_orm::Database;
-use std::env;
+use sea_orm::{ConnectOptions, Database};
+use services::jwt_keys::jwt_keys_from_config;
+use services::lockout::lockout_from_config;
+use services::mailer::{mailer_from_config, Mailer};
+use services::notifications::notifier_from_config;
+use services::scheduler::Scheduler;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
-    let database_url: String = env::var("DATABASE_URL")
-        .expect("DATABASE_URL must be set")
-        .parse()
-        .unwrap();
-    println!("{}", database_url);
-    match Database::connect(database_url).await {
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Invalid configuration:\n{}", err);
+            std::process::exit(1);
+        }
+    };
+    env_logger::Builder::new()
+        .parse_filters(config.logging.level.as_str())
+        .init();
+
+    let mut options = ConnectOptions::new(config.database.url.to_owned());
+    options
+        .max_connections(config.database.max_connections)
+        .min_connections(config.database.min_connections)
+        .connect_timeout(Duration::from_secs(config.database.connect_timeout_seconds));
+    match Database::connect(options).await {
         Ok(db) => {
             // Database migrations
-            println!("Database connected");
+            info!("Database connected");
             Migrator::up(&db, None).await.unwrap();
 
+            let mailer = mailer_from_config(&config.mailer);
+            let lockout = Data::new(lockout_from_config(&config.lockout, &db));
+            let keys =
+                Data::new(jwt_keys_from_config(&config.jwt).expect("Unable to load JWT keys"));
+            let bind = (config.server.host.to_owned(), config.server.port);
+            let workers = config.server.workers;
+            let config = Data::new(config);
+
+            // Run background jobs
+            let notifier = Arc::new(notifier_from_config(&config, mailer.clone()));
+            let scheduler = Scheduler::new(db.clone(), config.clone().into_inner(), notifier);
+            if config.scheduler.enabled {
+                scheduler.start();
+            }
+            let scheduler = Data::new(scheduler);
+            let mailer: Data<dyn Mailer> = Data::from(mailer);
+
             // Run http server
-            println!("Starting server");
-            HttpServer::new(move || {
+            info!("Starting server on {}:{}", bind.0, bind.1);
+            let mut server = HttpServer::new(move || {
                 App::new()
                     .wrap(Compress::default())
                     .wrap(Logger::default())
                     .app_data(Data::new(db.clone()))
+                    .app_data(config.clone())
+                    .app_data(mailer.clone())
+                    .app_data(lockout.clone())
+                    .app_data(keys.clone())
+                    .app_data(scheduler.clone())
                     .configure(configure)
-            })
-            .bind(("0.0.0.0", 8000))?
-            .run()
-            .await
+            });
+            if let Some(workers) = workers {
+                server = server.workers(workers);
+            }
+            server.bind(bind)?.run().await
         }
         Err(e) => panic!("Error connecting to database: {:?}", e),
     }
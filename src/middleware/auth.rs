@@ -1,17 +1,22 @@
-use crate::utils::token::decode_token;
+use crate::errors::ApiError;
+use crate::services::jwt_keys::JwtKeys;
+use crate::services::sessions::{validate_access_token, SessionError};
+use crate::utils::token::{decode_token, TokenClaims};
 use actix_web::{
-    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
-    error::ErrorUnauthorized,
-    Error,
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    web, Error, FromRequest, HttpMessage, HttpRequest,
 };
 use futures::future::LocalBoxFuture;
+use sea_orm::DatabaseConnection;
 use std::future::{ready, Ready};
+use std::ops::Deref;
+use std::rc::Rc;
 
 pub struct JwtValidator;
 
 impl<S, B> Transform<S, ServiceRequest> for JwtValidator
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
     B: 'static,
 {
@@ -22,17 +27,19 @@ where
     type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
     fn new_transform(&self, service: S) -> Self::Future {
-        ready(Ok(JwtValidatorMiddleware { service }))
+        ready(Ok(JwtValidatorMiddleware {
+            service: Rc::new(service),
+        }))
     }
 }
 
 pub struct JwtValidatorMiddleware<S> {
-    service: S,
+    service: Rc<S>,
 }
 
 impl<S, B> Service<ServiceRequest> for JwtValidatorMiddleware<S>
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
     B: 'static,
 {
@@ -43,19 +50,66 @@ where
     forward_ready!(service);
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        let token = req.headers().get("Authorization").cloned();
-        let fut = self.service.call(req);
+        let service = self.service.clone();
         Box::pin(async move {
-            match token {
-                Some(token) => {
-                    let res = fut.await?;
-                    match decode_token(token) {
-                        Ok(_) => Ok(res),
-                        Err(err) => Err(err),
+            // Validate before touching the inner service, so rejected requests never reach a handler
+            let keys = req
+                .app_data::<web::Data<JwtKeys>>()
+                .ok_or_else(|| ApiError::Internal("JWT keys not configured.".to_string()))?;
+            let claims = match req.headers().get("Authorization").cloned() {
+                Some(token) => decode_token(keys, token)?,
+                None => {
+                    return Err(
+                        ApiError::Unauthorized("Missing authorization token.".to_string()).into(),
+                    )
+                }
+            };
+            let db = req
+                .app_data::<web::Data<DatabaseConnection>>()
+                .ok_or_else(|| {
+                    ApiError::Internal("Database connection not configured.".to_string())
+                })?;
+            if let Err(err) = validate_access_token(db.get_ref(), &claims).await {
+                return Err(match err {
+                    SessionError::Revoked => {
+                        ApiError::Unauthorized("Token has been revoked.".to_string())
+                    }
+                    SessionError::UnknownUser => {
+                        ApiError::Unauthorized("Invalid user.".to_string())
                     }
+                    SessionError::Inactive(reason) => ApiError::Forbidden(reason.to_string()),
+                    SessionError::Expired => {
+                        ApiError::Unauthorized("Session expired, please login again.".to_string())
+                    }
+                    SessionError::Db(err) => err.into(),
                 }
-                None => Err(ErrorUnauthorized("Missing authorization token.")),
+                .into());
             }
+            req.extensions_mut().insert(claims);
+            service.call(req).await
+        })
+    }
+}
+
+/// Claims of the caller, as validated by [`JwtValidator`].
+pub struct AuthenticatedUser(pub TokenClaims);
+
+impl Deref for AuthenticatedUser {
+    type Target = TokenClaims;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(match req.extensions().get::<TokenClaims>() {
+            Some(claims) => Ok(AuthenticatedUser(claims.clone())),
+            None => Err(ApiError::Unauthorized("Missing authorization token.".to_string()).into()),
         })
     }
 }
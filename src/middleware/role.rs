@@ -0,0 +1,82 @@
+use crate::errors::ApiError;
+use crate::models::users::Role;
+use crate::utils::token::TokenClaims;
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpMessage,
+};
+use futures::future::LocalBoxFuture;
+use std::future::{ready, Ready};
+
+/// Restricts a scope to users holding at least the given role.
+///
+/// Staff roles are only honoured for logins that passed a second factor.
+///
+/// Relies on the claims inserted by [`JwtValidator`](super::auth::JwtValidator),
+/// so it must be wrapped inside of it.
+pub struct RequireRole(pub Role);
+
+impl RequireRole {
+    pub fn librarian() -> Self {
+        RequireRole(Role::Librarian)
+    }
+
+    pub fn admin() -> Self {
+        RequireRole(Role::Admin)
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequireRole
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequireRoleMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireRoleMiddleware {
+            service,
+            role: self.0,
+        }))
+    }
+}
+
+pub struct RequireRoleMiddleware<S> {
+    service: S,
+    role: Role,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireRoleMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let authorized = match req.extensions().get::<TokenClaims>() {
+            Some(claims) if claims.has_role(self.role) => Ok(()),
+            Some(claims) if claims.role.satisfies(self.role) => {
+                Err(ApiError::Forbidden("Second factor required.".to_string()))
+            }
+            Some(_) => Err(ApiError::Forbidden("Insufficient role.".to_string())),
+            None => Err(ApiError::Unauthorized(
+                "Missing authorization token.".to_string(),
+            )),
+        };
+        match authorized {
+            Ok(()) => Box::pin(self.service.call(req)),
+            Err(err) => Box::pin(async move { Err(err.into()) }),
+        }
+    }
+}
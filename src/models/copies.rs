@@ -0,0 +1,88 @@
+use chrono::NaiveDateTime;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A physical, circulating item of a book.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "copies")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    pub book_id: Uuid,
+    #[sea_orm(unique)]
+    pub barcode: String,
+    pub condition: CopyCondition,
+    pub shelf_location: Option<String>,
+    pub status: CopyStatus,
+    pub created_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+#[derive(
+    Copy, Clone, Debug, Default, PartialEq, Eq, EnumIter, DeriveActiveEnum, Deserialize, Serialize,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(16))")]
+#[serde(rename_all = "lowercase")]
+pub enum CopyCondition {
+    #[sea_orm(string_value = "new")]
+    New,
+    #[default]
+    #[sea_orm(string_value = "good")]
+    Good,
+    #[sea_orm(string_value = "fair")]
+    Fair,
+    #[sea_orm(string_value = "poor")]
+    Poor,
+    #[sea_orm(string_value = "damaged")]
+    Damaged,
+}
+
+#[derive(
+    Copy, Clone, Debug, Default, PartialEq, Eq, EnumIter, DeriveActiveEnum, Deserialize, Serialize,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(16))")]
+#[serde(rename_all = "snake_case")]
+pub enum CopyStatus {
+    /// On the shelf, can be lent.
+    #[default]
+    #[sea_orm(string_value = "available")]
+    Available,
+    /// Set aside for a reservation awaiting pickup.
+    #[sea_orm(string_value = "on_hold")]
+    OnHold,
+    #[sea_orm(string_value = "on_loan")]
+    OnLoan,
+    #[sea_orm(string_value = "in_repair")]
+    InRepair,
+    #[sea_orm(string_value = "lost")]
+    Lost,
+    #[sea_orm(string_value = "withdrawn")]
+    Withdrawn,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::books::Entity",
+        from = "Column::BookId",
+        to = "super::books::Column::Id"
+    )]
+    Book,
+    #[sea_orm(has_many = "super::reservations::Entity")]
+    Reservation,
+}
+
+impl Related<super::books::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Book.def()
+    }
+}
+
+impl Related<super::reservations::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Reservation.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,85 @@
+use chrono::NaiveDateTime;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// One execution of a background job, by the scheduler or an admin.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "job_runs")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    pub job: Job,
+    pub trigger: JobTrigger,
+    pub status: JobStatus,
+    /// Records the job changed, once it finished.
+    pub affected: Option<i64>,
+    pub error: Option<String>,
+    /// Admin who ran the job by hand.
+    pub triggered_by: Option<Uuid>,
+    pub started_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, Hash, EnumIter, DeriveActiveEnum, Deserialize, Serialize,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(32))")]
+#[serde(rename_all = "snake_case")]
+pub enum Job {
+    /// Checked out loans past their due date and grace period become overdue.
+    #[sea_orm(string_value = "mark_overdue")]
+    MarkOverdue,
+    /// Copies set aside but not collected in time go to the next hold.
+    #[sea_orm(string_value = "expire_holds")]
+    ExpireHolds,
+    /// Expired refresh, revoked, verification and password reset tokens are deleted.
+    #[sea_orm(string_value = "purge_tokens")]
+    PurgeTokens,
+    /// Borrowers are notified before their loans are due.
+    #[sea_orm(string_value = "send_reminders")]
+    SendReminders,
+    /// Queued notifications go out, or are retried.
+    #[sea_orm(string_value = "deliver_notifications")]
+    DeliverNotifications,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Deserialize, Serialize)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(16))")]
+#[serde(rename_all = "lowercase")]
+pub enum JobTrigger {
+    #[sea_orm(string_value = "schedule")]
+    Schedule,
+    #[sea_orm(string_value = "manual")]
+    Manual,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Deserialize, Serialize)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(16))")]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    #[sea_orm(string_value = "running")]
+    Running,
+    #[sea_orm(string_value = "succeeded")]
+    Succeeded,
+    #[sea_orm(string_value = "failed")]
+    Failed,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::TriggeredBy",
+        to = "super::users::Column::Id"
+    )]
+    User,
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,60 @@
+use chrono::NaiveDateTime;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// One movement on a patron's account; amounts are always positive, the
+/// kind tells which way they count.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "ledger_entries")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    pub user_id: Uuid,
+    /// The loan a fine was charged for, if any.
+    pub reservation_id: Option<Uuid>,
+    pub kind: LedgerKind,
+    pub amount_cents: i64,
+    pub note: Option<String>,
+    /// Staff member who recorded the entry; `None` for charges of the fines engine.
+    pub actor_id: Option<Uuid>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Deserialize, Serialize)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(16))")]
+#[serde(rename_all = "lowercase")]
+pub enum LedgerKind {
+    /// Owed by the patron.
+    #[sea_orm(string_value = "charge")]
+    Charge,
+    #[sea_orm(string_value = "payment")]
+    Payment,
+    /// Forgiven by staff.
+    #[sea_orm(string_value = "waiver")]
+    Waiver,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::UserId",
+        to = "super::users::Column::Id"
+    )]
+    User,
+    #[sea_orm(
+        belongs_to = "super::reservations::Entity",
+        from = "Column::ReservationId",
+        to = "super::reservations::Column::Id"
+    )]
+    Reservation,
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,3 +1,14 @@
 pub mod books;
+pub mod copies;
+pub mod email_verification_tokens;
+pub mod job_runs;
+pub mod ledger_entries;
+pub mod login_attempts;
+pub mod notifications;
+pub mod password_reset_tokens;
+pub mod recovery_codes;
+pub mod refresh_tokens;
+pub mod reservation_events;
 pub mod reservations;
+pub mod revoked_tokens;
 pub mod users;
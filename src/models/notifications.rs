@@ -0,0 +1,90 @@
+use chrono::NaiveDateTime;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Outbox of messages to patrons, rendered when queued and delivered by the
+/// scheduler, so they survive restarts and failing transports.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "notifications")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub kind: NotificationKind,
+    pub channel: Channel,
+    /// Email address of the patron when the notification was queued.
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+    /// Values the templates were rendered with, passed on to webhooks.
+    #[sea_orm(column_type = "JsonBinary")]
+    pub data: Json,
+    pub status: NotificationStatus,
+    pub attempts: i32,
+    pub next_attempt_at: NaiveDateTime,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub sent_at: Option<NaiveDateTime>,
+}
+
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, Hash, EnumIter, DeriveActiveEnum, Deserialize, Serialize,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(32))")]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// A loan is due in the next few days.
+    #[sea_orm(string_value = "due_soon")]
+    DueSoon,
+    #[sea_orm(string_value = "overdue")]
+    Overdue,
+    /// A copy is set aside for the patron's hold.
+    #[sea_orm(string_value = "hold_ready")]
+    HoldReady,
+    /// A loan came back, or was lost, with a fine.
+    #[sea_orm(string_value = "fine_assessed")]
+    FineAssessed,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Deserialize, Serialize)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(16))")]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    #[sea_orm(string_value = "email")]
+    Email,
+    /// Posted to the webhook set in the configuration, e.g. an SMS gateway.
+    #[sea_orm(string_value = "webhook")]
+    Webhook,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Deserialize, Serialize)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(16))")]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationStatus {
+    #[sea_orm(string_value = "pending")]
+    Pending,
+    #[sea_orm(string_value = "sent")]
+    Sent,
+    /// Gave up after the configured number of attempts.
+    #[sea_orm(string_value = "failed")]
+    Failed,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::UserId",
+        to = "super::users::Column::Id"
+    )]
+    User,
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,43 @@
+use super::reservations::ReservationStatus;
+use chrono::NaiveDateTime;
+use sea_orm::entity::prelude::*;
+use uuid::Uuid;
+
+/// One status change of a reservation, with who made it and when.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "reservation_events")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    pub reservation_id: Uuid,
+    /// `None` for the event that created the reservation.
+    pub from_status: Option<ReservationStatus>,
+    pub to_status: ReservationStatus,
+    /// `None` for changes made by the system, or by since deleted users.
+    pub actor_id: Option<Uuid>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::reservations::Entity",
+        from = "Column::ReservationId",
+        to = "super::reservations::Column::Id"
+    )]
+    Reservation,
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::ActorId",
+        to = "super::users::Column::Id"
+    )]
+    Actor,
+}
+
+impl Related<super::reservations::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Reservation.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,27 +1,89 @@
-use crate::utils::default::default_created_at;
-use chrono::{NaiveDateTime, Utc};
-use sea_orm::{entity::prelude::*, Set};
+use chrono::NaiveDateTime;
+use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
 #[sea_orm(table_name = "reservations")]
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
-    #[serde(skip_deserializing)]
-    #[serde(default = "Uuid::new_v4")]
     pub id: Uuid,
     pub user_id: Uuid,
     pub book_id: Uuid,
+    /// Unset while a hold waits in line for a copy.
+    pub copy_id: Option<Uuid>,
     pub reservation_date: Option<NaiveDateTime>,
     pub return_date: Option<NaiveDateTime>,
-    #[serde(skip_deserializing)]
-    #[serde(default = "default_created_at")]
+    pub status: ReservationStatus,
+    /// Set at checkout from the loan policy, pushed back by renewals.
+    pub due_date: Option<NaiveDateTime>,
+    pub renewals: i32,
+    /// Deadline to collect a copy set aside for the reservation.
+    pub pickup_expires_at: Option<NaiveDateTime>,
+    /// When the borrower was reminded of the current due date.
+    pub reminded_at: Option<NaiveDateTime>,
     pub created_at: Option<NaiveDateTime>,
-    #[serde(skip_deserializing)]
     pub updated_at: Option<NaiveDateTime>,
 }
 
+/// Where a reservation is in its lifecycle; see [`ReservationStatus::can_become`].
+#[derive(
+    Copy, Clone, Debug, Default, PartialEq, Eq, EnumIter, DeriveActiveEnum, Deserialize, Serialize,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(20))")]
+#[serde(rename_all = "snake_case")]
+pub enum ReservationStatus {
+    /// Waiting for a copy.
+    #[default]
+    #[sea_orm(string_value = "requested")]
+    Requested,
+    /// A copy is set aside for the borrower.
+    #[sea_orm(string_value = "ready_for_pickup")]
+    ReadyForPickup,
+    #[sea_orm(string_value = "checked_out")]
+    CheckedOut,
+    #[sea_orm(string_value = "returned")]
+    Returned,
+    #[sea_orm(string_value = "cancelled")]
+    Cancelled,
+    /// Checked out and past its due date.
+    #[sea_orm(string_value = "overdue")]
+    Overdue,
+    #[sea_orm(string_value = "lost")]
+    Lost,
+}
+
+impl ReservationStatus {
+    /// Whether the lifecycle allows moving from this status to `next`.
+    pub fn can_become(self, next: ReservationStatus) -> bool {
+        use ReservationStatus::*;
+        matches!(
+            (self, next),
+            (Requested, ReadyForPickup)
+                | (Requested, Cancelled)
+                | (ReadyForPickup, CheckedOut)
+                | (ReadyForPickup, Cancelled)
+                | (CheckedOut, Returned)
+                | (CheckedOut, Overdue)
+                | (CheckedOut, Lost)
+                | (Overdue, Returned)
+                | (Overdue, Lost)
+                // A lost copy that turns up again
+                | (Lost, Returned)
+        )
+    }
+
+    /// Whether a reservation in this status keeps its copy off the shelf.
+    pub fn holds_copy(self) -> bool {
+        matches!(
+            self,
+            ReservationStatus::ReadyForPickup
+                | ReservationStatus::CheckedOut
+                | ReservationStatus::Overdue
+        )
+    }
+}
+
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(
@@ -36,6 +98,14 @@ pub enum Relation {
         to = "super::books::Column::Id"
     )]
     Book,
+    #[sea_orm(
+        belongs_to = "super::copies::Entity",
+        from = "Column::CopyId",
+        to = "super::copies::Column::Id"
+    )]
+    Copy,
+    #[sea_orm(has_many = "super::reservation_events::Entity")]
+    Event,
 }
 
 impl Related<super::books::Entity> for Entity {
@@ -44,20 +114,22 @@ impl Related<super::books::Entity> for Entity {
     }
 }
 
-impl Related<super::users::Entity> for Entity {
+impl Related<super::copies::Entity> for Entity {
     fn to() -> RelationDef {
-        Relation::User.def()
+        Relation::Copy.def()
     }
 }
 
-impl ActiveModelBehavior for ActiveModel {}
+impl Related<super::reservation_events::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Event.def()
+    }
+}
 
-impl ActiveModel {
-    pub fn merge(&mut self, other: Model) {
-        self.user_id = Set(other.user_id.to_owned());
-        self.book_id = Set(other.book_id.to_owned());
-        self.reservation_date = Set(other.reservation_date.to_owned());
-        self.return_date = Set(other.return_date.to_owned());
-        self.updated_at = Set(Some(Utc::now().naive_utc()));
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
     }
 }
+
+impl ActiveModelBehavior for ActiveModel {}
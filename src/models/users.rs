@@ -1,15 +1,13 @@
-use crate::utils::default::default_created_at;
-use chrono::{NaiveDateTime, Utc};
+use crate::models::notifications::{Channel, NotificationKind};
+use chrono::NaiveDateTime;
 use sea_orm::{entity::prelude::*, Set};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
 #[sea_orm(table_name = "users")]
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
-    #[serde(skip_deserializing)]
-    #[serde(default = "Uuid::new_v4")]
     pub id: Uuid,
     #[sea_orm(unique)]
     pub email: String,
@@ -18,13 +16,61 @@ pub struct Model {
     pub name: Option<String>,
     pub phone: Option<String>,
     pub address: Option<String>,
-    #[serde(skip_deserializing)]
-    #[serde(default = "default_created_at")]
+    pub role: Role,
+    pub password_changed_at: Option<NaiveDateTime>,
+    pub suspended_at: Option<NaiveDateTime>,
+    pub suspension_reason: Option<String>,
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+    pub totp_last_step: Option<i64>,
+    /// Language of the notifications sent to the user.
+    pub locale: String,
+    pub notify_email: bool,
+    pub notify_webhook: bool,
+    pub notify_due_dates: bool,
+    pub notify_holds: bool,
+    pub notify_fines: bool,
     pub created_at: Option<NaiveDateTime>,
-    #[serde(skip_deserializing)]
     pub updated_at: Option<NaiveDateTime>,
 }
 
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    EnumIter,
+    DeriveActiveEnum,
+    Deserialize,
+    Serialize,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(16))")]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    #[sea_orm(string_value = "member")]
+    Member,
+    #[sea_orm(string_value = "librarian")]
+    Librarian,
+    #[sea_orm(string_value = "admin")]
+    Admin,
+}
+
+impl Role {
+    /// Roles are ordered by privilege, so an admin can do anything a librarian can.
+    pub fn satisfies(&self, required: Role) -> bool {
+        *self >= required
+    }
+
+    pub fn is_staff(&self) -> bool {
+        self.satisfies(Role::Librarian)
+    }
+}
+
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(has_many = "super::reservations::Entity")]
@@ -46,13 +92,34 @@ impl ActiveModelBehavior for ActiveModel {
     }
 }
 
-impl ActiveModel {
-    pub fn merge(&mut self, other: Model) {
-        self.password = Set(other.password.to_owned());
-        self.active = Set(other.active.to_owned());
-        self.name = Set(other.name.to_owned());
-        self.phone = Set(other.phone.to_owned());
-        self.address = Set(other.address.to_owned());
-        self.updated_at = Set(Some(Utc::now().naive_utc()));
+impl Model {
+    /// Why the account can't be used, if it is inactive.
+    pub fn inactive_reason(&self) -> Option<&'static str> {
+        match (self.active, self.suspended_at) {
+            (true, _) => None,
+            (false, Some(_)) => Some("Account suspended."),
+            (false, None) => Some("Account not activated, check your email."),
+        }
+    }
+
+    /// Whether the user wants to hear about `kind` at all.
+    pub fn wants(&self, kind: NotificationKind) -> bool {
+        match kind {
+            NotificationKind::DueSoon | NotificationKind::Overdue => self.notify_due_dates,
+            NotificationKind::HoldReady => self.notify_holds,
+            NotificationKind::FineAssessed => self.notify_fines,
+        }
+    }
+
+    /// Channels the user chose to be notified on.
+    pub fn channels(&self) -> Vec<Channel> {
+        let mut channels = Vec::new();
+        if self.notify_email {
+            channels.push(Channel::Email);
+        }
+        if self.notify_webhook {
+            channels.push(Channel::Webhook);
+        }
+        channels
     }
 }
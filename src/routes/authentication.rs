@@ -1,18 +1,22 @@
-use crate::models::users::{Column as ColumnUser, Entity as EntityUser};
-use crate::utils::default::encrypt_password;
-use crate::utils::token::TokenClaims;
-use actix_web::{post, web, HttpResponse, Responder};
-use base64::{engine::general_purpose, Engine as _};
-use jsonwebtoken::{encode, EncodingKey, Header};
+use crate::config::{Config, PasswordConfig};
+use crate::errors::ApiError;
+use crate::middleware::auth::AuthenticatedUser;
+use crate::models::users::{
+    ActiveModel as ActiveModelUser, Column as ColumnUser, Entity as EntityUser, Model as ModelUser,
+};
+use crate::services::jwt_keys::JwtKeys;
+use crate::services::lockout::Lockout;
+use crate::services::mfa::{verify_second_factor, MfaError};
+use crate::services::sessions::{
+    issue_session, revoke_access_token, revoke_session, rotate_session, RefreshError,
+};
+use crate::utils::password::{dummy_verify, hash_password, verify_password, PasswordVerification};
+use crate::utils::token::{decode_mfa_token, encode_mfa_token};
+use actix_web::{post, web, HttpRequest, HttpResponse};
 use log::warn;
-use sea_orm::{ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter};
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
 use serde::{Deserialize, Serialize};
-use std::env;
-
-#[derive(Serialize, Deserialize)]
-pub struct JwtToken {
-    token: String,
-}
+use uuid::Uuid;
 
 #[derive(Deserialize)]
 pub struct Login {
@@ -20,59 +24,217 @@ pub struct Login {
     password: String,
 }
 
+#[derive(Serialize, Deserialize)]
+struct MfaChallenge {
+    mfa_required: bool,
+    mfa_token: String,
+}
+
+#[derive(Deserialize)]
+pub struct MfaLogin {
+    mfa_token: String,
+    code: String,
+}
+
+#[derive(Deserialize)]
+pub struct RefreshToken {
+    refresh_token: String,
+}
+
+/// Replace a legacy or outdated password hash after a successful login.
+async fn rehash_password(
+    config: &PasswordConfig,
+    user: &ModelUser,
+    password: &str,
+    connection: &DatabaseConnection,
+) {
+    let hash = match hash_password(config, password) {
+        Ok(hash) => hash,
+        Err(err) => {
+            warn!("Unable to rehash password (Authentication::login): {}", err);
+            return;
+        }
+    };
+    let mut model: ActiveModelUser = user.clone().into();
+    model.password = Set(hash);
+    if let Err(err) = model.update(connection).await {
+        warn!("Unable to rehash password (Authentication::login): {}", err);
+    }
+}
+
+async fn record_failure(lockout: &Lockout, email: &str, ip: &str) {
+    if let Err(err) = lockout.record_failure(email, ip).await {
+        warn!(
+            "Unable to record failed login (Authentication::login): {}",
+            err
+        );
+    }
+}
+
+fn client_ip(req: &HttpRequest) -> String {
+    req.peer_addr()
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_default()
+}
+
+fn invalid_credentials() -> ApiError {
+    ApiError::NotFound("Invalid email or password.".to_string())
+}
+
+/// Refuse with 429 and `Retry-After` while the account or address is locked out.
+async fn check_lockout(lockout: &Lockout, email: &str, ip: &str) -> Result<(), ApiError> {
+    match lockout.retry_after(email, ip).await? {
+        None => Ok(()),
+        Some(wait) => {
+            warn!("Unable to login (Authentication::login): Too many failed attempts");
+            // Round up, so clients never retry a moment too early
+            Err(ApiError::TooManyRequests(
+                (wait.num_milliseconds() + 999) / 1000,
+            ))
+        }
+    }
+}
+
 #[post("/login")]
-pub async fn login(login: web::Form<Login>, db: web::Data<DatabaseConnection>) -> impl Responder {
+pub async fn login(
+    req: HttpRequest,
+    login: web::Form<Login>,
+    db: web::Data<DatabaseConnection>,
+    lockout: web::Data<Lockout>,
+    keys: web::Data<JwtKeys>,
+    config: web::Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+    let ip = client_ip(&req);
+    check_lockout(&lockout, login.email.as_str(), ip.as_str()).await?;
+
     let connection = db.get_ref();
-    match EntityUser::find()
-        .filter(
-            Condition::all()
-                .add(ColumnUser::Email.contains(login.email.as_str()))
-                .add(
-                    ColumnUser::Password
-                        .contains(encrypt_password(login.password.to_string()).as_str()),
-                ),
-        )
+    let user = match EntityUser::find()
+        .filter(ColumnUser::Email.eq(login.email.as_str()))
         .one(connection)
-        .await
+        .await?
     {
-        Ok(Some(user)) => {
-            let now = chrono::Utc::now();
-            let iat = now.timestamp() as usize;
-            let exp = (now
-                + chrono::Duration::minutes(
-                    env::var("JWT_TIMEOUT")
-                        .expect("JWT_TIMEOUT: Not Found!")
-                        .parse::<i64>()
-                        .expect("JWT_TIMEOUT: Wrong type!"),
-                ))
-            .timestamp() as usize;
-            let claims: TokenClaims = TokenClaims {
-                sub: user.id.to_string(),
-                exp,
-                iat,
-            };
-
-            let token = encode(
-                &Header::default(),
-                &claims,
-                &EncodingKey::from_secret(
-                    env::var("JWT_SECRET")
-                        .expect("JWT_SECRET: Not Found!")
-                        .as_ref(),
-                ),
-            )
-            .unwrap();
-            HttpResponse::Ok().json(JwtToken {
-                token: general_purpose::STANDARD_NO_PAD.encode(token),
-            })
-        }
-        Ok(None) => {
+        Some(user) => user,
+        None => {
+            dummy_verify(&config.password, login.password.as_str());
             warn!("Unable to login (Authentication::login): User not found");
-            HttpResponse::NotFound().finish()
+            record_failure(&lockout, login.email.as_str(), ip.as_str()).await;
+            return Err(invalid_credentials());
         }
+    };
+
+    let verification = verify_password(
+        &config.password,
+        login.password.as_str(),
+        user.password.as_str(),
+    );
+    if !verification.is_valid() {
+        warn!("Unable to login (Authentication::login): Invalid password");
+        record_failure(&lockout, login.email.as_str(), ip.as_str()).await;
+        return Err(invalid_credentials());
+    }
+    if let Err(err) = lockout.record_success(login.email.as_str()).await {
+        warn!(
+            "Unable to clear failed logins (Authentication::login): {}",
+            err
+        );
+    }
+    if let Some(reason) = user.inactive_reason() {
+        warn!("Unable to login (Authentication::login): {}", reason);
+        return Err(ApiError::Forbidden(reason.to_string()));
+    }
+    if verification == PasswordVerification::ValidNeedsRehash {
+        rehash_password(&config.password, &user, login.password.as_str(), connection).await;
+    }
+
+    // Users with a second factor only get a short-lived token to present with their code
+    if user.totp_enabled {
+        return Ok(HttpResponse::Ok().json(MfaChallenge {
+            mfa_required: true,
+            mfa_token: encode_mfa_token(&keys, &config.jwt, user.id),
+        }));
+    }
+
+    let tokens = issue_session(connection, &keys, &config.jwt, &user, false).await?;
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+#[post("/login/mfa")]
+pub async fn login_mfa(
+    req: HttpRequest,
+    form: web::Form<MfaLogin>,
+    db: web::Data<DatabaseConnection>,
+    lockout: web::Data<Lockout>,
+    keys: web::Data<JwtKeys>,
+    config: web::Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+    let invalid_token = || ApiError::Unauthorized("Invalid mfa token.".to_string());
+    let user_id = decode_mfa_token(&keys, form.mfa_token.as_str())
+        .and_then(|claims| Uuid::parse_str(claims.sub.as_str()).ok())
+        .ok_or_else(|| {
+            warn!("Unable to login (Authentication::login_mfa): Invalid mfa token");
+            invalid_token()
+        })?;
+    let connection = db.get_ref();
+    let user = EntityUser::find_by_id(user_id)
+        .one(connection)
+        .await?
+        .ok_or_else(|| {
+            warn!("Unable to login (Authentication::login_mfa): User not found");
+            invalid_token()
+        })?;
+    if let Some(reason) = user.inactive_reason() {
+        warn!("Unable to login (Authentication::login_mfa): {}", reason);
+        return Err(ApiError::Forbidden(reason.to_string()));
+    }
+    let ip = client_ip(&req);
+    check_lockout(&lockout, user.email.as_str(), ip.as_str()).await?;
+
+    match verify_second_factor(connection, &user, form.code.as_str()).await {
+        Ok(()) => {}
+        Err(MfaError::Db(err)) => return Err(err.into()),
         Err(err) => {
-            warn!("Unable to login (Authentication::login): {}", err);
-            HttpResponse::NotFound().finish()
+            warn!(
+                "Unable to verify code (Authentication::login_mfa): {:?}",
+                err
+            );
+            record_failure(&lockout, user.email.as_str(), ip.as_str()).await;
+            return Err(ApiError::Unauthorized("Invalid code.".to_string()));
         }
     }
+    let tokens = issue_session(connection, &keys, &config.jwt, &user, true).await?;
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+#[post("/token/refresh")]
+pub async fn refresh(
+    form: web::Form<RefreshToken>,
+    db: web::Data<DatabaseConnection>,
+    keys: web::Data<JwtKeys>,
+    config: web::Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+    let connection = db.get_ref();
+    let tokens = rotate_session(connection, &keys, &config.jwt, form.refresh_token.as_str())
+        .await
+        .map_err(|err| {
+            if !matches!(err, RefreshError::Db(_)) {
+                warn!(
+                    "Unable to refresh token (Authentication::refresh): {:?}",
+                    err
+                );
+            }
+            ApiError::from(err)
+        })?;
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+#[post("")]
+pub async fn logout(
+    auth: AuthenticatedUser,
+    form: web::Form<RefreshToken>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let connection = db.get_ref();
+    revoke_access_token(connection, &auth).await?;
+    revoke_session(connection, form.refresh_token.as_str()).await?;
+    Ok(HttpResponse::NoContent().finish())
 }
@@ -1,11 +1,18 @@
+use crate::dto::books::{BookFilter, BookRequest, BookResponse, SearchQuery, BOOK_SORTABLE};
+use crate::errors::ApiError;
 use crate::models::books::{
     ActiveModel as ActiveModelBook, Entity as EntityBook, Model as ModelBook,
 };
-use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
-use log::warn;
-use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, TryIntoModel};
+use crate::services::availability::{availability, Availability};
+use crate::services::search::{prefix_query, search_books};
+use crate::utils::pagination::{
+    page_offset, page_response, paginate, Page, PageMeta, PageParams, DEFAULT_PER_PAGE,
+};
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
+use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
+use validator::Validate;
 
 #[derive(Serialize, Deserialize)]
 struct DeletedRecord {
@@ -13,111 +20,122 @@ struct DeletedRecord {
     message: String,
 }
 
+pub async fn find_book(
+    connection: &DatabaseConnection,
+    book_id: Uuid,
+) -> Result<ModelBook, ApiError> {
+    EntityBook::find_by_id(book_id)
+        .one(connection)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Book not found.".to_string()))
+}
+
+async fn book_response(
+    connection: &DatabaseConnection,
+    book: ModelBook,
+) -> Result<BookResponse, ApiError> {
+    let counts = availability(connection, &[book.id]).await?;
+    let available = counts.get(&book.id).copied().unwrap_or_default();
+    Ok(BookResponse::new(book, available))
+}
+
 #[get("")]
-pub async fn get_all(db: web::Data<DatabaseConnection>) -> impl Responder {
+pub async fn get_all(
+    req: HttpRequest,
+    params: web::Query<PageParams>,
+    filter: web::Query<BookFilter>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    filter.validate()?;
+    let select = filter.apply(EntityBook::find());
     let connection = db.get_ref();
-    match EntityBook::find().into_json().all(connection).await {
-        Ok(data) => HttpResponse::Ok().json(data),
-        Err(err) => {
-            warn!("Unable to load data (Book::get_all): {}", err);
-            HttpResponse::InternalServerError().finish()
-        }
-    }
+    let page = paginate(connection, select, &params, BOOK_SORTABLE, "title").await?;
+    let ids: Vec<Uuid> = page.data.iter().map(|book| book.id).collect();
+    let mut counts = availability(connection, &ids).await?;
+    Ok(page_response(
+        &req,
+        page.map(|book| {
+            let available = counts.remove(&book.id).unwrap_or_default();
+            BookResponse::new(book, available)
+        }),
+    ))
+}
+
+#[get("/search")]
+pub async fn search(
+    req: HttpRequest,
+    query: web::Query<SearchQuery>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    query.validate()?;
+    let text = prefix_query(query.q.as_str()).unwrap_or_default();
+    let page = query.page.unwrap_or(1);
+    let per_page = query.per_page.unwrap_or(DEFAULT_PER_PAGE);
+    let offset = page_offset(page, per_page)?;
+    let (data, total) = search_books(db.get_ref(), &text, per_page, offset).await?;
+    let total_pages = total / per_page + u64::from(total % per_page != 0);
+    Ok(page_response(
+        &req,
+        Page {
+            data,
+            meta: PageMeta {
+                total,
+                per_page,
+                page: Some(page),
+                total_pages: Some(total_pages),
+                next_cursor: None,
+            },
+        },
+    ))
 }
 
 #[get("/{id}")]
-pub async fn get_one(path: web::Path<Uuid>, db: web::Data<DatabaseConnection>) -> impl Responder {
-    let book_id = path.into_inner();
+pub async fn get_one(
+    path: web::Path<Uuid>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
     let connection = db.get_ref();
-    match EntityBook::find_by_id(book_id)
-        .into_json()
-        .one(connection)
-        .await
-    {
-        Ok(Some(data)) => HttpResponse::Ok().json(data),
-        Ok(None) => {
-            warn!("Unable to load data (Book::get_one): Book not found");
-            HttpResponse::NotFound().finish()
-        }
-        Err(err) => {
-            warn!("Unable to load data (Book::get_one): {}", err);
-            HttpResponse::NotFound().finish()
-        }
-    }
+    let book = find_book(connection, path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(book_response(connection, book).await?))
 }
 
 #[post("")]
 pub async fn create(
-    book: web::Json<ModelBook>,
+    book: web::Json<BookRequest>,
     db: web::Data<DatabaseConnection>,
-) -> impl Responder {
-    warn!("Creating book: {:?}", book);
-    let connection = db.get_ref();
-    match ActiveModelBook::from(book.0).insert(connection).await {
-        Ok(data) => HttpResponse::Ok().json(data.try_into_model().unwrap()),
-        Err(err) => {
-            warn!("Unable to insert data (Book::create): {}", err);
-            HttpResponse::InternalServerError().finish()
-        }
-    }
+) -> Result<HttpResponse, ApiError> {
+    book.validate()?;
+    let data = ActiveModelBook::from(book.into_inner())
+        .insert(db.get_ref())
+        .await?;
+    // A new book has no copies yet
+    Ok(HttpResponse::Ok().json(BookResponse::new(data, Availability::default())))
 }
 
 #[put("/{id}")]
 pub async fn update(
     path: web::Path<Uuid>,
-    book: web::Json<ModelBook>,
+    book: web::Json<BookRequest>,
     db: web::Data<DatabaseConnection>,
-) -> impl Responder {
-    let book_id = path.into_inner();
+) -> Result<HttpResponse, ApiError> {
+    book.validate()?;
     let connection = db.get_ref();
-    match EntityBook::find_by_id(book_id).one(connection).await {
-        Ok(Some(data)) => {
-            let mut model: ActiveModelBook = data.into();
-            model.merge(book.0);
-            match model.update(connection).await {
-                Ok(data) => HttpResponse::Ok().json(data),
-                Err(err) => {
-                    warn!("Unable to update data (Book::update): {}", err);
-                    HttpResponse::InternalServerError().finish()
-                }
-            }
-        }
-        Ok(None) => {
-            warn!("Unable to load data (Book::update): Book not found");
-            HttpResponse::NotFound().finish()
-        }
-        Err(err) => {
-            warn!("Unable to load data (Book::update): {}", err);
-            HttpResponse::NotFound().finish()
-        }
-    }
+    let mut model: ActiveModelBook = find_book(connection, path.into_inner()).await?.into();
+    book.into_inner().apply_to(&mut model);
+    let book = model.update(connection).await?;
+    Ok(HttpResponse::Ok().json(book_response(connection, book).await?))
 }
 
 #[delete("/{id}")]
-pub async fn delete(path: web::Path<Uuid>, db: web::Data<DatabaseConnection>) -> impl Responder {
-    let book_id = path.into_inner();
+pub async fn delete(
+    path: web::Path<Uuid>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
     let connection = db.get_ref();
-    match EntityBook::find_by_id(book_id).one(connection).await {
-        Ok(Some(data)) => {
-            let model: ActiveModelBook = data.into();
-            match model.delete(connection).await {
-                Ok(_) => HttpResponse::Ok().json(DeletedRecord {
-                    status: true,
-                    message: "Record deleted successfully".to_string(),
-                }),
-                Err(err) => {
-                    warn!("Unable to delete data (Book::delete): {}", err);
-                    HttpResponse::InternalServerError().finish()
-                }
-            }
-        }
-        Ok(None) => {
-            warn!("Unable to load data (Book::delete): Book not found");
-            HttpResponse::NotFound().finish()
-        }
-        Err(err) => {
-            warn!("Unable to load data (Book::delete): {}", err);
-            HttpResponse::NotFound().finish()
-        }
-    }
+    let model: ActiveModelBook = find_book(connection, path.into_inner()).await?.into();
+    model.delete(connection).await?;
+    Ok(HttpResponse::Ok().json(DeletedRecord {
+        status: true,
+        message: "Record deleted successfully".to_string(),
+    }))
 }
@@ -0,0 +1,143 @@
+use crate::config::Config;
+use crate::dto::copies::{CopyRequest, CopyResponse};
+use crate::errors::ApiError;
+use crate::models::copies::{
+    ActiveModel as ActiveModelCopy, Column as ColumnCopy, CopyStatus, Entity as EntityCopy,
+    Model as ModelCopy,
+};
+use crate::routes::books::find_book;
+use crate::services::circulation::copy_is_reserved;
+use crate::services::holds::shelve;
+use actix_web::{delete, get, post, put, web, HttpResponse};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Serialize, Deserialize)]
+struct DeletedRecord {
+    status: bool,
+    message: String,
+}
+
+pub async fn find_copy(
+    connection: &DatabaseConnection,
+    copy_id: Uuid,
+) -> Result<ModelCopy, ApiError> {
+    EntityCopy::find_by_id(copy_id)
+        .one(connection)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Copy not found.".to_string()))
+}
+
+#[get("/{id}/copies")]
+pub async fn get_for_book(
+    path: web::Path<Uuid>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let connection = db.get_ref();
+    let book = find_book(connection, path.into_inner()).await?;
+    let copies = EntityCopy::find()
+        .filter(ColumnCopy::BookId.eq(book.id))
+        .order_by_asc(ColumnCopy::Barcode)
+        .all(connection)
+        .await?;
+    let data: Vec<CopyResponse> = copies.into_iter().map(CopyResponse::from).collect();
+    Ok(HttpResponse::Ok().json(data))
+}
+
+/// Hand a copy that is on the shelf to the book's hold queue, if anyone is waiting.
+async fn offer_to_holds(
+    connection: &DatabaseConnection,
+    config: &Config,
+    copy: ModelCopy,
+) -> Result<ModelCopy, ApiError> {
+    if copy.status != CopyStatus::Available {
+        return Ok(copy);
+    }
+    shelve(connection, &config.loans, copy.id).await?;
+    find_copy(connection, copy.id).await
+}
+
+#[post("/{id}/copies")]
+pub async fn create(
+    path: web::Path<Uuid>,
+    copy: web::Json<CopyRequest>,
+    config: web::Data<Config>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    copy.validate()?;
+    if matches!(copy.status, Some(CopyStatus::OnHold | CopyStatus::OnLoan)) {
+        return Err(ApiError::Conflict(
+            "Copy status is managed by its reservation.".to_string(),
+        ));
+    }
+    let connection = db.get_ref();
+    let book = find_book(connection, path.into_inner()).await?;
+    let data = copy
+        .into_inner()
+        .into_active_model(book.id)
+        .insert(connection)
+        .await?;
+    let data = offer_to_holds(connection, &config, data).await?;
+    Ok(HttpResponse::Ok().json(CopyResponse::from(data)))
+}
+
+#[get("/{id}")]
+pub async fn get_one(
+    path: web::Path<Uuid>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let copy = find_copy(db.get_ref(), path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(CopyResponse::from(copy)))
+}
+
+#[put("/{id}")]
+pub async fn update(
+    path: web::Path<Uuid>,
+    copy: web::Json<CopyRequest>,
+    config: web::Data<Config>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    copy.validate()?;
+    let connection = db.get_ref();
+    let txn = connection.begin().await?;
+    let stored = EntityCopy::find_by_id(path.into_inner())
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Copy not found.".to_string()))?;
+    // Holds and loans move a copy through circulation, never by hand
+    if let Some(status) = copy.status.filter(|status| *status != stored.status) {
+        if matches!(status, CopyStatus::OnHold | CopyStatus::OnLoan)
+            || copy_is_reserved(&txn, stored.id).await?
+        {
+            return Err(ApiError::Conflict(
+                "Copy status is managed by its reservation.".to_string(),
+            ));
+        }
+    }
+    let mut model: ActiveModelCopy = stored.into();
+    copy.into_inner().apply_to(&mut model);
+    let data = model.update(&txn).await?;
+    txn.commit().await?;
+    let data = offer_to_holds(connection, &config, data).await?;
+    Ok(HttpResponse::Ok().json(CopyResponse::from(data)))
+}
+
+#[delete("/{id}")]
+pub async fn delete(
+    path: web::Path<Uuid>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let connection = db.get_ref();
+    let model: ActiveModelCopy = find_copy(connection, path.into_inner()).await?.into();
+    model.delete(connection).await?;
+    Ok(HttpResponse::Ok().json(DeletedRecord {
+        status: true,
+        message: "Record deleted successfully".to_string(),
+    }))
+}
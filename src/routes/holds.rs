@@ -0,0 +1,99 @@
+use crate::config::Config;
+use crate::dto::holds::{HoldResponse, HoldsQuery};
+use crate::errors::ApiError;
+use crate::middleware::auth::AuthenticatedUser;
+use crate::models::reservations::{
+    Column as ColumnReservation, Entity as EntityReservation, ReservationStatus,
+};
+use crate::models::users::Role;
+use crate::routes::books::find_book;
+use crate::routes::reservations::find_reservation;
+use crate::services::circulation::transition;
+use crate::services::holds::{queue, queue_position};
+use actix_web::{delete, get, web, HttpResponse};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder};
+use uuid::Uuid;
+
+/// Holds waiting for a copy or for their pickup.
+const OPEN_HOLD_STATUSES: [ReservationStatus; 2] = [
+    ReservationStatus::Requested,
+    ReservationStatus::ReadyForPickup,
+];
+
+#[get("")]
+pub async fn get_all(
+    auth: AuthenticatedUser,
+    query: web::Query<HoldsQuery>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = match query.user_id {
+        Some(user_id) if auth.can_act_for(user_id, Role::Librarian) => user_id,
+        Some(_) => {
+            return Err(ApiError::Forbidden(
+                "Only staff can see the holds of other users.".to_string(),
+            ))
+        }
+        None => auth
+            .user_id()
+            .ok_or_else(|| ApiError::Unauthorized("Invalid token subject.".to_string()))?,
+    };
+    let connection = db.get_ref();
+    let holds = EntityReservation::find()
+        .filter(ColumnReservation::UserId.eq(user_id))
+        .filter(ColumnReservation::Status.is_in(OPEN_HOLD_STATUSES))
+        .order_by_asc(ColumnReservation::CreatedAt)
+        .all(connection)
+        .await?;
+    let mut data = Vec::with_capacity(holds.len());
+    for hold in holds {
+        let position = queue_position(connection, &hold).await?;
+        data.push(HoldResponse::new(hold, position));
+    }
+    Ok(HttpResponse::Ok().json(data))
+}
+
+/// Give up a place in line, or a copy waiting for pickup.
+#[delete("/{id}")]
+pub async fn cancel(
+    path: web::Path<Uuid>,
+    auth: AuthenticatedUser,
+    config: web::Data<Config>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let connection = db.get_ref();
+    let hold = find_reservation(connection, path.into_inner()).await?;
+    if !OPEN_HOLD_STATUSES.contains(&hold.status) {
+        return Err(ApiError::NotFound("Hold not found.".to_string()));
+    }
+    if !auth.can_act_for(hold.user_id, Role::Librarian) {
+        return Err(ApiError::Forbidden(
+            "Hold belongs to another user.".to_string(),
+        ));
+    }
+    let data = transition(
+        connection,
+        &config.loans,
+        hold.id,
+        ReservationStatus::Cancelled,
+        auth.user_id(),
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(HoldResponse::new(data, None)))
+}
+
+/// The line of patrons waiting for a book, first in line first.
+#[get("/{id}/holds")]
+pub async fn get_for_book(
+    path: web::Path<Uuid>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let connection = db.get_ref();
+    let book = find_book(connection, path.into_inner()).await?;
+    let holds = queue(book.id).all(connection).await?;
+    let data: Vec<HoldResponse> = holds
+        .into_iter()
+        .zip(1..)
+        .map(|(hold, position)| HoldResponse::new(hold, Some(position)))
+        .collect();
+    Ok(HttpResponse::Ok().json(data))
+}
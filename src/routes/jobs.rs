@@ -0,0 +1,41 @@
+use crate::dto::jobs::{JobRunFilter, JobRunResponse, JOB_RUN_SORTABLE};
+use crate::errors::ApiError;
+use crate::middleware::auth::AuthenticatedUser;
+use crate::models::job_runs::{Entity as EntityJobRun, Job, JobTrigger};
+use crate::services::scheduler::Scheduler;
+use crate::utils::pagination::{page_response, paginate, PageParams};
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use sea_orm::{DatabaseConnection, EntityTrait};
+
+#[get("/runs")]
+pub async fn get_runs(
+    req: HttpRequest,
+    params: web::Query<PageParams>,
+    filter: web::Query<JobRunFilter>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let select = filter.apply(EntityJobRun::find());
+    let page = paginate(
+        db.get_ref(),
+        select,
+        &params,
+        JOB_RUN_SORTABLE,
+        "-started_at",
+    )
+    .await?;
+    Ok(page_response(&req, page.map(JobRunResponse::from)))
+}
+
+/// Run a job right away instead of waiting for its next scheduled run.
+#[post("/{job}/run")]
+pub async fn run(
+    path: web::Path<Job>,
+    auth: AuthenticatedUser,
+    scheduler: web::Data<Scheduler>,
+) -> Result<HttpResponse, ApiError> {
+    let run = scheduler
+        .run(path.into_inner(), JobTrigger::Manual, auth.user_id())
+        .await?
+        .ok_or_else(|| ApiError::Conflict("The job is already running.".to_string()))?;
+    Ok(HttpResponse::Ok().json(JobRunResponse::from(run)))
+}
@@ -0,0 +1,10 @@
+use crate::services::jwt_keys::JwtKeys;
+use actix_web::{get, http::header, web, HttpResponse, Responder};
+
+/// Public keys other services use to validate our access tokens.
+#[get("/.well-known/jwks.json")]
+pub async fn jwks(keys: web::Data<JwtKeys>) -> impl Responder {
+    HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "public, max-age=300"))
+        .json(keys.jwks())
+}
@@ -0,0 +1,123 @@
+use crate::config::Config;
+use crate::dto::ledger::{
+    BalanceResponse, LedgerEntryRequest, LedgerEntryResponse, LEDGER_SORTABLE,
+};
+use crate::errors::ApiError;
+use crate::middleware::auth::AuthenticatedUser;
+use crate::models::ledger_entries::{
+    Column as ColumnLedgerEntry, Entity as EntityLedgerEntry, LedgerKind,
+};
+use crate::models::users::Role;
+use crate::routes::reservations::find_reservation;
+use crate::routes::users::find_user;
+use crate::services::fines::{balance, record_entry};
+use crate::utils::pagination::{page_response, paginate, PageParams};
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use uuid::Uuid;
+use validator::Validate;
+
+/// Patrons may see their own account, staff may see anyone's.
+fn owner_validation(auth: &AuthenticatedUser, user_id: Uuid) -> Result<(), ApiError> {
+    if auth.can_act_for(user_id, Role::Librarian) {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden(
+            "Account belongs to another user.".to_string(),
+        ))
+    }
+}
+
+#[get("/{id}/balance")]
+pub async fn get_balance(
+    path: web::Path<Uuid>,
+    auth: AuthenticatedUser,
+    config: web::Data<Config>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = path.into_inner();
+    owner_validation(&auth, user_id)?;
+    let connection = db.get_ref();
+    let user = find_user(connection, user_id).await?;
+    // Fines of loans still out are brought up to date by the scheduler
+    let balance = balance(connection, user.id).await?;
+    Ok(HttpResponse::Ok().json(BalanceResponse {
+        user_id: user.id,
+        balance,
+        blocked: balance.balance_cents > config.loans.max_balance_cents,
+    }))
+}
+
+#[get("/{id}/ledger")]
+pub async fn get_ledger(
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    auth: AuthenticatedUser,
+    params: web::Query<PageParams>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = path.into_inner();
+    owner_validation(&auth, user_id)?;
+    let select = EntityLedgerEntry::find().filter(ColumnLedgerEntry::UserId.eq(user_id));
+    let page = paginate(
+        db.get_ref(),
+        select,
+        &params,
+        LEDGER_SORTABLE,
+        "-created_at",
+    )
+    .await?;
+    Ok(page_response(&req, page.map(LedgerEntryResponse::from)))
+}
+
+async fn record(
+    path: web::Path<Uuid>,
+    auth: AuthenticatedUser,
+    entry: web::Json<LedgerEntryRequest>,
+    db: web::Data<DatabaseConnection>,
+    kind: LedgerKind,
+) -> Result<HttpResponse, ApiError> {
+    entry.validate()?;
+    let connection = db.get_ref();
+    let user = find_user(connection, path.into_inner()).await?;
+    let entry = entry.into_inner();
+    if let Some(reservation_id) = entry.reservation_id {
+        let reservation = find_reservation(connection, reservation_id).await?;
+        if reservation.user_id != user.id {
+            return Err(ApiError::BadRequest(
+                "Reservation belongs to another user.".to_string(),
+            ));
+        }
+    }
+    let data = record_entry(
+        connection,
+        user.id,
+        kind,
+        entry.amount_cents,
+        entry.reservation_id,
+        entry.note,
+        auth.user_id(),
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(LedgerEntryResponse::from(data)))
+}
+
+#[post("/{id}/payments")]
+pub async fn create_payment(
+    path: web::Path<Uuid>,
+    auth: AuthenticatedUser,
+    entry: web::Json<LedgerEntryRequest>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    record(path, auth, entry, db, LedgerKind::Payment).await
+}
+
+#[post("/{id}/waivers")]
+pub async fn create_waiver(
+    path: web::Path<Uuid>,
+    auth: AuthenticatedUser,
+    entry: web::Json<LedgerEntryRequest>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    record(path, auth, entry, db, LedgerKind::Waiver).await
+}
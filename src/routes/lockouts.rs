@@ -0,0 +1,29 @@
+use crate::errors::ApiError;
+use crate::services::lockout::Lockout;
+use actix_web::{delete, web, HttpResponse};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct LockoutTarget {
+    email: Option<String>,
+    ip: Option<String>,
+}
+
+#[delete("")]
+pub async fn clear(
+    target: web::Query<LockoutTarget>,
+    lockout: web::Data<Lockout>,
+) -> Result<HttpResponse, ApiError> {
+    if target.email.is_none() && target.ip.is_none() {
+        return Err(ApiError::BadRequest(
+            "Give an email or an ip to clear.".to_string(),
+        ));
+    }
+    if let Some(email) = &target.email {
+        lockout.clear_account(email.as_str()).await?;
+    }
+    if let Some(ip) = &target.ip {
+        lockout.clear_ip(ip.as_str()).await?;
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,63 @@
+use crate::config::Config;
+use crate::dto::reservations::{ReservationFilter, ReservationResponse, RESERVATION_SORTABLE};
+use crate::dto::users::{UpdateUserRequest, UserResponse};
+use crate::errors::ApiError;
+use crate::middleware::auth::AuthenticatedUser;
+use crate::models::reservations::Entity as EntityReservation;
+use crate::routes::users::{find_user, update_user};
+use crate::utils::pagination::{page_response, paginate, PageParams};
+use actix_web::{get, put, web, HttpRequest, HttpResponse};
+use sea_orm::{DatabaseConnection, EntityTrait};
+use uuid::Uuid;
+use validator::Validate;
+
+/// The caller's user id, as carried in the token's `sub`.
+pub fn caller_id(auth: &AuthenticatedUser) -> Result<Uuid, ApiError> {
+    auth.user_id()
+        .ok_or_else(|| ApiError::Unauthorized("Invalid token subject.".to_string()))
+}
+
+#[get("")]
+pub async fn get_profile(
+    auth: AuthenticatedUser,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let user = find_user(db.get_ref(), caller_id(&auth)?).await?;
+    Ok(HttpResponse::Ok().json(UserResponse::from(user)))
+}
+
+#[put("")]
+pub async fn update_profile(
+    auth: AuthenticatedUser,
+    user: web::Json<UpdateUserRequest>,
+    db: web::Data<DatabaseConnection>,
+    config: web::Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = caller_id(&auth)?;
+    let data = update_user(db.get_ref(), &config, &auth, user_id, user.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(UserResponse::from(data)))
+}
+
+/// The caller's borrowing history, current loans and holds included.
+#[get("/reservations")]
+pub async fn get_reservations(
+    req: HttpRequest,
+    auth: AuthenticatedUser,
+    params: web::Query<PageParams>,
+    filter: web::Query<ReservationFilter>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    filter.validate()?;
+    let mut filter = filter.into_inner();
+    filter.user_id = Some(caller_id(&auth)?);
+    let select = filter.apply(EntityReservation::find());
+    let page = paginate(
+        db.get_ref(),
+        select,
+        &params,
+        RESERVATION_SORTABLE,
+        "-reservation_date",
+    )
+    .await?;
+    Ok(page_response(&req, page.map(ReservationResponse::from)))
+}
@@ -0,0 +1,53 @@
+use crate::errors::ApiError;
+use crate::middleware::auth::AuthenticatedUser;
+use crate::models::users::{Entity as EntityUser, Model as ModelUser};
+use crate::services::mfa::{confirm_enrollment, start_enrollment};
+use actix_web::{post, web, HttpResponse};
+use sea_orm::{DatabaseConnection, EntityTrait};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+pub struct Confirmation {
+    code: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecoveryCodes {
+    recovery_codes: Vec<String>,
+}
+
+async fn current_user(
+    auth: &AuthenticatedUser,
+    connection: &DatabaseConnection,
+) -> Result<ModelUser, ApiError> {
+    let user_id = Uuid::parse_str(auth.sub.as_str())
+        .map_err(|_| ApiError::Unauthorized("Invalid user.".to_string()))?;
+    EntityUser::find_by_id(user_id)
+        .one(connection)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("User not found.".to_string()))
+}
+
+#[post("/enroll")]
+pub async fn enroll(
+    auth: AuthenticatedUser,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let connection = db.get_ref();
+    let user = current_user(&auth, connection).await?;
+    let enrollment = start_enrollment(connection, &user).await?;
+    Ok(HttpResponse::Ok().json(enrollment))
+}
+
+#[post("/confirm")]
+pub async fn confirm(
+    auth: AuthenticatedUser,
+    form: web::Form<Confirmation>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let connection = db.get_ref();
+    let user = current_user(&auth, connection).await?;
+    let recovery_codes = confirm_enrollment(connection, &user, form.code.as_str()).await?;
+    Ok(HttpResponse::Ok().json(RecoveryCodes { recovery_codes }))
+}
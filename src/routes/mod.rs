@@ -1,6 +1,17 @@
 pub mod authentication;
 pub mod books;
+pub mod copies;
+pub mod holds;
 pub mod index;
+pub mod jobs;
+pub mod jwks;
+pub mod ledger;
+pub mod lockouts;
+pub mod me;
+pub mod mfa;
+pub mod notifications;
+pub mod password;
 pub mod register;
 pub mod reservations;
+pub mod signup;
 pub mod users;
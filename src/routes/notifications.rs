@@ -0,0 +1,108 @@
+use crate::config::Config;
+use crate::dto::notifications::{
+    NotificationFilter, NotificationPreferencesRequest, NotificationPreferencesResponse,
+    NotificationResponse, NOTIFICATION_SORTABLE,
+};
+use crate::errors::ApiError;
+use crate::middleware::auth::AuthenticatedUser;
+use crate::models::notifications::{
+    ActiveModel as ActiveModelNotification, Entity as EntityNotification, NotificationStatus,
+};
+use crate::models::users::{ActiveModel as ActiveModelUser, Role};
+use crate::routes::users::find_user;
+use crate::utils::pagination::{page_response, paginate, PageParams};
+use actix_web::{get, post, put, web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, Set};
+use uuid::Uuid;
+use validator::Validate;
+
+/// Users may only manage their own preferences, unless they are an admin.
+fn owner_validation(auth: &AuthenticatedUser, user_id: Uuid) -> Result<(), ApiError> {
+    if auth.can_act_for(user_id, Role::Admin) {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden(
+            "Only admins may manage other accounts.".to_string(),
+        ))
+    }
+}
+
+#[get("/{id}/notification-preferences")]
+pub async fn get_preferences(
+    path: web::Path<Uuid>,
+    auth: AuthenticatedUser,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = path.into_inner();
+    owner_validation(&auth, user_id)?;
+    let user = find_user(db.get_ref(), user_id).await?;
+    Ok(HttpResponse::Ok().json(NotificationPreferencesResponse::from(user)))
+}
+
+#[put("/{id}/notification-preferences")]
+pub async fn update_preferences(
+    path: web::Path<Uuid>,
+    auth: AuthenticatedUser,
+    preferences: web::Json<NotificationPreferencesRequest>,
+    config: web::Data<Config>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = path.into_inner();
+    owner_validation(&auth, user_id)?;
+    preferences.validate()?;
+    if preferences.webhook == Some(true) && config.notifications.webhook_url.is_none() {
+        return Err(ApiError::BadRequest(
+            "No webhook is configured for notifications.".to_string(),
+        ));
+    }
+
+    let connection = db.get_ref();
+    let mut model: ActiveModelUser = find_user(connection, user_id).await?.into();
+    preferences.into_inner().apply_to(&mut model);
+    let user = model.update(connection).await?;
+    Ok(HttpResponse::Ok().json(NotificationPreferencesResponse::from(user)))
+}
+
+#[get("")]
+pub async fn get_all(
+    req: HttpRequest,
+    params: web::Query<PageParams>,
+    filter: web::Query<NotificationFilter>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let select = filter.apply(EntityNotification::find());
+    let page = paginate(
+        db.get_ref(),
+        select,
+        &params,
+        NOTIFICATION_SORTABLE,
+        "-created_at",
+    )
+    .await?;
+    Ok(page_response(&req, page.map(NotificationResponse::from)))
+}
+
+/// Queue a notification again, with a fresh set of attempts.
+#[post("/{id}/retry")]
+pub async fn retry(
+    path: web::Path<Uuid>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let connection = db.get_ref();
+    let notification = EntityNotification::find_by_id(path.into_inner())
+        .one(connection)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Notification not found.".to_string()))?;
+    if notification.status == NotificationStatus::Sent {
+        return Err(ApiError::Conflict(
+            "The notification was already sent.".to_string(),
+        ));
+    }
+    let mut model: ActiveModelNotification = notification.into();
+    model.status = Set(NotificationStatus::Pending);
+    model.attempts = Set(0);
+    model.next_attempt_at = Set(Utc::now().naive_utc());
+    let notification = model.update(connection).await?;
+    Ok(HttpResponse::Ok().json(NotificationResponse::from(notification)))
+}
@@ -0,0 +1,50 @@
+use crate::config::{Config, PasswordConfig};
+use crate::errors::ApiError;
+use crate::services::mailer::Mailer;
+use crate::services::password_reset::{request_password_reset, reset_password};
+use crate::utils::validation::password_strength;
+use actix_web::{post, web, HttpResponse};
+use sea_orm::DatabaseConnection;
+use serde::Deserialize;
+use validator::{Validate, ValidateArgs};
+
+#[derive(Deserialize)]
+pub struct ForgotPassword {
+    email: String,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct ResetPassword {
+    token: String,
+    #[validate(custom(function = "password_strength", arg = "&'v_a PasswordConfig"))]
+    password: String,
+}
+
+#[post("/password/forgot")]
+pub async fn forgot(
+    form: web::Form<ForgotPassword>,
+    db: web::Data<DatabaseConnection>,
+    mailer: web::Data<dyn Mailer>,
+    config: web::Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+    request_password_reset(db.get_ref(), &config, mailer.get_ref(), form.email.as_str()).await?;
+    // Same answer whether or not the email is registered
+    Ok(HttpResponse::Accepted().finish())
+}
+
+#[post("/password/reset")]
+pub async fn reset(
+    form: web::Form<ResetPassword>,
+    db: web::Data<DatabaseConnection>,
+    config: web::Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+    form.validate_args(&config.password)?;
+    reset_password(
+        db.get_ref(),
+        &config.password,
+        form.token.as_str(),
+        form.password.as_str(),
+    )
+    .await?;
+    Ok(HttpResponse::NoContent().finish())
+}
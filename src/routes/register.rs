@@ -1,35 +1,145 @@
-use crate::middleware::auth::JwtValidator;
+use crate::errors::ApiError;
+use crate::middleware::{auth::JwtValidator, role::RequireRole};
 use crate::routes::*;
 use actix_web::web;
 
+/// Malformed bodies, paths and queries answer with the same problem documents as handlers.
+fn extractor_configs(cfg: &mut web::ServiceConfig) {
+    cfg.app_data(
+        web::JsonConfig::default()
+            .error_handler(|err, _| ApiError::BadRequest(err.to_string()).into()),
+    )
+    .app_data(
+        web::FormConfig::default()
+            .error_handler(|err, _| ApiError::BadRequest(err.to_string()).into()),
+    )
+    .app_data(
+        web::PathConfig::default()
+            .error_handler(|err, _| ApiError::NotFound(err.to_string()).into()),
+    )
+    .app_data(
+        web::QueryConfig::default()
+            .error_handler(|err, _| ApiError::BadRequest(err.to_string()).into()),
+    );
+}
+
 pub fn configure(cfg: &mut web::ServiceConfig) {
+    extractor_configs(cfg);
     cfg.service(index::index)
+        .service(jwks::jwks)
         .service(authentication::login)
+        .service(authentication::login_mfa)
+        .service(authentication::refresh)
+        .service(signup::register)
+        .service(signup::verify)
+        .service(signup::resend)
+        .service(password::forgot)
+        .service(password::reset)
+        .service(
+            web::scope("/logout")
+                .wrap(JwtValidator)
+                .service(authentication::logout),
+        )
         .service(
             web::scope("/api")
                 .wrap(JwtValidator)
+                .service(
+                    web::scope("/me")
+                        .service(me::get_profile)
+                        .service(me::update_profile)
+                        .service(me::get_reservations),
+                )
                 .service(
                     web::scope("/books")
                         .service(books::get_all)
+                        .service(books::search)
                         .service(books::get_one)
-                        .service(books::create)
-                        .service(books::update)
-                        .service(books::delete),
+                        .service(copies::get_for_book)
+                        .service(
+                            web::scope("")
+                                .wrap(RequireRole::librarian())
+                                .service(books::create)
+                                .service(books::update)
+                                .service(books::delete)
+                                .service(copies::create)
+                                .service(holds::get_for_book),
+                        ),
+                )
+                .service(
+                    web::scope("/copies").service(copies::get_one).service(
+                        web::scope("")
+                            .wrap(RequireRole::librarian())
+                            .service(copies::update)
+                            .service(copies::delete),
+                    ),
                 )
                 .service(
                     web::scope("/reservations")
                         .service(reservations::get_all)
                         .service(reservations::get_one)
+                        .service(reservations::get_events)
                         .service(reservations::create)
-                        .service(reservations::update)
-                        .service(reservations::delete),
+                        .service(reservations::cancel)
+                        .service(reservations::renew_loan)
+                        .service(
+                            web::scope("")
+                                .wrap(RequireRole::librarian())
+                                .service(reservations::checkout)
+                                .service(reservations::return_copy)
+                                .service(reservations::mark_lost)
+                                .service(reservations::delete),
+                        ),
+                )
+                .service(
+                    web::scope("/holds")
+                        .service(holds::get_all)
+                        .service(holds::cancel),
                 )
                 .service(
                     web::scope("/users")
                         .service(users::get_one)
-                        .service(users::create)
                         .service(users::update)
-                        .service(users::delete),
+                        .service(users::delete)
+                        .service(ledger::get_balance)
+                        .service(ledger::get_ledger)
+                        .service(notifications::get_preferences)
+                        .service(notifications::update_preferences)
+                        .service(
+                            // Nested, since an empty scope answers every path it doesn't route
+                            web::scope("")
+                                .wrap(RequireRole::librarian())
+                                .service(ledger::create_payment)
+                                .service(ledger::create_waiver)
+                                .service(
+                                    web::scope("")
+                                        .wrap(RequireRole::admin())
+                                        .service(users::create)
+                                        .service(users::suspend)
+                                        .service(users::reactivate),
+                                ),
+                        ),
+                )
+                .service(
+                    web::scope("/mfa")
+                        .service(mfa::enroll)
+                        .service(mfa::confirm),
+                )
+                .service(
+                    web::scope("/jobs")
+                        .wrap(RequireRole::admin())
+                        .service(jobs::get_runs)
+                        .service(jobs::run),
+                )
+                .service(
+                    web::scope("/notifications")
+                        .wrap(RequireRole::admin())
+                        .service(notifications::get_all)
+                        .service(notifications::retry),
+                )
+                .service(
+                    web::scope("/lockouts")
+                        .wrap(RequireRole::admin())
+                        .service(lockouts::clear),
                 ),
         );
 }
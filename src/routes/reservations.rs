@@ -1,11 +1,26 @@
+use crate::config::Config;
+use crate::dto::reservations::{
+    ReservationEventResponse, ReservationFilter, ReservationRequest, ReservationResponse,
+    RESERVATION_SORTABLE,
+};
+use crate::errors::ApiError;
+use crate::middleware::auth::AuthenticatedUser;
+use crate::models::reservation_events::{
+    Column as ColumnReservationEvent, Entity as EntityReservationEvent,
+};
 use crate::models::reservations::{
-    ActiveModel as ActiveModelReservation, Entity as EntityReservation, Model as ModelReservation,
+    Entity as EntityReservation, Model as ModelReservation, ReservationStatus,
 };
-use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
-use log::warn;
-use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, TryIntoModel};
+use crate::models::users::Role;
+use crate::routes::me::caller_id;
+use crate::services::circulation::{release, renew, reserve, transition};
+use crate::services::holds::expire_pickups;
+use crate::utils::pagination::{page_response, paginate, PageParams};
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
+use validator::Validate;
 
 #[derive(Serialize, Deserialize)]
 struct DeletedRecord {
@@ -13,120 +28,217 @@ struct DeletedRecord {
     message: String,
 }
 
-#[get("")]
-pub async fn get_all(db: web::Data<DatabaseConnection>) -> impl Responder {
-    let connection = db.get_ref();
-    match EntityReservation::find().into_json().all(connection).await {
-        Ok(data) => HttpResponse::Ok().json(data),
-        Err(err) => {
-            warn!("Unable to load data (Reservation::get_all): {}", err);
-            HttpResponse::InternalServerError().finish()
-        }
+/// Members may only touch their own reservations, staff may touch anyone's.
+fn owner_validation(auth: &AuthenticatedUser, user_id: Uuid) -> Result<(), ApiError> {
+    if auth.can_act_for(user_id, Role::Librarian) {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden(
+            "Reservation belongs to another user.".to_string(),
+        ))
     }
 }
 
-#[get("/{id}")]
-pub async fn get_one(path: web::Path<Uuid>, db: web::Data<DatabaseConnection>) -> impl Responder {
-    let reservation_id = path.into_inner();
-    let connection = db.get_ref();
-    match EntityReservation::find_by_id(reservation_id)
-        .into_json()
+pub async fn find_reservation(
+    connection: &DatabaseConnection,
+    reservation_id: Uuid,
+) -> Result<ModelReservation, ApiError> {
+    EntityReservation::find_by_id(reservation_id)
         .one(connection)
-        .await
-    {
-        Ok(Some(data)) => HttpResponse::Ok().json(data),
-        Ok(None) => {
-            warn!("Unable to load data (Reservation::get_one): Reservation not found");
-            HttpResponse::NotFound().finish()
-        }
-        Err(err) => {
-            warn!("Unable to load data (Reservation::get_one): {}", err);
-            HttpResponse::NotFound().finish()
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Reservation not found.".to_string()))
+}
+
+#[get("")]
+pub async fn get_all(
+    req: HttpRequest,
+    auth: AuthenticatedUser,
+    params: web::Query<PageParams>,
+    filter: web::Query<ReservationFilter>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    filter.validate()?;
+    let mut filter = filter.into_inner();
+    // Members only ever list their own reservations
+    if !auth.has_role(Role::Librarian) {
+        match filter.user_id {
+            Some(user_id) if !auth.can_act_for(user_id, Role::Librarian) => {
+                return Err(ApiError::Forbidden(
+                    "Only staff can see the reservations of other users.".to_string(),
+                ))
+            }
+            _ => filter.user_id = Some(caller_id(&auth)?),
         }
     }
+    let select = filter.apply(EntityReservation::find());
+    let page = paginate(
+        db.get_ref(),
+        select,
+        &params,
+        RESERVATION_SORTABLE,
+        "-reservation_date",
+    )
+    .await?;
+    Ok(page_response(&req, page.map(ReservationResponse::from)))
+}
+
+#[get("/{id}")]
+pub async fn get_one(
+    path: web::Path<Uuid>,
+    auth: AuthenticatedUser,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let reservation = find_reservation(db.get_ref(), path.into_inner()).await?;
+    owner_validation(&auth, reservation.user_id)?;
+    Ok(HttpResponse::Ok().json(ReservationResponse::from(reservation)))
 }
 
 #[post("")]
 pub async fn create(
-    reservation: web::Json<ModelReservation>,
+    auth: AuthenticatedUser,
+    reservation: web::Json<ReservationRequest>,
+    config: web::Data<Config>,
     db: web::Data<DatabaseConnection>,
-) -> impl Responder {
-    warn!("Creating reservation: {:?}", reservation);
-    let connection = db.get_ref();
-    match ActiveModelReservation::from(reservation.0)
-        .insert(connection)
-        .await
-    {
-        Ok(data) => HttpResponse::Ok().json(data.try_into_model().unwrap()),
-        Err(err) => {
-            warn!("Unable to insert data (Reservation::create): {}", err);
-            HttpResponse::InternalServerError().finish()
-        }
+) -> Result<HttpResponse, ApiError> {
+    reservation.validate()?;
+    owner_validation(&auth, reservation.user_id)?;
+    if let Some(book_id) = reservation.book_id {
+        // Free copies whose pickup lapsed before deciding whether to queue
+        expire_pickups(db.get_ref(), &config.loans, Some(book_id)).await?;
     }
+    let data = reserve(
+        db.get_ref(),
+        &config.loans,
+        reservation.into_inner(),
+        auth.user_id(),
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(ReservationResponse::from(data)))
 }
 
-#[put("/{id}")]
-pub async fn update(
+#[get("/{id}/events")]
+pub async fn get_events(
     path: web::Path<Uuid>,
-    reservation: web::Json<ModelReservation>,
+    auth: AuthenticatedUser,
     db: web::Data<DatabaseConnection>,
-) -> impl Responder {
-    let reservation_id = path.into_inner();
+) -> Result<HttpResponse, ApiError> {
     let connection = db.get_ref();
-    match EntityReservation::find_by_id(reservation_id)
-        .one(connection)
-        .await
-    {
-        Ok(Some(data)) => {
-            let mut model: ActiveModelReservation = data.into();
-            model.merge(reservation.0);
-            match model.update(connection).await {
-                Ok(data) => HttpResponse::Ok().json(data),
-                Err(err) => {
-                    warn!("Unable to update data (Reservation::update): {}", err);
-                    HttpResponse::InternalServerError().finish()
-                }
-            }
-        }
-        Ok(None) => {
-            warn!("Unable to load data (Reservation::update): Reservation not found");
-            HttpResponse::NotFound().finish()
-        }
-        Err(err) => {
-            warn!("Unable to load data (Reservation::update): {}", err);
-            HttpResponse::NotFound().finish()
-        }
-    }
+    let reservation = find_reservation(connection, path.into_inner()).await?;
+    owner_validation(&auth, reservation.user_id)?;
+    let events = EntityReservationEvent::find()
+        .filter(ColumnReservationEvent::ReservationId.eq(reservation.id))
+        .order_by_asc(ColumnReservationEvent::CreatedAt)
+        .all(connection)
+        .await?;
+    Ok(HttpResponse::Ok().json(
+        events
+            .into_iter()
+            .map(ReservationEventResponse::from)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+/// Borrowers may withdraw their own reservations until they pick the copy up.
+#[post("/{id}/cancel")]
+pub async fn cancel(
+    path: web::Path<Uuid>,
+    auth: AuthenticatedUser,
+    config: web::Data<Config>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let connection = db.get_ref();
+    let reservation = find_reservation(connection, path.into_inner()).await?;
+    owner_validation(&auth, reservation.user_id)?;
+    let data = transition(
+        connection,
+        &config.loans,
+        reservation.id,
+        ReservationStatus::Cancelled,
+        auth.user_id(),
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(ReservationResponse::from(data)))
+}
+
+#[post("/{id}/renew")]
+pub async fn renew_loan(
+    path: web::Path<Uuid>,
+    auth: AuthenticatedUser,
+    config: web::Data<Config>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let connection = db.get_ref();
+    let reservation = find_reservation(connection, path.into_inner()).await?;
+    owner_validation(&auth, reservation.user_id)?;
+    let data = renew(connection, &config.loans, reservation.id).await?;
+    Ok(HttpResponse::Ok().json(ReservationResponse::from(data)))
+}
+
+#[post("/{id}/checkout")]
+pub async fn checkout(
+    path: web::Path<Uuid>,
+    auth: AuthenticatedUser,
+    config: web::Data<Config>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let data = transition(
+        db.get_ref(),
+        &config.loans,
+        path.into_inner(),
+        ReservationStatus::CheckedOut,
+        auth.user_id(),
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(ReservationResponse::from(data)))
+}
+
+#[post("/{id}/return")]
+pub async fn return_copy(
+    path: web::Path<Uuid>,
+    auth: AuthenticatedUser,
+    config: web::Data<Config>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let data = transition(
+        db.get_ref(),
+        &config.loans,
+        path.into_inner(),
+        ReservationStatus::Returned,
+        auth.user_id(),
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(ReservationResponse::from(data)))
+}
+
+#[post("/{id}/mark-lost")]
+pub async fn mark_lost(
+    path: web::Path<Uuid>,
+    auth: AuthenticatedUser,
+    config: web::Data<Config>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let data = transition(
+        db.get_ref(),
+        &config.loans,
+        path.into_inner(),
+        ReservationStatus::Lost,
+        auth.user_id(),
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(ReservationResponse::from(data)))
 }
 
 #[delete("/{id}")]
-pub async fn delete(path: web::Path<Uuid>, db: web::Data<DatabaseConnection>) -> impl Responder {
-    let reservation_id = path.into_inner();
+pub async fn delete(
+    path: web::Path<Uuid>,
+    config: web::Data<Config>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
     let connection = db.get_ref();
-    match EntityReservation::find_by_id(reservation_id)
-        .one(connection)
-        .await
-    {
-        Ok(Some(data)) => {
-            let model: ActiveModelReservation = data.into();
-            match model.delete(connection).await {
-                Ok(_) => HttpResponse::Ok().json(DeletedRecord {
-                    status: true,
-                    message: "Record deleted successfully".to_string(),
-                }),
-                Err(err) => {
-                    warn!("Unable to delete data (Reservation::delete): {}", err);
-                    HttpResponse::InternalServerError().finish()
-                }
-            }
-        }
-        Ok(None) => {
-            warn!("Unable to load data (Reservation::delete): Reservation not found");
-            HttpResponse::NotFound().finish()
-        }
-        Err(err) => {
-            warn!("Unable to load data (Reservation::delete): {}", err);
-            HttpResponse::NotFound().finish()
-        }
-    }
+    let data = find_reservation(connection, path.into_inner()).await?;
+    release(connection, &config.loans, data).await?;
+    Ok(HttpResponse::Ok().json(DeletedRecord {
+        status: true,
+        message: "Record deleted successfully".to_string(),
+    }))
 }
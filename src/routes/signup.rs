@@ -0,0 +1,122 @@
+use crate::config::{Config, PasswordConfig};
+use crate::errors::ApiError;
+use crate::models::users::{
+    ActiveModel as ActiveModelUser, Column as ColumnUser, Entity as EntityUser, Role,
+};
+use crate::services::mailer::Mailer;
+use crate::services::notifications::DEFAULT_LOCALE;
+use crate::services::verification::{resend_verification, send_verification, verify_email};
+use crate::utils::password::hash_password;
+use crate::utils::validation::password_strength;
+use actix_web::{get, post, web, HttpResponse};
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::{Validate, ValidateArgs};
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct Registration {
+    #[validate(email(message = "Must be a valid email address."))]
+    email: String,
+    #[validate(custom(function = "password_strength", arg = "&'v_a PasswordConfig"))]
+    password: String,
+    #[validate(length(max = 255, message = "Must be at most 255 characters."))]
+    name: Option<String>,
+    #[validate(length(max = 32, message = "Must be at most 32 characters."))]
+    phone: Option<String>,
+    #[validate(length(max = 255, message = "Must be at most 255 characters."))]
+    address: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct Verification {
+    token: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResendVerification {
+    email: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RegistrationStatus {
+    status: bool,
+    message: String,
+}
+
+#[post("/register")]
+pub async fn register(
+    registration: web::Json<Registration>,
+    db: web::Data<DatabaseConnection>,
+    mailer: web::Data<dyn Mailer>,
+    config: web::Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+    registration.validate_args(&config.password)?;
+    let connection = db.get_ref();
+    let existing = EntityUser::find()
+        .filter(ColumnUser::Email.eq(registration.email.as_str()))
+        .one(connection)
+        .await?;
+    if existing.is_some() {
+        return Err(ApiError::Conflict("Email already in use.".to_string()));
+    }
+
+    let registration = registration.into_inner();
+    let password = hash_password(&config.password, registration.password.as_str())?;
+    // Accounts stay inactive until the email address is confirmed
+    let model = ActiveModelUser {
+        id: Set(Uuid::new_v4()),
+        email: Set(registration.email),
+        password: Set(password),
+        active: Set(false),
+        name: Set(registration.name),
+        phone: Set(registration.phone),
+        address: Set(registration.address),
+        role: Set(Role::Member),
+        password_changed_at: Set(None),
+        suspended_at: Set(None),
+        suspension_reason: Set(None),
+        totp_secret: Set(None),
+        totp_enabled: Set(false),
+        totp_last_step: Set(None),
+        locale: Set(DEFAULT_LOCALE.to_string()),
+        notify_email: Set(true),
+        notify_webhook: Set(false),
+        notify_due_dates: Set(true),
+        notify_holds: Set(true),
+        notify_fines: Set(true),
+        created_at: Set(Some(Utc::now().naive_utc())),
+        updated_at: Set(None),
+    };
+    let user = model.insert(connection).await?;
+    send_verification(connection, &config, mailer.get_ref(), &user).await?;
+    Ok(HttpResponse::Created().json(RegistrationStatus {
+        status: true,
+        message: "Check your email to activate the account".to_string(),
+    }))
+}
+
+#[get("/register/verify")]
+pub async fn verify(
+    query: web::Query<Verification>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    verify_email(db.get_ref(), query.token.as_str()).await?;
+    Ok(HttpResponse::Ok().json(RegistrationStatus {
+        status: true,
+        message: "Account activated".to_string(),
+    }))
+}
+
+#[post("/register/resend")]
+pub async fn resend(
+    form: web::Form<ResendVerification>,
+    db: web::Data<DatabaseConnection>,
+    mailer: web::Data<dyn Mailer>,
+    config: web::Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+    resend_verification(db.get_ref(), &config, mailer.get_ref(), form.email.as_str()).await?;
+    // Same answer whether or not a link was sent
+    Ok(HttpResponse::Accepted().finish())
+}
@@ -1,17 +1,21 @@
+use crate::config::Config;
+use crate::dto::users::{CreateUserRequest, UpdateUserRequest, UserResponse};
+use crate::errors::ApiError;
+use crate::middleware::auth::AuthenticatedUser;
 use crate::models::users::{
-    ActiveModel as ActiveModelUser, Entity as EntityUser, Model as ModelUser,
-};
-use crate::utils::default::encrypt_password;
-use crate::utils::token::decode_token;
-use actix_web::HttpRequest;
-use actix_web::{
-    delete, error::ErrorUnauthorized, get, http::header::HeaderValue, post, put, web, Error,
-    HttpResponse, Responder,
+    ActiveModel as ActiveModelUser, Entity as EntityUser, Model as ModelUser, Role,
 };
+use crate::services::sessions::revoke_all_sessions;
+use crate::utils::password::{hash_password, verify_password};
+use crate::utils::validation::not_blank;
+use actix_web::{delete, get, post, put, web, HttpResponse};
+use chrono::Utc;
 use log::warn;
-use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, Set, TryIntoModel};
+use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, Set, TransactionTrait};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use uuid::Uuid;
+use validator::{Validate, ValidateArgs, ValidationError, ValidationErrors};
 
 #[derive(Serialize, Deserialize)]
 struct DeletedRecord {
@@ -19,163 +23,191 @@ struct DeletedRecord {
     message: String,
 }
 
-fn user_validation(token: Option<HeaderValue>, user_id: Uuid) -> Result<bool, Error> {
-    match token {
-        Some(token) => match decode_token(token) {
-            Ok(claims) => {
-                if claims.sub == user_id.to_string() {
-                    Ok(true)
-                } else {
-                    Err(ErrorUnauthorized("Invalid user."))
-                }
-            }
-            Err(err) => Err(err),
-        },
-        None => Err(ErrorUnauthorized("Missing authorization token.")),
+/// Users may only manage their own account, unless they are an admin.
+fn owner_validation(auth: &AuthenticatedUser, user_id: Uuid) -> Result<(), ApiError> {
+    if auth.can_act_for(user_id, Role::Admin) {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden(
+            "Only admins may manage other accounts.".to_string(),
+        ))
     }
 }
 
+pub async fn find_user(
+    connection: &DatabaseConnection,
+    user_id: Uuid,
+) -> Result<ModelUser, ApiError> {
+    EntityUser::find_by_id(user_id)
+        .one(connection)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("User not found.".to_string()))
+}
+
 #[get("/{id}")]
 pub async fn get_one(
     path: web::Path<Uuid>,
-    req: HttpRequest,
+    auth: AuthenticatedUser,
     db: web::Data<DatabaseConnection>,
-) -> impl Responder {
+) -> Result<HttpResponse, ApiError> {
     let user_id = path.into_inner();
-    let token = req.headers().get("Authorization").cloned();
-    match user_validation(token, user_id) {
-        Err(err) => {
-            warn!(
-                "Token user_id not match with changing user_id (User::get_one): {}",
-                err
-            );
-            return HttpResponse::Unauthorized().finish();
-        }
-        _ => {}
-    };
-
-    let connection = db.get_ref();
-    match EntityUser::find_by_id(user_id)
-        .into_json()
-        .one(connection)
-        .await
-    {
-        Ok(Some(data)) => HttpResponse::Ok().json(data),
-        Ok(None) => {
-            warn!("Unable to load data (User::get_one): User not found");
-            HttpResponse::NotFound().finish()
-        }
-        Err(err) => {
-            warn!("Unable to load data (User::get_one): {}", err);
-            HttpResponse::NotFound().finish()
-        }
-    }
+    owner_validation(&auth, user_id)?;
+    let user = find_user(db.get_ref(), user_id).await?;
+    Ok(HttpResponse::Ok().json(UserResponse::from(user)))
 }
 
 #[post("")]
 pub async fn create(
-    user: web::Json<ModelUser>,
+    user: web::Json<CreateUserRequest>,
     db: web::Data<DatabaseConnection>,
-) -> impl Responder {
-    warn!("Creating user: {:?}", user);
-    let connection = db.get_ref();
-    let mut model = user.0;
-    model.password = encrypt_password(model.password.to_owned());
-    match ActiveModelUser::from(model).insert(connection).await {
-        Ok(data) => HttpResponse::Ok().json(data.try_into_model().unwrap()),
-        Err(err) => {
-            warn!("Unable to insert data (user::create): {}", err);
-            HttpResponse::InternalServerError().finish()
-        }
-    }
+    config: web::Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+    user.validate_args(&config.password)?;
+    let password = hash_password(&config.password, user.password.as_str())?;
+    let data = user
+        .into_inner()
+        .into_active_model(password)
+        .insert(db.get_ref())
+        .await?;
+    Ok(HttpResponse::Ok().json(UserResponse::from(data)))
 }
 
 #[put("/{id}")]
 pub async fn update(
     path: web::Path<Uuid>,
-    req: HttpRequest,
-    user: web::Json<ModelUser>,
+    auth: AuthenticatedUser,
+    user: web::Json<UpdateUserRequest>,
     db: web::Data<DatabaseConnection>,
-) -> impl Responder {
+    config: web::Data<Config>,
+) -> Result<HttpResponse, ApiError> {
     let user_id = path.into_inner();
-    let token = req.headers().get("Authorization").cloned();
-    match user_validation(token, user_id) {
-        Err(err) => {
-            warn!(
-                "Token user_id not match with changing user_id (User::get_one): {}",
-                err
-            );
-            return HttpResponse::Unauthorized().finish();
+    owner_validation(&auth, user_id)?;
+    let data = update_user(db.get_ref(), &config, &auth, user_id, user.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(UserResponse::from(data)))
+}
+
+/// Apply a profile update on behalf of `auth`, who was already allowed to make it.
+/// Refuse a password change that doesn't prove the current password.
+fn check_current_password(
+    config: &Config,
+    user: &UpdateUserRequest,
+    stored: &str,
+) -> Result<(), ApiError> {
+    let message = match &user.current_password {
+        None => "Required to change the password.",
+        Some(current) if !verify_password(&config.password, current, stored).is_valid() => {
+            "Doesn't match the current password."
         }
-        _ => {}
+        Some(_) => return Ok(()),
     };
+    let mut err = ValidationError::new("invalid");
+    err.message = Some(Cow::from(message));
+    let mut errors = ValidationErrors::new();
+    errors.add("current_password", err);
+    Err(errors.into())
+}
 
-    let connection = db.get_ref();
-    match EntityUser::find_by_id(user_id).one(connection).await {
-        Ok(Some(data)) => {
-            let mut model: ActiveModelUser = data.into();
-            model.merge(user.0);
-            model.password = Set(encrypt_password(model.password.unwrap()));
-            match model.update(connection).await {
-                Ok(data) => HttpResponse::Ok().json(data),
-                Err(err) => {
-                    warn!("Unable to update data (User::update): {}", err);
-                    HttpResponse::InternalServerError().finish()
-                }
-            }
-        }
-        Ok(None) => {
-            warn!("Unable to load data (User::update): User not found");
-            HttpResponse::NotFound().finish()
-        }
-        Err(err) => {
-            warn!("Unable to load data (User::update): {}", err);
-            HttpResponse::NotFound().finish()
-        }
+pub async fn update_user(
+    connection: &DatabaseConnection,
+    config: &Config,
+    auth: &AuthenticatedUser,
+    user_id: Uuid,
+    user: UpdateUserRequest,
+) -> Result<ModelUser, ApiError> {
+    user.validate_args(&config.password)?;
+
+    let stored = find_user(connection, user_id).await?;
+    let password_changed = user.password.is_some();
+    // A stolen session alone mustn't be enough to take over the account
+    if password_changed && !auth.has_role(Role::Admin) {
+        check_current_password(config, &user, stored.password.as_str())?;
     }
+    let mut model: ActiveModelUser = stored.into();
+    if let Some(password) = &user.password {
+        model.password = Set(hash_password(&config.password, password.as_str())?);
+        // Like a reset, a new password ends every session opened with the old one
+        model.password_changed_at = Set(Some(Utc::now().naive_utc()));
+    }
+    // Only admins may promote or demote accounts
+    if let Some(role) = user.role.filter(|_| auth.has_role(Role::Admin)) {
+        model.role = Set(role);
+    }
+    user.apply_to(&mut model);
+    let txn = connection.begin().await?;
+    let data = model.update(&txn).await?;
+    if password_changed {
+        revoke_all_sessions(&txn, user_id).await?;
+    }
+    txn.commit().await?;
+    Ok(data)
 }
 
 #[delete("/{id}")]
 pub async fn delete(
     path: web::Path<Uuid>,
-    req: HttpRequest,
+    auth: AuthenticatedUser,
     db: web::Data<DatabaseConnection>,
-) -> impl Responder {
+) -> Result<HttpResponse, ApiError> {
     let user_id = path.into_inner();
-    let token = req.headers().get("Authorization").cloned();
-    match user_validation(token, user_id) {
-        Err(err) => {
-            warn!(
-                "Token user_id not match with changing user_id (User::get_one): {}",
-                err
-            );
-            return HttpResponse::Unauthorized().finish();
-        }
-        _ => {}
-    };
+    owner_validation(&auth, user_id)?;
 
     let connection = db.get_ref();
-    match EntityUser::find_by_id(user_id).one(connection).await {
-        Ok(Some(data)) => {
-            let model: ActiveModelUser = data.into();
-            match model.delete(connection).await {
-                Ok(_) => HttpResponse::Ok().json(DeletedRecord {
-                    status: true,
-                    message: "Record deleted successfully".to_string(),
-                }),
-                Err(err) => {
-                    warn!("Unable to delete data (User::delete): {}", err);
-                    HttpResponse::InternalServerError().finish()
-                }
-            }
-        }
-        Ok(None) => {
-            warn!("Unable to load data (User::delete): User not found");
-            HttpResponse::NotFound().finish()
-        }
-        Err(err) => {
-            warn!("Unable to load data (User::delete): {}", err);
-            HttpResponse::NotFound().finish()
-        }
+    let model: ActiveModelUser = find_user(connection, user_id).await?.into();
+    model.delete(connection).await?;
+    Ok(HttpResponse::Ok().json(DeletedRecord {
+        status: true,
+        message: "Record deleted successfully".to_string(),
+    }))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct Suspension {
+    #[validate(
+        custom = "not_blank",
+        length(max = 500, message = "Must be at most 500 characters.")
+    )]
+    reason: String,
+}
+
+#[post("/{id}/suspend")]
+pub async fn suspend(
+    path: web::Path<Uuid>,
+    auth: AuthenticatedUser,
+    suspension: web::Json<Suspension>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    suspension.validate()?;
+    let user_id = path.into_inner();
+    if auth.sub == user_id.to_string() {
+        return Err(ApiError::BadRequest(
+            "You can't suspend your own account.".to_string(),
+        ));
+    }
+
+    let connection = db.get_ref();
+    let mut model: ActiveModelUser = find_user(connection, user_id).await?.into();
+    model.active = Set(false);
+    model.suspended_at = Set(Some(Utc::now().naive_utc()));
+    model.suspension_reason = Set(Some(suspension.0.reason));
+    model.updated_at = Set(Some(Utc::now().naive_utc()));
+    let data = model.update(connection).await?;
+    // Access tokens are rejected by the validator, refresh tokens go too
+    if let Err(err) = revoke_all_sessions(connection, user_id).await {
+        warn!("Unable to revoke sessions (User::suspend): {}", err);
     }
+    Ok(HttpResponse::Ok().json(UserResponse::from(data)))
+}
+
+#[post("/{id}/reactivate")]
+pub async fn reactivate(
+    path: web::Path<Uuid>,
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, ApiError> {
+    let connection = db.get_ref();
+    let mut model: ActiveModelUser = find_user(connection, path.into_inner()).await?.into();
+    model.active = Set(true);
+    model.suspended_at = Set(None);
+    model.suspension_reason = Set(None);
+    model.updated_at = Set(Some(Utc::now().naive_utc()));
+    Ok(HttpResponse::Ok().json(UserResponse::from(model.update(connection).await?)))
 }
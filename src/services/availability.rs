@@ -0,0 +1,78 @@
+use crate::models::copies::{Column as ColumnCopy, CopyStatus, Entity as EntityCopy};
+use sea_orm::sea_query::{Expr, Query, SimpleExpr};
+use sea_orm::{
+    ActiveEnum, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, FromQueryResult, QueryFilter,
+    QuerySelect,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// How many copies of a book exist and how many of them are on the shelf.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct Availability {
+    pub total_copies: i64,
+    pub available_copies: i64,
+}
+
+impl Availability {
+    pub fn is_available(&self) -> bool {
+        self.available_copies > 0
+    }
+}
+
+#[derive(FromQueryResult)]
+struct AvailabilityRow {
+    book_id: Uuid,
+    total_copies: i64,
+    available_copies: i64,
+}
+
+/// Count copies per book in one query; books without copies are left out.
+pub async fn availability<C: ConnectionTrait>(
+    connection: &C,
+    book_ids: &[Uuid],
+) -> Result<HashMap<Uuid, Availability>, DbErr> {
+    if book_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let rows = EntityCopy::find()
+        .select_only()
+        .column(ColumnCopy::BookId)
+        .column_as(Expr::col(ColumnCopy::Id).count(), "total_copies")
+        .column_as(
+            Expr::cust_with_values(
+                "COUNT(*) FILTER (WHERE status = $1)",
+                [CopyStatus::Available.to_value()],
+            ),
+            "available_copies",
+        )
+        .filter(ColumnCopy::BookId.is_in(book_ids.iter().copied()))
+        .group_by(ColumnCopy::BookId)
+        .into_model::<AvailabilityRow>()
+        .all(connection)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            (
+                row.book_id,
+                Availability {
+                    total_copies: row.total_copies,
+                    available_copies: row.available_copies,
+                },
+            )
+        })
+        .collect())
+}
+
+/// Condition on `books.id` selecting books with at least one copy on the shelf.
+pub fn has_available_copy(book_id: impl ColumnTrait) -> SimpleExpr {
+    book_id.in_subquery(
+        Query::select()
+            .column(ColumnCopy::BookId)
+            .from(EntityCopy)
+            .and_where(ColumnCopy::Status.eq(CopyStatus::Available))
+            .to_owned(),
+    )
+}
@@ -0,0 +1,670 @@
+use crate::config::LoanConfig;
+use crate::dto::reservations::ReservationRequest;
+use crate::models::books::Entity as EntityBook;
+use crate::models::copies::{
+    ActiveModel as ActiveModelCopy, Column as ColumnCopy, CopyStatus, Entity as EntityCopy,
+    Model as ModelCopy,
+};
+use crate::models::notifications::NotificationKind;
+use crate::models::reservation_events::ActiveModel as ActiveModelReservationEvent;
+use crate::models::reservations::{
+    ActiveModel as ActiveModelReservation, Column as ColumnReservation,
+    Entity as EntityReservation, Model as ModelReservation, ReservationStatus,
+};
+use crate::models::users::Entity as EntityUser;
+use crate::services::fines::{accrue_fines, assess, balance, charged_cents};
+use crate::services::holds::{hand_over, pickup_deadline, queue_length};
+use crate::services::loan_policy::LoanPolicy;
+use crate::services::notifications::notify_reservation;
+use chrono::{Duration, Utc};
+use log::warn;
+use sea_orm::sea_query::{LockBehavior, LockType};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DatabaseTransaction, DbErr,
+    EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, QueryTrait, Set,
+    TransactionTrait,
+};
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub enum CirculationError {
+    ReservationNotFound,
+    UserNotFound,
+    CopyNotFound,
+    BookNotFound,
+    /// The requested copy is lent, in repair or otherwise off the shelf.
+    CopyUnavailable,
+    /// Every copy of the requested book is taken.
+    NoCopyAvailable,
+    /// The lifecycle doesn't allow the requested status change.
+    InvalidTransition(ReservationStatus, ReservationStatus),
+    /// The borrower already holds as many copies as the policy allows.
+    LoanLimitReached(u32),
+    /// Only checked out loans can be renewed.
+    NotRenewable(ReservationStatus),
+    RenewalLimitReached(u32),
+    /// Other borrowers are waiting for the book.
+    HoldsQueued,
+    /// The borrower owes more than checkouts allow: the balance and the limit, in cents.
+    BalanceTooHigh(i64, i64),
+    Db(DbErr),
+}
+
+impl From<DbErr> for CirculationError {
+    fn from(err: DbErr) -> Self {
+        CirculationError::Db(err)
+    }
+}
+
+/// Lock the requested copy, or the first free copy of the requested book.
+///
+/// Book-level requests skip copies another transaction is already lending, so
+/// concurrent checkouts of the same book spread over its copies instead of
+/// queueing on the first one.
+async fn lock_copy(
+    txn: &DatabaseTransaction,
+    request: &ReservationRequest,
+) -> Result<ModelCopy, CirculationError> {
+    if let Some(copy_id) = request.copy_id {
+        let copy = EntityCopy::find_by_id(copy_id)
+            .lock_exclusive()
+            .one(txn)
+            .await?
+            .ok_or(CirculationError::CopyNotFound)?;
+        return match copy.status {
+            CopyStatus::Available => Ok(copy),
+            _ => Err(CirculationError::CopyUnavailable),
+        };
+    }
+
+    let book_id = request.book_id.ok_or(CirculationError::BookNotFound)?;
+    let mut select = EntityCopy::find()
+        .filter(ColumnCopy::BookId.eq(book_id))
+        .filter(ColumnCopy::Status.eq(CopyStatus::Available))
+        .order_by_asc(ColumnCopy::Barcode)
+        .limit(1);
+    QueryTrait::query(&mut select).lock_with_behavior(LockType::Update, LockBehavior::SkipLocked);
+    match select.one(txn).await? {
+        Some(copy) => Ok(copy),
+        None if EntityBook::find_by_id(book_id).one(txn).await?.is_some() => {
+            Err(CirculationError::NoCopyAvailable)
+        }
+        None => Err(CirculationError::BookNotFound),
+    }
+}
+
+/// Loan rules for a borrower and a book.
+pub async fn loan_policy<C: ConnectionTrait>(
+    connection: &C,
+    config: &LoanConfig,
+    user_id: Uuid,
+    book_id: Uuid,
+) -> Result<LoanPolicy, CirculationError> {
+    let user = EntityUser::find_by_id(user_id)
+        .one(connection)
+        .await?
+        .ok_or(CirculationError::UserNotFound)?;
+    let book = EntityBook::find_by_id(book_id)
+        .one(connection)
+        .await?
+        .ok_or(CirculationError::BookNotFound)?;
+    Ok(LoanPolicy::resolve(
+        config,
+        user.role,
+        book.category.as_deref(),
+    ))
+}
+
+/// Statuses counted against a borrower's concurrent loan limit.
+const ACTIVE_LOAN_STATUSES: [ReservationStatus; 3] = [
+    ReservationStatus::ReadyForPickup,
+    ReservationStatus::CheckedOut,
+    ReservationStatus::Overdue,
+];
+
+/// Whether a reservation is holding or lending `copy_id`.
+pub async fn copy_is_reserved<C: ConnectionTrait>(
+    connection: &C,
+    copy_id: Uuid,
+) -> Result<bool, DbErr> {
+    let holders = EntityReservation::find()
+        .filter(ColumnReservation::CopyId.eq(copy_id))
+        .filter(ColumnReservation::Status.is_in(ACTIVE_LOAN_STATUSES))
+        .count(connection)
+        .await?;
+    Ok(holders > 0)
+}
+
+pub async fn set_copy_status<C: ConnectionTrait>(
+    connection: &C,
+    copy: ModelCopy,
+    status: CopyStatus,
+) -> Result<(), DbErr> {
+    let mut model: ActiveModelCopy = copy.into();
+    model.status = Set(status);
+    model.updated_at = Set(Some(Utc::now().naive_utc()));
+    model.update(connection).await?;
+    Ok(())
+}
+
+pub async fn record_event<C: ConnectionTrait>(
+    connection: &C,
+    reservation_id: Uuid,
+    from_status: Option<ReservationStatus>,
+    to_status: ReservationStatus,
+    actor_id: Option<Uuid>,
+) -> Result<(), DbErr> {
+    ActiveModelReservationEvent {
+        id: Set(Uuid::new_v4()),
+        reservation_id: Set(reservation_id),
+        from_status: Set(from_status),
+        to_status: Set(to_status),
+        actor_id: Set(actor_id),
+        created_at: Set(Utc::now().naive_utc()),
+    }
+    .insert(connection)
+    .await?;
+    Ok(())
+}
+
+/// Set a copy aside for a borrower, or queue them for the book when none is
+/// on the shelf or others are already waiting for it.
+///
+/// The reservation is recorded and the copy taken off the shelf in one
+/// transaction, so two borrowers can never hold the same copy. The
+/// borrower's row stays locked until commit, so concurrent requests of the
+/// same borrower can't overshoot the concurrent loan limit together.
+pub async fn reserve(
+    connection: &DatabaseConnection,
+    config: &LoanConfig,
+    request: ReservationRequest,
+    actor_id: Option<Uuid>,
+) -> Result<ModelReservation, CirculationError> {
+    let txn = connection.begin().await?;
+    EntityUser::find_by_id(request.user_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+        .ok_or(CirculationError::UserNotFound)?;
+    let copy = loop {
+        let copy = match lock_copy(&txn, &request).await {
+            Ok(copy) => copy,
+            Err(CirculationError::NoCopyAvailable) => break None,
+            Err(err) => return Err(err),
+        };
+        match request.book_id {
+            // Patrons already in line get free copies before newcomers, so
+            // copies left on the shelf while they waited are handed over now
+            Some(book_id) if queue_length(&txn, book_id).await? > 0 => {
+                if hand_over(&txn, config, copy).await?.is_none() {
+                    // The holds left are being served by other transactions
+                    break None;
+                }
+            }
+            _ => break Some(copy),
+        }
+    };
+    let book_id = match &copy {
+        Some(copy) => copy.book_id,
+        None => request.book_id.ok_or(CirculationError::BookNotFound)?,
+    };
+
+    let policy = loan_policy(&txn, config, request.user_id, book_id).await?;
+    let active_loans = EntityReservation::find()
+        .filter(ColumnReservation::UserId.eq(request.user_id))
+        .filter(ColumnReservation::Status.is_in(ACTIVE_LOAN_STATUSES))
+        .count(&txn)
+        .await?;
+    if active_loans >= u64::from(policy.max_concurrent_loans) {
+        return Err(CirculationError::LoanLimitReached(
+            policy.max_concurrent_loans,
+        ));
+    }
+
+    let reservation = match copy {
+        Some(copy) => {
+            let mut model = request.into_active_model(
+                Some(copy.id),
+                book_id,
+                ReservationStatus::ReadyForPickup,
+            );
+            model.pickup_expires_at = Set(Some(pickup_deadline(config, Utc::now().naive_utc())));
+            let reservation = model.insert(&txn).await?;
+            set_copy_status(&txn, copy, CopyStatus::OnHold).await?;
+            reservation
+        }
+        None => {
+            request
+                .into_active_model(None, book_id, ReservationStatus::Requested)
+                .insert(&txn)
+                .await?
+        }
+    };
+    record_event(&txn, reservation.id, None, reservation.status, actor_id).await?;
+    if reservation.status == ReservationStatus::ReadyForPickup {
+        notify_reservation(&txn, NotificationKind::HoldReady, &reservation, None).await?;
+    }
+    txn.commit().await?;
+    Ok(reservation)
+}
+
+/// Status the copy of a reservation takes once it moves to `status`, if it changes.
+fn copy_status_for(status: ReservationStatus) -> Option<CopyStatus> {
+    match status {
+        ReservationStatus::ReadyForPickup => Some(CopyStatus::OnHold),
+        ReservationStatus::CheckedOut => Some(CopyStatus::OnLoan),
+        ReservationStatus::Returned | ReservationStatus::Cancelled => Some(CopyStatus::Available),
+        ReservationStatus::Lost => Some(CopyStatus::Lost),
+        ReservationStatus::Requested | ReservationStatus::Overdue => None,
+    }
+}
+
+/// Move a reservation to `next`, updating its copy and recording who did it.
+///
+/// Checking out starts the loan period of the borrower's policy, unless they
+/// owe more than allowed; a copy coming back goes to the next hold in line
+/// for its book. Returns and losses settle the loan's overdue fine.
+pub async fn transition(
+    connection: &DatabaseConnection,
+    config: &LoanConfig,
+    reservation_id: Uuid,
+    next: ReservationStatus,
+    actor_id: Option<Uuid>,
+) -> Result<ModelReservation, CirculationError> {
+    let txn = connection.begin().await?;
+    let reservation = EntityReservation::find_by_id(reservation_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+        .ok_or(CirculationError::ReservationNotFound)?;
+    let current = reservation.status;
+    if !current.can_become(next) {
+        return Err(CirculationError::InvalidTransition(current, next));
+    }
+    if next == ReservationStatus::CheckedOut {
+        accrue_fines(&txn, config, Some(reservation.user_id)).await?;
+        let owed = balance(&txn, reservation.user_id).await?.balance_cents;
+        if owed > config.max_balance_cents {
+            return Err(CirculationError::BalanceTooHigh(
+                owed,
+                config.max_balance_cents,
+            ));
+        }
+    }
+
+    let copy = match (reservation.copy_id, copy_status_for(next)) {
+        (Some(copy_id), Some(status)) => {
+            let copy = EntityCopy::find_by_id(copy_id)
+                .lock_exclusive()
+                .one(&txn)
+                .await?
+                .ok_or(CirculationError::CopyNotFound)?;
+            Some((copy, status))
+        }
+        _ => None,
+    };
+
+    let now = Utc::now().naive_utc();
+    let due_date = match next {
+        ReservationStatus::CheckedOut => {
+            let policy =
+                loan_policy(&txn, config, reservation.user_id, reservation.book_id).await?;
+            Some(policy.due_date(now))
+        }
+        _ => None,
+    };
+    let mut model: ActiveModelReservation = reservation.into();
+    model.status = Set(next);
+    if let Some(due_date) = due_date {
+        model.due_date = Set(Some(due_date));
+        model.renewals = Set(0);
+        model.pickup_expires_at = Set(None);
+    }
+    if next == ReservationStatus::Returned {
+        model.return_date = Set(Some(now));
+    }
+    model.updated_at = Set(Some(now));
+    let reservation = model.update(&txn).await?;
+    // Only once this reservation lets go of the copy can the next one take it
+    match copy {
+        Some((copy, CopyStatus::Available)) => {
+            hand_over(&txn, config, copy).await?;
+        }
+        Some((copy, status)) => set_copy_status(&txn, copy, status).await?,
+        None => {}
+    }
+    // Lost loans stop accruing, so a copy turning up later adds nothing
+    if next == ReservationStatus::Lost
+        || (next == ReservationStatus::Returned && current != ReservationStatus::Lost)
+    {
+        assess(&txn, config, &reservation).await?;
+        let fine = charged_cents(&txn, reservation.id).await?;
+        if fine > 0 {
+            notify_reservation(
+                &txn,
+                NotificationKind::FineAssessed,
+                &reservation,
+                Some(fine),
+            )
+            .await?;
+        }
+    }
+    if next == ReservationStatus::Overdue {
+        notify_reservation(&txn, NotificationKind::Overdue, &reservation, None).await?;
+    }
+    record_event(&txn, reservation.id, Some(current), next, actor_id).await?;
+    txn.commit().await?;
+    Ok(reservation)
+}
+
+/// Mark loans still out past their due date and grace period overdue, then
+/// bring every overdue fine up to date. Returns how many loans were marked.
+pub async fn mark_overdue(
+    connection: &DatabaseConnection,
+    config: &LoanConfig,
+) -> Result<u64, CirculationError> {
+    let now = Utc::now().naive_utc();
+    let loans = EntityReservation::find()
+        .filter(ColumnReservation::Status.eq(ReservationStatus::CheckedOut))
+        .filter(ColumnReservation::DueDate.lt(now))
+        .all(connection)
+        .await?;
+    let mut marked = 0;
+    for loan in loans {
+        let policy = loan_policy(connection, config, loan.user_id, loan.book_id).await?;
+        let late = matches!(
+            loan.due_date,
+            Some(due) if due + Duration::days(policy.grace_period_days) < now
+        );
+        if !late {
+            continue;
+        }
+        match transition(
+            connection,
+            config,
+            loan.id,
+            ReservationStatus::Overdue,
+            None,
+        )
+        .await
+        {
+            Ok(_) => marked += 1,
+            // Returned or lost meanwhile
+            Err(CirculationError::InvalidTransition(_, _)) => {}
+            Err(err) => warn!("Unable to mark loan {} overdue: {:?}", loan.id, err),
+        }
+    }
+
+    let txn = connection.begin().await?;
+    accrue_fines(&txn, config, None).await?;
+    txn.commit().await?;
+    Ok(marked)
+}
+
+/// Extend a loan by another loan period, unless the policy's renewals are
+/// used up or other borrowers are waiting for the book.
+pub async fn renew(
+    connection: &DatabaseConnection,
+    config: &LoanConfig,
+    reservation_id: Uuid,
+) -> Result<ModelReservation, CirculationError> {
+    let txn = connection.begin().await?;
+    let reservation = EntityReservation::find_by_id(reservation_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+        .ok_or(CirculationError::ReservationNotFound)?;
+    if reservation.status != ReservationStatus::CheckedOut {
+        return Err(CirculationError::NotRenewable(reservation.status));
+    }
+    let policy = loan_policy(&txn, config, reservation.user_id, reservation.book_id).await?;
+    if reservation.renewals >= policy.max_renewals as i32 {
+        return Err(CirculationError::RenewalLimitReached(policy.max_renewals));
+    }
+    if queue_length(&txn, reservation.book_id).await? > 0 {
+        return Err(CirculationError::HoldsQueued);
+    }
+
+    let now = Utc::now().naive_utc();
+    // Renewing early extends from the current due date, not from today
+    let from = reservation.due_date.map_or(now, |due| due.max(now));
+    let renewals = reservation.renewals + 1;
+    let mut model: ActiveModelReservation = reservation.into();
+    model.due_date = Set(Some(policy.due_date(from)));
+    model.renewals = Set(renewals);
+    // The new due date deserves its own reminder
+    model.reminded_at = Set(None);
+    model.updated_at = Set(Some(now));
+    let reservation = model.update(&txn).await?;
+    txn.commit().await?;
+    Ok(reservation)
+}
+
+/// Delete a reservation, passing its copy on if it still held it.
+pub async fn release(
+    connection: &DatabaseConnection,
+    config: &LoanConfig,
+    reservation: ModelReservation,
+) -> Result<(), DbErr> {
+    let txn = connection.begin().await?;
+    let copy = match reservation.copy_id {
+        Some(copy_id) => {
+            EntityCopy::find_by_id(copy_id)
+                .lock_exclusive()
+                .one(&txn)
+                .await?
+        }
+        None => None,
+    };
+    let holds_copy = reservation.status.holds_copy();
+    let model: ActiveModelReservation = reservation.into();
+    model.delete(&txn).await?;
+    if let Some(copy) = copy.filter(|_| holds_copy) {
+        hand_over(&txn, config, copy).await?;
+    }
+    txn.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ApiError;
+    use crate::services::holds::queue_position;
+    use crate::services::testing::Fixture;
+    use actix_web::http::StatusCode;
+    use actix_web::ResponseError;
+
+    fn request(user_id: Uuid, copy_id: Option<Uuid>, book_id: Option<Uuid>) -> ReservationRequest {
+        ReservationRequest {
+            user_id,
+            copy_id,
+            book_id,
+        }
+    }
+
+    async fn copy_status(fixture: &Fixture, copy_id: Uuid) -> CopyStatus {
+        EntityCopy::find_by_id(copy_id)
+            .one(&fixture.db)
+            .await
+            .unwrap()
+            .unwrap()
+            .status
+    }
+
+    /// Reservations still holding `copy_id`.
+    async fn holders(fixture: &Fixture, copy_id: Uuid) -> u64 {
+        EntityReservation::find()
+            .filter(ColumnReservation::CopyId.eq(copy_id))
+            .filter(ColumnReservation::Status.is_in(ACTIVE_LOAN_STATUSES))
+            .count(&fixture.db)
+            .await
+            .unwrap()
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn concurrent_book_reservations_share_the_last_copy_once() {
+        let config = LoanConfig::default();
+        for _ in 0..10 {
+            let mut fixture = Fixture::new(1).await;
+            let (first, second) = (fixture.add_user().await, fixture.add_user().await);
+            let book_id = Some(fixture.book_id);
+            let (a, b) = futures::join!(
+                reserve(&fixture.db, &config, request(first, None, book_id), None),
+                reserve(&fixture.db, &config, request(second, None, book_id), None),
+            );
+            let mut statuses = vec![a.unwrap().status, b.unwrap().status];
+            statuses.sort_by_key(|status| format!("{:?}", status));
+            assert_eq!(
+                statuses,
+                [
+                    ReservationStatus::ReadyForPickup,
+                    ReservationStatus::Requested
+                ]
+            );
+            let copy_id = fixture.copies[0];
+            assert_eq!(holders(&fixture, copy_id).await, 1);
+            assert_eq!(copy_status(&fixture, copy_id).await, CopyStatus::OnHold);
+        }
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn concurrent_copy_reservations_conflict() {
+        let config = LoanConfig::default();
+        for _ in 0..10 {
+            let mut fixture = Fixture::new(1).await;
+            let (first, second) = (fixture.add_user().await, fixture.add_user().await);
+            let copy_id = Some(fixture.copies[0]);
+            let (a, b) = futures::join!(
+                reserve(&fixture.db, &config, request(first, copy_id, None), None),
+                reserve(&fixture.db, &config, request(second, copy_id, None), None),
+            );
+            let (reserved, refused) = match (a, b) {
+                (Ok(reserved), Err(refused)) | (Err(refused), Ok(reserved)) => (reserved, refused),
+                (a, b) => panic!("expected exactly one reservation, got {:?} and {:?}", a, b),
+            };
+            assert_eq!(reserved.status, ReservationStatus::ReadyForPickup);
+            assert!(matches!(refused, CirculationError::CopyUnavailable));
+            assert_eq!(ApiError::from(refused).status_code(), StatusCode::CONFLICT);
+            assert_eq!(holders(&fixture, fixture.copies[0]).await, 1);
+        }
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn checked_out_copy_cant_be_reserved_again() {
+        let config = LoanConfig::default();
+        let mut fixture = Fixture::new(1).await;
+        let (first, second) = (fixture.add_user().await, fixture.add_user().await);
+        let copy_id = fixture.copies[0];
+        let hold = reserve(
+            &fixture.db,
+            &config,
+            request(first, Some(copy_id), None),
+            None,
+        )
+        .await
+        .unwrap();
+        let loan = transition(
+            &fixture.db,
+            &config,
+            hold.id,
+            ReservationStatus::CheckedOut,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(loan.status, ReservationStatus::CheckedOut);
+        let refused = reserve(
+            &fixture.db,
+            &config,
+            request(second, Some(copy_id), None),
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(ApiError::from(refused).status_code(), StatusCode::CONFLICT);
+        assert_eq!(holders(&fixture, copy_id).await, 1);
+        assert_eq!(copy_status(&fixture, copy_id).await, CopyStatus::OnLoan);
+    }
+
+    async fn reload(fixture: &Fixture, reservation: &ModelReservation) -> ModelReservation {
+        EntityReservation::find_by_id(reservation.id)
+            .one(&fixture.db)
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn returned_copy_goes_to_the_head_of_the_queue() {
+        let config = LoanConfig::default();
+        let mut fixture = Fixture::new(1).await;
+        let book_id = Some(fixture.book_id);
+        let mut users = Vec::new();
+        for _ in 0..3 {
+            users.push(fixture.add_user().await);
+        }
+        let mut reservations = Vec::new();
+        for user_id in &users {
+            let reservation = reserve(&fixture.db, &config, request(*user_id, None, book_id), None)
+                .await
+                .unwrap();
+            reservations.push(reservation);
+        }
+        let statuses: Vec<_> = reservations.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                ReservationStatus::ReadyForPickup,
+                ReservationStatus::Requested,
+                ReservationStatus::Requested
+            ]
+        );
+        let loan = &reservations[0];
+        for next in [ReservationStatus::CheckedOut, ReservationStatus::Returned] {
+            transition(&fixture.db, &config, loan.id, next, None)
+                .await
+                .unwrap();
+        }
+
+        let head = reload(&fixture, &reservations[1]).await;
+        assert_eq!(head.status, ReservationStatus::ReadyForPickup);
+        assert_eq!(head.copy_id, loan.copy_id);
+        assert!(head.pickup_expires_at.is_some());
+        let next = reload(&fixture, &reservations[2]).await;
+        assert_eq!(next.status, ReservationStatus::Requested);
+        assert_eq!(queue_position(&fixture.db, &next).await.unwrap(), Some(1));
+        assert_eq!(
+            copy_status(&fixture, fixture.copies[0]).await,
+            CopyStatus::OnHold
+        );
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn free_copy_goes_to_the_queue_before_a_newcomer() {
+        let config = LoanConfig::default();
+        let mut fixture = Fixture::new(0).await;
+        let book_id = Some(fixture.book_id);
+        let (waiting, newcomer) = (fixture.add_user().await, fixture.add_user().await);
+        let hold = reserve(&fixture.db, &config, request(waiting, None, book_id), None)
+            .await
+            .unwrap();
+        assert_eq!(hold.status, ReservationStatus::Requested);
+        // Shelved without passing through the queue, as when a hold was locked
+        let copy_id = fixture.add_copy().await;
+
+        let late = reserve(&fixture.db, &config, request(newcomer, None, book_id), None)
+            .await
+            .unwrap();
+        assert_eq!(late.status, ReservationStatus::Requested);
+        let hold = reload(&fixture, &hold).await;
+        assert_eq!(hold.status, ReservationStatus::ReadyForPickup);
+        assert_eq!(hold.copy_id, Some(copy_id));
+        assert_eq!(copy_status(&fixture, copy_id).await, CopyStatus::OnHold);
+    }
+}
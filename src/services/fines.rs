@@ -0,0 +1,167 @@
+use crate::config::LoanConfig;
+use crate::models::ledger_entries::{
+    ActiveModel as ActiveModelLedgerEntry, Column as ColumnLedgerEntry,
+    Entity as EntityLedgerEntry, LedgerKind, Model as ModelLedgerEntry,
+};
+use crate::models::reservations::{
+    Column as ColumnReservation, Entity as EntityReservation, Model as ModelReservation,
+    ReservationStatus,
+};
+use crate::services::circulation::{loan_policy, CirculationError};
+use chrono::Utc;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, FromQueryResult,
+    QueryFilter, QuerySelect, Set,
+};
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Totals of a patron's account, in cents.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct Balance {
+    pub charges_cents: i64,
+    pub payments_cents: i64,
+    pub waivers_cents: i64,
+    /// Still owed: charges minus payments and waivers.
+    pub balance_cents: i64,
+}
+
+#[derive(FromQueryResult)]
+struct KindTotal {
+    kind: LedgerKind,
+    total: i64,
+}
+
+/// Render cents as a decimal amount, e.g. `1250` as `12.50`.
+pub fn format_cents(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    format!("{}{}.{:02}", sign, (cents / 100).abs(), (cents % 100).abs())
+}
+
+pub async fn balance<C: ConnectionTrait>(connection: &C, user_id: Uuid) -> Result<Balance, DbErr> {
+    let totals = EntityLedgerEntry::find()
+        .select_only()
+        .column(ColumnLedgerEntry::Kind)
+        .column_as(Expr::cust("SUM(amount_cents)::bigint"), "total")
+        .filter(ColumnLedgerEntry::UserId.eq(user_id))
+        .group_by(ColumnLedgerEntry::Kind)
+        .into_model::<KindTotal>()
+        .all(connection)
+        .await?;
+    let mut balance = Balance::default();
+    for row in totals {
+        match row.kind {
+            LedgerKind::Charge => balance.charges_cents = row.total,
+            LedgerKind::Payment => balance.payments_cents = row.total,
+            LedgerKind::Waiver => balance.waivers_cents = row.total,
+        }
+    }
+    balance.balance_cents = balance.charges_cents - balance.payments_cents - balance.waivers_cents;
+    Ok(balance)
+}
+
+pub async fn record_entry<C: ConnectionTrait>(
+    connection: &C,
+    user_id: Uuid,
+    kind: LedgerKind,
+    amount_cents: i64,
+    reservation_id: Option<Uuid>,
+    note: Option<String>,
+    actor_id: Option<Uuid>,
+) -> Result<ModelLedgerEntry, DbErr> {
+    ActiveModelLedgerEntry {
+        id: Set(Uuid::new_v4()),
+        user_id: Set(user_id),
+        reservation_id: Set(reservation_id),
+        kind: Set(kind),
+        amount_cents: Set(amount_cents),
+        note: Set(note),
+        actor_id: Set(actor_id),
+        created_at: Set(Utc::now().naive_utc()),
+    }
+    .insert(connection)
+    .await
+}
+
+/// Total fine charged for a loan so far, in cents.
+pub async fn charged_cents<C: ConnectionTrait>(
+    connection: &C,
+    reservation_id: Uuid,
+) -> Result<i64, DbErr> {
+    let charged: Option<i64> = EntityLedgerEntry::find()
+        .select_only()
+        .column_as(Expr::cust("SUM(amount_cents)::bigint"), "total")
+        .filter(ColumnLedgerEntry::ReservationId.eq(reservation_id))
+        .filter(ColumnLedgerEntry::Kind.eq(LedgerKind::Charge))
+        .into_tuple()
+        .one(connection)
+        .await?
+        .flatten();
+    Ok(charged.unwrap_or(0))
+}
+
+/// Charge what a loan's fine grew by since it was last assessed, counting
+/// until its return or until now while it's still out.
+///
+/// Waivers don't lower what was charged, so a waived fine isn't charged
+/// again; the caller must hold the reservation's row lock.
+pub async fn assess<C: ConnectionTrait>(
+    connection: &C,
+    config: &LoanConfig,
+    reservation: &ModelReservation,
+) -> Result<i64, CirculationError> {
+    let due_date = match reservation.due_date {
+        Some(due_date) => due_date,
+        None => return Ok(0),
+    };
+    let until = reservation
+        .return_date
+        .unwrap_or_else(|| Utc::now().naive_utc());
+    let policy = loan_policy(connection, config, reservation.user_id, reservation.book_id).await?;
+    let owed = policy.fine_cents(due_date, until);
+    if owed == 0 {
+        return Ok(0);
+    }
+
+    let due = owed - charged_cents(connection, reservation.id).await?;
+    if due > 0 {
+        record_entry(
+            connection,
+            reservation.user_id,
+            LedgerKind::Charge,
+            due,
+            Some(reservation.id),
+            Some("Overdue fine".to_string()),
+            None,
+        )
+        .await?;
+    }
+    Ok(due.max(0))
+}
+
+/// Bring the fines of loans still out past their due date up to date, for one
+/// patron or everyone. Returns how much was charged, in cents.
+///
+/// The loans are locked until the caller's transaction ends, so concurrent
+/// runs can't charge the same days twice.
+pub async fn accrue_fines<C: ConnectionTrait>(
+    connection: &C,
+    config: &LoanConfig,
+    user_id: Option<Uuid>,
+) -> Result<i64, CirculationError> {
+    let mut select = EntityReservation::find()
+        .filter(
+            ColumnReservation::Status
+                .is_in([ReservationStatus::CheckedOut, ReservationStatus::Overdue]),
+        )
+        .filter(ColumnReservation::DueDate.lt(Utc::now().naive_utc()));
+    if let Some(user_id) = user_id {
+        select = select.filter(ColumnReservation::UserId.eq(user_id));
+    }
+    let mut charged = 0;
+    for loan in select.lock_exclusive().all(connection).await? {
+        charged += assess(connection, config, &loan).await?;
+    }
+    Ok(charged)
+}
@@ -0,0 +1,150 @@
+use crate::config::LoanConfig;
+use crate::models::copies::{CopyStatus, Entity as EntityCopy, Model as ModelCopy};
+use crate::models::notifications::NotificationKind;
+use crate::models::reservations::{
+    ActiveModel as ActiveModelReservation, Column as ColumnReservation,
+    Entity as EntityReservation, Model as ModelReservation, ReservationStatus,
+};
+use crate::services::circulation::{record_event, set_copy_status, transition, CirculationError};
+use crate::services::notifications::notify_reservation;
+use chrono::{Duration, NaiveDateTime, Utc};
+use log::warn;
+use sea_orm::sea_query::{LockBehavior, LockType};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, DbErr,
+    EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, QueryTrait, Select, Set,
+    TransactionTrait,
+};
+use uuid::Uuid;
+
+/// Holds of a book still waiting for a copy, first come first served.
+pub fn queue(book_id: Uuid) -> Select<EntityReservation> {
+    EntityReservation::find()
+        .filter(ColumnReservation::BookId.eq(book_id))
+        .filter(ColumnReservation::Status.eq(ReservationStatus::Requested))
+        .order_by_asc(ColumnReservation::CreatedAt)
+        .order_by_asc(ColumnReservation::Id)
+}
+
+pub async fn queue_length<C: ConnectionTrait>(connection: &C, book_id: Uuid) -> Result<u64, DbErr> {
+    queue(book_id).count(connection).await
+}
+
+/// 1-based place of a hold in its book's line, `None` once it left the line.
+pub async fn queue_position<C: ConnectionTrait>(
+    connection: &C,
+    hold: &ModelReservation,
+) -> Result<Option<u64>, DbErr> {
+    if hold.status != ReservationStatus::Requested {
+        return Ok(None);
+    }
+    let ahead = queue(hold.book_id)
+        .filter(
+            Condition::any()
+                .add(ColumnReservation::CreatedAt.lt(hold.created_at))
+                .add(
+                    Condition::all()
+                        .add(ColumnReservation::CreatedAt.eq(hold.created_at))
+                        .add(ColumnReservation::Id.lt(hold.id)),
+                ),
+        )
+        .count(connection)
+        .await?;
+    Ok(Some(ahead + 1))
+}
+
+/// Deadline to collect a copy set aside at `from`.
+pub fn pickup_deadline(config: &LoanConfig, from: NaiveDateTime) -> NaiveDateTime {
+    from + Duration::days(config.hold_pickup_days)
+}
+
+/// Put a copy back into circulation: set it aside for the first hold in its
+/// book's line, or back on the shelf when nobody is waiting.
+///
+/// The copy must already be locked by the caller's transaction.
+pub async fn hand_over<C: ConnectionTrait>(
+    connection: &C,
+    config: &LoanConfig,
+    copy: ModelCopy,
+) -> Result<Option<ModelReservation>, DbErr> {
+    let mut select = queue(copy.book_id).limit(1);
+    QueryTrait::query(&mut select).lock_with_behavior(LockType::Update, LockBehavior::SkipLocked);
+    let hold = match select.one(connection).await? {
+        Some(hold) => hold,
+        None => {
+            set_copy_status(connection, copy, CopyStatus::Available).await?;
+            return Ok(None);
+        }
+    };
+
+    let now = Utc::now().naive_utc();
+    let mut model: ActiveModelReservation = hold.into();
+    model.copy_id = Set(Some(copy.id));
+    model.status = Set(ReservationStatus::ReadyForPickup);
+    model.pickup_expires_at = Set(Some(pickup_deadline(config, now)));
+    model.updated_at = Set(Some(now));
+    let hold = model.update(connection).await?;
+    set_copy_status(connection, copy, CopyStatus::OnHold).await?;
+    record_event(
+        connection,
+        hold.id,
+        Some(ReservationStatus::Requested),
+        ReservationStatus::ReadyForPickup,
+        None,
+    )
+    .await?;
+    notify_reservation(connection, NotificationKind::HoldReady, &hold, None).await?;
+    Ok(Some(hold))
+}
+
+/// Offer a copy that just became available, e.g. added or back from repair,
+/// to the hold queue of its book.
+pub async fn shelve(
+    connection: &DatabaseConnection,
+    config: &LoanConfig,
+    copy_id: Uuid,
+) -> Result<(), DbErr> {
+    let txn = connection.begin().await?;
+    let copy = EntityCopy::find_by_id(copy_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await?;
+    if let Some(copy) = copy.filter(|copy| copy.status == CopyStatus::Available) {
+        hand_over(&txn, config, copy).await?;
+    }
+    txn.commit().await?;
+    Ok(())
+}
+
+/// Cancel holds whose copy wasn't collected in time, passing each copy on to
+/// the next patron in line. Limited to one book when `book_id` is set.
+pub async fn expire_pickups(
+    connection: &DatabaseConnection,
+    config: &LoanConfig,
+    book_id: Option<Uuid>,
+) -> Result<u64, CirculationError> {
+    let mut select = EntityReservation::find()
+        .filter(ColumnReservation::Status.eq(ReservationStatus::ReadyForPickup))
+        .filter(ColumnReservation::PickupExpiresAt.lt(Utc::now().naive_utc()));
+    if let Some(book_id) = book_id {
+        select = select.filter(ColumnReservation::BookId.eq(book_id));
+    }
+    let mut expired = 0;
+    for hold in select.all(connection).await? {
+        match transition(
+            connection,
+            config,
+            hold.id,
+            ReservationStatus::Cancelled,
+            None,
+        )
+        .await
+        {
+            Ok(_) => expired += 1,
+            // Collected or cancelled meanwhile
+            Err(CirculationError::InvalidTransition(_, _)) => {}
+            Err(err) => warn!("Unable to expire hold {}: {:?}", hold.id, err),
+        }
+    }
+    Ok(expired)
+}
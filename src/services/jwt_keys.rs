@@ -0,0 +1,204 @@
+use crate::config::JwtConfig;
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, OctetKeyPairParameters,
+    OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use log::{info, warn};
+use ring::signature::{Ed25519KeyPair, KeyPair, RsaKeyPair};
+use std::io;
+use std::path::Path;
+use uuid::Uuid;
+
+/// One key of the keyring, able to sign and verify tokens.
+pub struct SigningKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    /// When the key starts signing; keys without a date are always eligible.
+    pub activates_at: Option<NaiveDateTime>,
+    pub encoding: EncodingKey,
+    pub decoding: DecodingKey,
+    jwk: Jwk,
+}
+
+/// Every key tokens may be signed with, loaded once at startup.
+///
+/// Keys are rotated by dropping a new file whose `kid` starts with its
+/// activation date (e.g. `2023-09-01.pem`): it is published in the JWKS right
+/// away and starts signing on that date, while older keys keep verifying the
+/// tokens they signed until they are removed from the directory.
+pub struct JwtKeys {
+    keys: Vec<SigningKey>,
+}
+
+impl JwtKeys {
+    pub fn new(keys: Vec<SigningKey>) -> io::Result<Self> {
+        let keys = JwtKeys { keys };
+        if keys.signing_key().is_none() {
+            return Err(invalid_key("No JWT key is active yet".to_string()));
+        }
+        Ok(keys)
+    }
+
+    /// The most recently activated key, used for every new token.
+    pub fn signing_key(&self) -> Option<&SigningKey> {
+        let now = Utc::now().naive_utc();
+        self.keys
+            .iter()
+            .filter(|key| key.activates_at <= Some(now))
+            .max_by(|a, b| {
+                a.activates_at
+                    .cmp(&b.activates_at)
+                    .then_with(|| a.kid.cmp(&b.kid))
+            })
+    }
+
+    pub fn find(&self, kid: &str) -> Option<&SigningKey> {
+        self.keys.iter().find(|key| key.kid == kid)
+    }
+
+    /// Public half of every key, as served on `/.well-known/jwks.json`.
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self.keys.iter().map(|key| key.jwk.clone()).collect(),
+        }
+    }
+}
+
+fn invalid_key(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Activation date encoded at the start of a `kid`, if any.
+fn activation_of(kid: &str) -> Option<NaiveDateTime> {
+    kid.get(..10)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+}
+
+fn common_parameters(kid: &str, algorithm: Algorithm) -> CommonParameters {
+    CommonParameters {
+        public_key_use: Some(PublicKeyUse::Signature),
+        algorithm: Some(algorithm),
+        key_id: Some(kid.to_string()),
+        ..Default::default()
+    }
+}
+
+fn rsa_key(kid: &str, key_pair: RsaKeyPair, pem: &[u8]) -> io::Result<SigningKey> {
+    let modulus = key_pair.public_key().modulus();
+    let exponent = key_pair.public_key().exponent();
+    let n = modulus.big_endian_without_leading_zero();
+    let e = exponent.big_endian_without_leading_zero();
+    Ok(SigningKey {
+        kid: kid.to_string(),
+        algorithm: Algorithm::RS256,
+        activates_at: activation_of(kid),
+        encoding: EncodingKey::from_rsa_pem(pem)
+            .map_err(|err| invalid_key(format!("{}: {}", kid, err)))?,
+        decoding: DecodingKey::from_rsa_raw_components(n, e),
+        jwk: Jwk {
+            common: common_parameters(kid, Algorithm::RS256),
+            algorithm: AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: general_purpose::URL_SAFE_NO_PAD.encode(n),
+                e: general_purpose::URL_SAFE_NO_PAD.encode(e),
+            }),
+        },
+    })
+}
+
+fn ed25519_key(kid: &str, pkcs8: &[u8]) -> io::Result<SigningKey> {
+    let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(pkcs8)
+        .map_err(|err| invalid_key(format!("{}: {}", kid, err)))?;
+    let x = general_purpose::URL_SAFE_NO_PAD.encode(key_pair.public_key().as_ref());
+    Ok(SigningKey {
+        kid: kid.to_string(),
+        algorithm: Algorithm::EdDSA,
+        activates_at: activation_of(kid),
+        encoding: EncodingKey::from_ed_der(pkcs8),
+        decoding: DecodingKey::from_ed_components(x.as_str())
+            .map_err(|err| invalid_key(format!("{}: {}", kid, err)))?,
+        jwk: Jwk {
+            common: common_parameters(kid, Algorithm::EdDSA),
+            algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x,
+            }),
+        },
+    })
+}
+
+/// Parse a PEM private key: RSA (PKCS#1 or PKCS#8) signs with RS256, Ed25519 with EdDSA.
+pub fn signing_key_from_pem(kid: &str, pem: &[u8]) -> io::Result<SigningKey> {
+    let parsed = pem::parse(pem).map_err(|err| invalid_key(format!("{}: {}", kid, err)))?;
+    match parsed.tag.as_str() {
+        "RSA PRIVATE KEY" => match RsaKeyPair::from_der(&parsed.contents) {
+            Ok(key_pair) => rsa_key(kid, key_pair, pem),
+            Err(err) => Err(invalid_key(format!("{}: {}", kid, err))),
+        },
+        "PRIVATE KEY" => match RsaKeyPair::from_pkcs8(&parsed.contents) {
+            Ok(key_pair) => rsa_key(kid, key_pair, pem),
+            Err(_) => ed25519_key(kid, &parsed.contents),
+        },
+        tag => Err(invalid_key(format!(
+            "{}: Unsupported key type {}",
+            kid, tag
+        ))),
+    }
+}
+
+/// Every `<kid>.pem` file of a directory.
+pub fn signing_keys_from_dir(directory: &Path) -> io::Result<Vec<SigningKey>> {
+    let mut keys = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("pem") {
+            continue;
+        }
+        let kid = match path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(kid) => kid.to_string(),
+            None => continue,
+        };
+        keys.push(signing_key_from_pem(kid.as_str(), &std::fs::read(&path)?)?);
+    }
+    Ok(keys)
+}
+
+/// Throwaway Ed25519 key, so a development setup works without any key files.
+fn ephemeral_key() -> io::Result<SigningKey> {
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&ring::rand::SystemRandom::new())
+        .map_err(|_| invalid_key("Unable to generate a JWT key".to_string()))?;
+    ed25519_key(
+        format!("ephemeral-{}", Uuid::new_v4()).as_str(),
+        pkcs8.as_ref(),
+    )
+}
+
+/// Load the keyring from the configured key directory.
+///
+/// Without any key file an ephemeral key is generated, which invalidates every
+/// token on restart and can't be shared between instances.
+pub fn jwt_keys_from_config(config: &JwtConfig) -> io::Result<JwtKeys> {
+    let path = Path::new(config.keys_dir.as_str());
+    let mut keys = if path.is_dir() {
+        signing_keys_from_dir(path)?
+    } else {
+        Vec::new()
+    };
+    if keys.is_empty() {
+        warn!(
+            "No JWT key found in {}, using an ephemeral key",
+            config.keys_dir
+        );
+        keys.push(ephemeral_key()?);
+    }
+    let keys = JwtKeys::new(keys)?;
+    if let Some(key) = keys.signing_key() {
+        info!("Signing tokens with key {}", key.kid);
+    }
+    Ok(keys)
+}
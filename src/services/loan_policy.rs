@@ -0,0 +1,240 @@
+use crate::config::{LoanConfig, LoanRule};
+use crate::models::users::Role;
+use chrono::{Duration, NaiveDateTime};
+
+/// Lending rules that apply to one borrower and one book.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoanPolicy {
+    pub loan_period_days: i64,
+    pub max_renewals: u32,
+    pub max_concurrent_loans: u32,
+    pub grace_period_days: i64,
+    pub daily_fine_cents: i64,
+    pub max_fine_cents: i64,
+}
+
+impl LoanPolicy {
+    /// Resolve the rules for a borrower `role` and a book `category`.
+    ///
+    /// Matching rules are applied from the least to the most specific, in file
+    /// order within the same specificity, so the last one applied wins.
+    pub fn resolve(config: &LoanConfig, role: Role, category: Option<&str>) -> LoanPolicy {
+        let mut policy = LoanPolicy {
+            loan_period_days: config.loan_period_days,
+            max_renewals: config.max_renewals,
+            max_concurrent_loans: config.max_concurrent_loans,
+            grace_period_days: config.grace_period_days,
+            daily_fine_cents: config.daily_fine_cents,
+            max_fine_cents: config.max_fine_cents,
+        };
+        let mut matching: Vec<(usize, &LoanRule)> = config
+            .rules
+            .iter()
+            .filter_map(|rule| specificity(rule, role, category).map(|rank| (rank, rule)))
+            .collect();
+        // Stable, so file order is kept within a rank
+        matching.sort_by_key(|(rank, _)| *rank);
+        for (_, rule) in matching {
+            policy.apply(rule);
+        }
+        policy
+    }
+
+    fn apply(&mut self, rule: &LoanRule) {
+        if let Some(days) = rule.loan_period_days {
+            self.loan_period_days = days;
+        }
+        if let Some(renewals) = rule.max_renewals {
+            self.max_renewals = renewals;
+        }
+        if let Some(loans) = rule.max_concurrent_loans {
+            self.max_concurrent_loans = loans;
+        }
+        if let Some(days) = rule.grace_period_days {
+            self.grace_period_days = days;
+        }
+        if let Some(cents) = rule.daily_fine_cents {
+            self.daily_fine_cents = cents;
+        }
+        if let Some(cents) = rule.max_fine_cents {
+            self.max_fine_cents = cents;
+        }
+    }
+
+    /// Due date of a loan, or of a renewal, starting at `from`.
+    pub fn due_date(&self, from: NaiveDateTime) -> NaiveDateTime {
+        from + Duration::days(self.loan_period_days)
+    }
+
+    /// Fine owed for a loan due at `due_date` and kept until `until`.
+    ///
+    /// Only full days late count, the grace days among them are free, and the
+    /// total never exceeds the cap.
+    pub fn fine_cents(&self, due_date: NaiveDateTime, until: NaiveDateTime) -> i64 {
+        let chargeable = (until - due_date).num_days() - self.grace_period_days;
+        if chargeable <= 0 {
+            return 0;
+        }
+        chargeable
+            .saturating_mul(self.daily_fine_cents)
+            .min(self.max_fine_cents)
+    }
+}
+
+/// How many criteria of `rule` match, or `None` if any of them doesn't.
+/// Categories compare case-insensitively.
+fn specificity(rule: &LoanRule, role: Role, category: Option<&str>) -> Option<usize> {
+    let role_matches = match rule.role {
+        Some(rule_role) if rule_role != role => return None,
+        Some(_) => 1,
+        None => 0,
+    };
+    let category_matches = match (rule.category.as_deref(), category) {
+        (Some(expected), Some(actual)) if expected.eq_ignore_ascii_case(actual) => 1,
+        (Some(_), _) => return None,
+        (None, _) => 0,
+    };
+    Some(role_matches + category_matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 8, day)
+            .and_then(|date| date.and_hms_opt(hour, 0, 0))
+            .unwrap()
+    }
+
+    fn config(rules: Vec<LoanRule>) -> LoanConfig {
+        LoanConfig {
+            rules,
+            ..LoanConfig::default()
+        }
+    }
+
+    fn rule(role: Option<Role>, category: Option<&str>, loan_period_days: i64) -> LoanRule {
+        LoanRule {
+            role,
+            category: category.map(str::to_string),
+            loan_period_days: Some(loan_period_days),
+            ..LoanRule::default()
+        }
+    }
+
+    #[test]
+    fn resolve_without_rules_uses_defaults() {
+        let config = config(Vec::new());
+        let policy = LoanPolicy::resolve(&config, Role::Member, Some("fiction"));
+        assert_eq!(policy.loan_period_days, config.loan_period_days);
+        assert_eq!(policy.max_renewals, config.max_renewals);
+        assert_eq!(policy.max_fine_cents, config.max_fine_cents);
+    }
+
+    #[test]
+    fn resolve_prefers_the_most_specific_rule() {
+        // Listed from the most to the least specific, so file order alone would pick wrong
+        let config = config(vec![
+            rule(Some(Role::Librarian), Some("reference"), 7),
+            rule(None, Some("reference"), 3),
+            rule(Some(Role::Librarian), None, 30),
+        ]);
+        let policy = |role, category| LoanPolicy::resolve(&config, role, category);
+        assert_eq!(
+            policy(Role::Librarian, Some("reference")).loan_period_days,
+            7
+        );
+        assert_eq!(policy(Role::Member, Some("reference")).loan_period_days, 3);
+        assert_eq!(
+            policy(Role::Librarian, Some("fiction")).loan_period_days,
+            30
+        );
+        assert_eq!(policy(Role::Librarian, None).loan_period_days, 30);
+        assert_eq!(policy(Role::Member, None).loan_period_days, 14);
+    }
+
+    #[test]
+    fn resolve_breaks_ties_by_file_order() {
+        let config = config(vec![
+            rule(Some(Role::Member), None, 10),
+            rule(None, Some("reference"), 3),
+            rule(Some(Role::Member), None, 21),
+        ]);
+        let policy = LoanPolicy::resolve(&config, Role::Member, None);
+        assert_eq!(policy.loan_period_days, 21);
+        // A role rule and a category rule tie too; the later one wins
+        let policy = LoanPolicy::resolve(&config, Role::Member, Some("Reference"));
+        assert_eq!(policy.loan_period_days, 21);
+    }
+
+    #[test]
+    fn resolve_merges_fields_of_matching_rules() {
+        let config = config(vec![
+            LoanRule {
+                category: Some("reference".to_string()),
+                max_renewals: Some(0),
+                ..LoanRule::default()
+            },
+            rule(Some(Role::Member), Some("reference"), 2),
+        ]);
+        let policy = LoanPolicy::resolve(&config, Role::Member, Some("reference"));
+        assert_eq!(policy.loan_period_days, 2);
+        assert_eq!(policy.max_renewals, 0);
+        assert_eq!(policy.max_concurrent_loans, config.max_concurrent_loans);
+    }
+
+    #[test]
+    fn due_date_adds_the_loan_period() {
+        let config = config(vec![
+            rule(None, Some("reference"), 3),
+            rule(Some(Role::Librarian), None, 28),
+        ]);
+        let from = at(1, 10);
+        let due = |role, category| LoanPolicy::resolve(&config, role, category).due_date(from);
+        assert_eq!(due(Role::Member, None), at(15, 10));
+        assert_eq!(due(Role::Member, Some("reference")), at(4, 10));
+        assert_eq!(due(Role::Librarian, None), at(29, 10));
+    }
+
+    fn fine_policy(grace_period_days: i64) -> LoanPolicy {
+        LoanPolicy {
+            loan_period_days: 14,
+            max_renewals: 2,
+            max_concurrent_loans: 5,
+            grace_period_days,
+            daily_fine_cents: 25,
+            max_fine_cents: 200,
+        }
+    }
+
+    #[test]
+    fn fine_counts_only_full_days_past_the_grace_period() {
+        let policy = fine_policy(2);
+        let due = at(1, 12);
+        assert_eq!(policy.fine_cents(due, at(1, 11)), 0);
+        assert_eq!(policy.fine_cents(due, at(3, 11)), 0);
+        assert_eq!(policy.fine_cents(due, at(3, 12)), 0);
+        assert_eq!(policy.fine_cents(due, at(4, 11)), 0);
+        assert_eq!(policy.fine_cents(due, at(4, 12)), 25);
+        assert_eq!(policy.fine_cents(due, at(5, 12)), 50);
+        assert_eq!(fine_policy(0).fine_cents(due, at(2, 12)), 25);
+    }
+
+    #[test]
+    fn fine_stops_at_the_cap() {
+        let policy = fine_policy(0);
+        let due = at(1, 0);
+        assert_eq!(policy.fine_cents(due, at(8, 0)), 175);
+        assert_eq!(policy.fine_cents(due, at(9, 0)), 200);
+        assert_eq!(policy.fine_cents(due, at(10, 0)), 200);
+        let forever = due + Duration::days(100_000);
+        let expensive = LoanPolicy {
+            daily_fine_cents: i64::MAX,
+            max_fine_cents: i64::MAX,
+            ..policy
+        };
+        assert_eq!(expensive.fine_cents(due, forever), i64::MAX);
+    }
+}
@@ -0,0 +1,405 @@
+use crate::config::{AttemptStoreKind, LockoutConfig, LockoutPolicyConfig};
+use crate::models::login_attempts::{Column as ColumnLoginAttempt, Entity as EntityLoginAttempt};
+use async_trait::async_trait;
+use chrono::{Duration, NaiveDateTime, Utc};
+use log::warn;
+use sea_orm::{
+    sea_query::Expr, ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, DbErr,
+    EntityTrait, QueryFilter, Statement,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Persistence for failed login counters, keyed by account or client address.
+#[async_trait]
+pub trait AttemptStore: Send + Sync {
+    async fn blocked_until(&self, key: &str) -> Result<Option<NaiveDateTime>, DbErr>;
+
+    /// Count a failure and return the new total; the count restarts when the
+    /// previous failure happened before `window_start`.
+    async fn record_failure(
+        &self,
+        key: &str,
+        now: NaiveDateTime,
+        window_start: NaiveDateTime,
+    ) -> Result<u32, DbErr>;
+
+    async fn block(&self, key: &str, until: NaiveDateTime) -> Result<(), DbErr>;
+
+    async fn clear(&self, key: &str) -> Result<(), DbErr>;
+}
+
+struct Attempts {
+    failures: u32,
+    last_failure_at: NaiveDateTime,
+    /// Counting window of the key's policy, as of its last failure.
+    window: Duration,
+    blocked_until: Option<NaiveDateTime>,
+}
+
+impl Attempts {
+    fn is_stale(&self, now: NaiveDateTime) -> bool {
+        self.last_failure_at < now - self.window
+            && !matches!(self.blocked_until, Some(until) if until > now)
+    }
+}
+
+/// How often the in-memory store drops entries that no longer matter.
+const SWEEP_INTERVAL_SECONDS: i64 = 60;
+
+#[derive(Default)]
+struct AttemptMap {
+    entries: HashMap<String, Attempts>,
+    next_sweep: Option<NaiveDateTime>,
+}
+
+/// Process-local store, fine for a single instance.
+#[derive(Default)]
+pub struct InMemoryAttemptStore {
+    attempts: Mutex<AttemptMap>,
+}
+
+#[async_trait]
+impl AttemptStore for InMemoryAttemptStore {
+    async fn blocked_until(&self, key: &str) -> Result<Option<NaiveDateTime>, DbErr> {
+        let attempts = self.attempts.lock().unwrap();
+        Ok(attempts
+            .entries
+            .get(key)
+            .and_then(|entry| entry.blocked_until))
+    }
+
+    async fn record_failure(
+        &self,
+        key: &str,
+        now: NaiveDateTime,
+        window_start: NaiveDateTime,
+    ) -> Result<u32, DbErr> {
+        let mut attempts = self.attempts.lock().unwrap();
+        // Forget stale entries now and then, so the map doesn't grow forever
+        // without paying for a full scan on every failure
+        if !matches!(attempts.next_sweep, Some(next) if next > now) {
+            attempts.entries.retain(|_, entry| !entry.is_stale(now));
+            attempts.next_sweep = Some(now + Duration::seconds(SWEEP_INTERVAL_SECONDS));
+        }
+        let entry = attempts.entries.entry(key.to_string()).or_insert(Attempts {
+            failures: 0,
+            last_failure_at: now,
+            window: now - window_start,
+            blocked_until: None,
+        });
+        if entry.last_failure_at < window_start {
+            entry.failures = 0;
+        }
+        entry.failures += 1;
+        entry.last_failure_at = now;
+        entry.window = now - window_start;
+        Ok(entry.failures)
+    }
+
+    async fn block(&self, key: &str, until: NaiveDateTime) -> Result<(), DbErr> {
+        let mut attempts = self.attempts.lock().unwrap();
+        if let Some(entry) = attempts.entries.get_mut(key) {
+            entry.blocked_until = Some(until);
+        }
+        Ok(())
+    }
+
+    async fn clear(&self, key: &str) -> Result<(), DbErr> {
+        self.attempts.lock().unwrap().entries.remove(key);
+        Ok(())
+    }
+}
+
+/// Store shared by every instance through the `login_attempts` table.
+pub struct PostgresAttemptStore {
+    connection: DatabaseConnection,
+}
+
+impl PostgresAttemptStore {
+    pub fn new(connection: DatabaseConnection) -> Self {
+        PostgresAttemptStore { connection }
+    }
+}
+
+#[async_trait]
+impl AttemptStore for PostgresAttemptStore {
+    async fn blocked_until(&self, key: &str) -> Result<Option<NaiveDateTime>, DbErr> {
+        Ok(EntityLoginAttempt::find_by_id(key.to_string())
+            .one(&self.connection)
+            .await?
+            .and_then(|attempt| attempt.blocked_until))
+    }
+
+    async fn record_failure(
+        &self,
+        key: &str,
+        now: NaiveDateTime,
+        window_start: NaiveDateTime,
+    ) -> Result<u32, DbErr> {
+        // Single statement, so concurrent failures can't lose increments
+        let row = self
+            .connection
+            .query_one(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                r#"
+                INSERT INTO login_attempts (key, failures, last_failure_at)
+                    VALUES ($1, 1, $2)
+                ON CONFLICT (key) DO UPDATE SET
+                    failures = CASE
+                        WHEN login_attempts.last_failure_at < $3 THEN 1
+                        ELSE login_attempts.failures + 1
+                    END,
+                    last_failure_at = $2
+                RETURNING failures
+                "#,
+                [key.into(), now.into(), window_start.into()],
+            ))
+            .await?
+            .ok_or_else(|| DbErr::RecordNotFound(key.to_string()))?;
+        let failures: i32 = row.try_get("", "failures")?;
+        Ok(failures as u32)
+    }
+
+    async fn block(&self, key: &str, until: NaiveDateTime) -> Result<(), DbErr> {
+        EntityLoginAttempt::update_many()
+            .col_expr(ColumnLoginAttempt::BlockedUntil, Expr::value(until))
+            .filter(ColumnLoginAttempt::Key.eq(key))
+            .exec(&self.connection)
+            .await?;
+        Ok(())
+    }
+
+    async fn clear(&self, key: &str) -> Result<(), DbErr> {
+        EntityLoginAttempt::delete_by_id(key.to_string())
+            .exec(&self.connection)
+            .await?;
+        Ok(())
+    }
+}
+
+/// How quickly repeated failures for one key are slowed down and locked out.
+#[derive(Clone, Debug)]
+pub struct LockoutPolicy {
+    /// Failures tolerated before any back-off applies.
+    pub free_attempts: u32,
+    pub backoff_base: Duration,
+    pub backoff_max: Duration,
+    /// Failures after which the key is locked for `lockout_duration`.
+    pub threshold: u32,
+    pub lockout_duration: Duration,
+    /// Failures older than this no longer count.
+    pub window: Duration,
+}
+
+impl LockoutPolicy {
+    /// How long the key must wait after its `failures`-th failure, if at all.
+    pub fn delay(&self, failures: u32) -> Option<Duration> {
+        if failures >= self.threshold {
+            return Some(self.lockout_duration);
+        }
+        if failures < self.free_attempts {
+            return None;
+        }
+        let exponent = (failures - self.free_attempts).min(20);
+        let backoff = 2i64
+            .checked_pow(exponent)
+            .and_then(|factor| self.backoff_base.num_milliseconds().checked_mul(factor))
+            .map_or(self.backoff_max, Duration::milliseconds);
+        Some(backoff.min(self.backoff_max))
+    }
+}
+
+pub struct Lockout {
+    store: Arc<dyn AttemptStore>,
+    account_policy: LockoutPolicy,
+    ip_policy: LockoutPolicy,
+}
+
+fn account_key(email: &str) -> String {
+    format!("account:{}", email.trim().to_lowercase())
+}
+
+fn ip_key(ip: &str) -> String {
+    format!("ip:{}", ip)
+}
+
+impl Lockout {
+    pub fn new(
+        store: Arc<dyn AttemptStore>,
+        account_policy: LockoutPolicy,
+        ip_policy: LockoutPolicy,
+    ) -> Self {
+        Lockout {
+            store,
+            account_policy,
+            ip_policy,
+        }
+    }
+
+    /// Time left before the account or address may try again, if blocked.
+    pub async fn retry_after(&self, email: &str, ip: &str) -> Result<Option<Duration>, DbErr> {
+        let now = Utc::now().naive_utc();
+        let mut wait: Option<Duration> = None;
+        for key in [account_key(email), ip_key(ip)] {
+            if let Some(until) = self.store.blocked_until(key.as_str()).await? {
+                if until > now {
+                    wait = wait.max(Some(until - now));
+                }
+            }
+        }
+        Ok(wait)
+    }
+
+    pub async fn record_failure(&self, email: &str, ip: &str) -> Result<(), DbErr> {
+        let now = Utc::now().naive_utc();
+        for (key, policy) in [
+            (account_key(email), &self.account_policy),
+            (ip_key(ip), &self.ip_policy),
+        ] {
+            let failures = self
+                .store
+                .record_failure(key.as_str(), now, now - policy.window)
+                .await?;
+            if let Some(delay) = policy.delay(failures) {
+                if failures >= policy.threshold {
+                    warn!("Locking out {} after {} failed logins", key, failures);
+                }
+                self.store.block(key.as_str(), now + delay).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Forget the failures of an account after a successful login.
+    ///
+    /// The address counter is left alone, otherwise logging into an own
+    /// account would reset the limit for guessing someone else's password.
+    pub async fn record_success(&self, email: &str) -> Result<(), DbErr> {
+        self.store.clear(account_key(email).as_str()).await
+    }
+
+    pub async fn clear_account(&self, email: &str) -> Result<(), DbErr> {
+        self.store.clear(account_key(email).as_str()).await
+    }
+
+    pub async fn clear_ip(&self, ip: &str) -> Result<(), DbErr> {
+        self.store.clear(ip_key(ip).as_str()).await
+    }
+}
+
+impl From<&LockoutPolicyConfig> for LockoutPolicy {
+    fn from(config: &LockoutPolicyConfig) -> Self {
+        LockoutPolicy {
+            free_attempts: config.free_attempts,
+            backoff_base: Duration::seconds(config.backoff_base_seconds),
+            backoff_max: Duration::seconds(config.backoff_max_seconds),
+            threshold: config.threshold,
+            lockout_duration: Duration::seconds(config.lockout_seconds),
+            window: Duration::seconds(config.window_seconds),
+        }
+    }
+}
+
+/// Build the lockout with the attempt store selected in the configuration.
+pub fn lockout_from_config(config: &LockoutConfig, connection: &DatabaseConnection) -> Lockout {
+    let store: Arc<dyn AttemptStore> = match config.store {
+        AttemptStoreKind::Postgres => Arc::new(PostgresAttemptStore::new(connection.clone())),
+        AttemptStoreKind::Memory => Arc::new(InMemoryAttemptStore::default()),
+    };
+    Lockout::new(store, (&config.account).into(), (&config.ip).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(backoff_base: Duration, backoff_max: Duration) -> LockoutPolicy {
+        LockoutPolicy {
+            free_attempts: 3,
+            backoff_base,
+            backoff_max,
+            threshold: 50,
+            lockout_duration: Duration::seconds(900),
+            window: Duration::seconds(3600),
+        }
+    }
+
+    #[test]
+    fn delay_doubles_after_the_free_attempts() {
+        let policy = policy(Duration::seconds(1), Duration::seconds(300));
+        assert_eq!(policy.delay(2), None);
+        assert_eq!(policy.delay(3), Some(Duration::seconds(1)));
+        assert_eq!(policy.delay(5), Some(Duration::seconds(4)));
+        assert_eq!(policy.delay(40), Some(Duration::seconds(300)));
+        assert_eq!(policy.delay(50), Some(Duration::seconds(900)));
+    }
+
+    #[test]
+    fn delay_caps_backoff_that_would_overflow() {
+        let long = Duration::seconds(i64::MAX / 1000);
+        let policy = policy(long, long);
+        assert_eq!(policy.delay(40), Some(long));
+    }
+
+    #[actix_web::test]
+    async fn in_memory_count_restarts_after_the_window() {
+        let store = InMemoryAttemptStore::default();
+        let start = Utc::now().naive_utc();
+        let window = Duration::seconds(3600);
+        for expected in 1..=3 {
+            let count = store.record_failure("ip:a", start, start - window).await;
+            assert_eq!(count.unwrap(), expected);
+        }
+        let later = start + window + Duration::seconds(1);
+        let count = store.record_failure("ip:a", later, later - window).await;
+        assert_eq!(count.unwrap(), 1);
+    }
+
+    #[actix_web::test]
+    async fn in_memory_sweep_drops_stale_entries_only() {
+        let store = InMemoryAttemptStore::default();
+        let start = Utc::now().naive_utc();
+        let window = Duration::seconds(3600);
+        for key in ["ip:stale", "ip:blocked"] {
+            store
+                .record_failure(key, start, start - window)
+                .await
+                .unwrap();
+        }
+        let later = start + window + Duration::seconds(SWEEP_INTERVAL_SECONDS);
+        store.block("ip:blocked", later + window).await.unwrap();
+        store
+            .record_failure("ip:new", later, later - window)
+            .await
+            .unwrap();
+        let attempts = store.attempts.lock().unwrap();
+        let mut keys: Vec<&str> = attempts.entries.keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(keys, ["ip:blocked", "ip:new"]);
+    }
+
+    #[actix_web::test]
+    async fn in_memory_sweep_keeps_entries_within_their_own_window() {
+        let store = InMemoryAttemptStore::default();
+        let start = Utc::now().naive_utc();
+        let (short, long) = (Duration::seconds(60), Duration::seconds(3600));
+        store
+            .record_failure("account:a", start, start - long)
+            .await
+            .unwrap();
+        store
+            .record_failure("ip:a", start, start - short)
+            .await
+            .unwrap();
+        let later = start + short + Duration::seconds(SWEEP_INTERVAL_SECONDS);
+        store
+            .record_failure("ip:b", later, later - short)
+            .await
+            .unwrap();
+        let attempts = store.attempts.lock().unwrap();
+        let mut keys: Vec<&str> = attempts.entries.keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(keys, ["account:a", "ip:b"]);
+    }
+}
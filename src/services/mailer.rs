@@ -0,0 +1,435 @@
+use crate::config::{MailerConfig, MailerKind, SmtpConfig, SmtpTls};
+use crate::utils::tls::upgrade;
+use async_trait::async_trait;
+use base64::{engine::general_purpose, Engine as _};
+use chrono::Utc;
+use log::info;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use uuid::Uuid;
+
+#[derive(Clone, Debug)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivery backend for outgoing emails.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: &Email) -> io::Result<()>;
+}
+
+/// Writes emails to the application log, for local development.
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, email: &Email) -> io::Result<()> {
+        info!("Email to {} ({}):\n{}", email.to, email.subject, email.body);
+        Ok(())
+    }
+}
+
+/// Drops every email as a file in a directory, so tests can read them back.
+pub struct FileMailer {
+    directory: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        FileMailer {
+            directory: directory.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: &Email) -> io::Result<()> {
+        std::fs::create_dir_all(&self.directory)?;
+        let path = self.directory.join(format!(
+            "{}-{}.eml",
+            Utc::now().format("%Y%m%d%H%M%S"),
+            Uuid::new_v4()
+        ));
+        std::fs::write(
+            path,
+            format!(
+                "To: {}\nSubject: {}\n\n{}\n",
+                email.to, email.subject, email.body
+            ),
+        )
+    }
+}
+
+/// Hands emails to an SMTP server, logging in with `AUTH PLAIN` when
+/// credentials are configured.
+pub struct SmtpMailer {
+    config: SmtpConfig,
+    from: String,
+}
+
+impl SmtpMailer {
+    pub fn new(config: SmtpConfig, from: impl Into<String>) -> Self {
+        SmtpMailer {
+            config,
+            from: from.into(),
+        }
+    }
+
+    async fn deliver(&self, email: &Email) -> io::Result<()> {
+        let host = self.config.host.as_str();
+        let stream = TcpStream::connect((host, self.config.port)).await?;
+        match self.config.tls {
+            SmtpTls::None => {
+                let mut session = BufReader::new(stream);
+                expect(&mut session, &[220]).await?;
+                self.hello(&mut session).await?;
+                self.transaction(&mut session, email).await
+            }
+            SmtpTls::StartTls => {
+                let mut session = BufReader::new(stream);
+                expect(&mut session, &[220]).await?;
+                self.hello(&mut session).await?;
+                command(&mut session, "STARTTLS", &[220]).await?;
+                // Nothing more was sent in the clear, so the buffer is empty
+                let mut session = BufReader::new(upgrade(session.into_inner(), host).await?);
+                self.hello(&mut session).await?;
+                self.transaction(&mut session, email).await
+            }
+            SmtpTls::Tls => {
+                let mut session = BufReader::new(upgrade(stream, host).await?);
+                expect(&mut session, &[220]).await?;
+                self.hello(&mut session).await?;
+                self.transaction(&mut session, email).await
+            }
+        }
+    }
+
+    async fn hello<S>(&self, session: &mut BufReader<S>) -> io::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let hello = format!("EHLO {}", mailbox_domain(&self.from));
+        command(session, hello.as_str(), &[250]).await
+    }
+
+    async fn transaction<S>(&self, session: &mut BufReader<S>, email: &Email) -> io::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        if let (Some(username), Some(password)) = (&self.config.username, &self.config.password) {
+            let credentials =
+                general_purpose::STANDARD.encode(format!("\0{}\0{}", username, password));
+            command(
+                session,
+                format!("AUTH PLAIN {}", credentials).as_str(),
+                &[235],
+            )
+            .await?;
+        }
+        let sender = format!("MAIL FROM:<{}>", mailbox_address(&self.from));
+        command(session, sender.as_str(), &[250]).await?;
+        let recipient = format!("RCPT TO:<{}>", mailbox_address(&email.to));
+        command(session, recipient.as_str(), &[250, 251]).await?;
+        command(session, "DATA", &[354]).await?;
+        session
+            .write_all(message(&self.from, email).as_bytes())
+            .await?;
+        command(session, ".", &[250]).await?;
+        // The server accepted the email, a failing goodbye changes nothing
+        let _ = command(session, "QUIT", &[221]).await;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: &Email) -> io::Result<()> {
+        tokio::time::timeout(
+            Duration::from_secs(self.config.timeout_seconds),
+            self.deliver(email),
+        )
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "SMTP server timed out"))?
+    }
+}
+
+/// Bare address of a mailbox such as `Name <user@example.com>`.
+fn mailbox_address(mailbox: &str) -> &str {
+    match (mailbox.find('<'), mailbox.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &mailbox[start + 1..end],
+        _ => mailbox.trim(),
+    }
+}
+
+fn mailbox_domain(mailbox: &str) -> &str {
+    mailbox_address(mailbox)
+        .rsplit_once('@')
+        .map_or("localhost", |(_, domain)| domain)
+}
+
+fn single_line(value: &str) -> String {
+    value.replace(['\r', '\n'], " ")
+}
+
+/// Longest header line RFC 5322 recommends, without the CRLF.
+const HEADER_LINE_LENGTH: usize = 78;
+
+/// Text bytes per MIME encoded word, so that one fits on a line even after
+/// `Subject: `.
+const ENCODED_WORD_BYTES: usize = 42;
+
+/// Join `words` with spaces, folding the line before a word that would run
+/// past `HEADER_LINE_LENGTH`; `used` is what the line already holds.
+fn fold<'a>(mut used: usize, words: impl IntoIterator<Item = &'a str>) -> String {
+    let mut folded = String::new();
+    for (index, word) in words.into_iter().enumerate() {
+        if index > 0 {
+            if used + 1 + word.len() > HEADER_LINE_LENGTH {
+                folded.push_str("\r\n");
+                used = 0;
+            }
+            folded.push(' ');
+            used += 1;
+        }
+        folded.push_str(word);
+        used += word.len();
+    }
+    folded
+}
+
+/// Subject as a header value, MIME-encoded unless it's plain ASCII and
+/// folded to keep its lines short.
+fn encoded_subject(subject: &str) -> String {
+    let value = single_line(subject);
+    let used = "Subject: ".len();
+    if value.is_ascii() {
+        return fold(used, value.split(' '));
+    }
+    // Encoded words may not split a character
+    let mut chunks = vec![String::new()];
+    for c in value.chars() {
+        if chunks.last().unwrap().len() + c.len_utf8() > ENCODED_WORD_BYTES {
+            chunks.push(String::new());
+        }
+        chunks.last_mut().unwrap().push(c);
+    }
+    let words: Vec<String> = chunks
+        .iter()
+        .map(|chunk| format!("=?UTF-8?B?{}?=", general_purpose::STANDARD.encode(chunk)))
+        .collect();
+    fold(used, words.iter().map(String::as_str))
+}
+
+/// The email as sent after `DATA`, with CRLF line endings and leading dots doubled.
+fn message(from: &str, email: &Email) -> String {
+    let mut message = format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMessage-ID: <{}@{}>\r\n\
+         MIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\n\
+         Content-Transfer-Encoding: 8bit\r\n\r\n",
+        single_line(from),
+        single_line(&email.to),
+        encoded_subject(&email.subject),
+        Utc::now().to_rfc2822(),
+        Uuid::new_v4(),
+        mailbox_domain(from)
+    );
+    for line in email.body.lines() {
+        if line.starts_with('.') {
+            message.push('.');
+        }
+        message.push_str(line);
+        message.push_str("\r\n");
+    }
+    message
+}
+
+/// Read one reply, joining the lines of multi-line replies.
+async fn reply<S>(session: &mut BufReader<S>) -> io::Result<(u16, String)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut text = String::new();
+    loop {
+        let mut line = String::new();
+        if session.read_line(&mut line).await? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "SMTP server closed the connection",
+            ));
+        }
+        let code = line
+            .get(..3)
+            .and_then(|code| code.parse::<u16>().ok())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid SMTP reply {:?}", line),
+                )
+            })?;
+        text.push_str(line.get(4..).unwrap_or("").trim_end());
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Ok((code, text));
+        }
+        text.push(' ');
+    }
+}
+
+async fn expect<S>(session: &mut BufReader<S>, expected: &[u16]) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (code, text) = reply(session).await?;
+    if expected.contains(&code) {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "SMTP server answered {} {}",
+            code, text
+        )))
+    }
+}
+
+async fn command<S>(session: &mut BufReader<S>, line: &str, expected: &[u16]) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    session
+        .write_all(format!("{}\r\n", line).as_bytes())
+        .await?;
+    session.flush().await?;
+    expect(session, expected).await
+}
+
+/// Build the mailer selected in the configuration.
+pub fn mailer_from_config(config: &MailerConfig) -> Arc<dyn Mailer> {
+    match config.kind {
+        MailerKind::File => Arc::new(FileMailer::new(config.dir.as_str())),
+        MailerKind::Log => Arc::new(LogMailer),
+        MailerKind::Smtp => Arc::new(SmtpMailer::new(config.smtp.clone(), config.from.as_str())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email(subject: &str, body: &str) -> Email {
+        Email {
+            to: "reader@example.com".to_string(),
+            subject: subject.to_string(),
+            body: body.to_string(),
+        }
+    }
+
+    /// Header lines of `message`, unfolded into one line per header.
+    fn headers(message: &str) -> Vec<String> {
+        let (head, _) = message.split_once("\r\n\r\n").unwrap();
+        head.replace("\r\n ", " ")
+            .split("\r\n")
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn header(message: &str, name: &str) -> String {
+        let prefix = format!("{}: ", name);
+        headers(message)
+            .into_iter()
+            .find_map(|line| line.strip_prefix(prefix.as_str()).map(str::to_string))
+            .unwrap()
+    }
+
+    async fn reply_to(server: &[u8]) -> io::Result<(u16, String)> {
+        let (client, mut peer) = tokio::io::duplex(1024);
+        peer.write_all(server).await.unwrap();
+        drop(peer);
+        reply(&mut BufReader::new(client)).await
+    }
+
+    #[test]
+    fn message_doubles_leading_dots() {
+        let message = message(
+            "Library <library@example.com>",
+            &email("Dots", ".\n.hidden\nplain. line\n"),
+        );
+        let (_, body) = message.split_once("\r\n\r\n").unwrap();
+        assert_eq!(body, "..\r\n..hidden\r\nplain. line\r\n");
+    }
+
+    #[test]
+    fn message_keeps_header_values_on_their_header() {
+        let message = message(
+            "library@example.com",
+            &email("Hello\r\nBcc: someone@example.com", "Body"),
+        );
+        assert!(!headers(&message)
+            .iter()
+            .any(|line| line.starts_with("Bcc:")));
+        assert_eq!(
+            header(&message, "Subject"),
+            "Hello  Bcc: someone@example.com"
+        );
+    }
+
+    #[test]
+    fn message_folds_long_ascii_subjects() {
+        let subject = "Your loan of a book with a rather long title is due in three days, \
+                       please return or renew it";
+        let message = message("library@example.com", &email(subject, "Body"));
+        let (head, _) = message.split_once("\r\n\r\n").unwrap();
+        assert!(head.contains("\r\n "));
+        assert!(head
+            .split("\r\n")
+            .all(|line| line.len() <= HEADER_LINE_LENGTH));
+        assert_eq!(header(&message, "Subject"), subject);
+    }
+
+    #[test]
+    fn message_encodes_and_folds_unicode_subjects() {
+        let subject = "«Cien años de soledad» está listo para recoger en la biblioteca";
+        let message = message("library@example.com", &email(subject, "Body"));
+        let (head, _) = message.split_once("\r\n\r\n").unwrap();
+        assert!(head
+            .split("\r\n")
+            .all(|line| line.len() <= HEADER_LINE_LENGTH));
+        let decoded: Vec<u8> = header(&message, "Subject")
+            .split(' ')
+            .flat_map(|word| {
+                let encoded = word.strip_prefix("=?UTF-8?B?").unwrap();
+                let encoded = encoded.strip_suffix("?=").unwrap();
+                general_purpose::STANDARD.decode(encoded).unwrap()
+            })
+            .collect();
+        assert_eq!(String::from_utf8(decoded).unwrap(), subject);
+    }
+
+    #[actix_web::test]
+    async fn reply_reads_a_single_line() {
+        let (code, text) = reply_to(b"250 OK\r\n").await.unwrap();
+        assert_eq!((code, text.as_str()), (250, "OK"));
+    }
+
+    #[actix_web::test]
+    async fn reply_joins_multi_line_replies() {
+        let (code, text) = reply_to(b"250-mail.example.com\r\n250-SIZE 1000\r\n250 AUTH PLAIN\r\n")
+            .await
+            .unwrap();
+        assert_eq!(code, 250);
+        assert_eq!(text, "mail.example.com SIZE 1000 AUTH PLAIN");
+    }
+
+    #[actix_web::test]
+    async fn reply_refuses_garbage_and_closed_connections() {
+        let garbage = reply_to(b"hello\r\n").await.unwrap_err();
+        assert_eq!(garbage.kind(), io::ErrorKind::InvalidData);
+        let closed = reply_to(b"250-mail.example.com\r\n").await.unwrap_err();
+        assert_eq!(closed.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
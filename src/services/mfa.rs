@@ -0,0 +1,204 @@
+use crate::models::recovery_codes::{
+    ActiveModel as ActiveModelRecoveryCode, Column as ColumnRecoveryCode,
+    Entity as EntityRecoveryCode,
+};
+use crate::models::users::{
+    ActiveModel as ActiveModelUser, Column as ColumnUser, Entity as EntityUser, Model as ModelUser,
+};
+use crate::utils::token::hash_opaque_token;
+use chrono::Utc;
+use rand::{rngs::OsRng, Rng, RngCore};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection,
+    DbErr, EntityTrait, QueryFilter, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use totp_rs::{Algorithm, Secret, TOTP};
+use uuid::Uuid;
+
+const ISSUER: &str = "BookBorrow";
+const STEP: u64 = 30;
+const RECOVERY_CODES: usize = 10;
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+#[derive(Debug)]
+pub enum MfaError {
+    NotEnrolled,
+    AlreadyEnabled,
+    InvalidCode,
+    Totp(String),
+    Db(DbErr),
+}
+
+impl From<DbErr> for MfaError {
+    fn from(err: DbErr) -> Self {
+        MfaError::Db(err)
+    }
+}
+
+/// Secret and provisioning URI shown to the user while enrolling.
+#[derive(Serialize, Deserialize)]
+pub struct Enrollment {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+fn totp(user: &ModelUser, secret: &str) -> Result<TOTP, MfaError> {
+    let bytes = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|err| MfaError::Totp(format!("{:?}", err)))?;
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        STEP,
+        bytes,
+        Some(ISSUER.to_string()),
+        user.email.to_owned(),
+    )
+    .map_err(|err| MfaError::Totp(err.to_string()))
+}
+
+/// Time step matched by `code` within one step of clock skew, if any.
+fn matching_step(totp: &TOTP, code: &str) -> Option<i64> {
+    let current = Utc::now().timestamp() / STEP as i64;
+    (current - 1..=current + 1).find(|step| {
+        let expected = totp.generate(*step as u64 * STEP);
+        bool::from(expected.as_bytes().ct_eq(code.trim().as_bytes()))
+    })
+}
+
+fn generate_recovery_code() -> String {
+    let code: String = (0..10)
+        .map(|_| {
+            let index = OsRng.gen_range(0..RECOVERY_CODE_ALPHABET.len());
+            RECOVERY_CODE_ALPHABET[index] as char
+        })
+        .collect();
+    format!("{}-{}", &code[..5], &code[5..])
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.trim().to_lowercase()
+}
+
+/// Generate a new secret for the user; it only takes effect once confirmed.
+pub async fn start_enrollment(
+    connection: &DatabaseConnection,
+    user: &ModelUser,
+) -> Result<Enrollment, MfaError> {
+    if user.totp_enabled {
+        return Err(MfaError::AlreadyEnabled);
+    }
+    let mut bytes = [0u8; 20];
+    OsRng.fill_bytes(&mut bytes);
+    let secret = Secret::Raw(bytes.to_vec()).to_encoded().to_string();
+    let otpauth_uri = totp(user, secret.as_str())?.get_url();
+
+    let mut model: ActiveModelUser = user.clone().into();
+    model.totp_secret = Set(Some(secret.to_owned()));
+    model.update(connection).await?;
+
+    Ok(Enrollment {
+        secret,
+        otpauth_uri,
+    })
+}
+
+/// Enable TOTP once the user proves their app produces valid codes, and
+/// return a fresh set of recovery codes (the only time they are shown).
+pub async fn confirm_enrollment(
+    connection: &DatabaseConnection,
+    user: &ModelUser,
+    code: &str,
+) -> Result<Vec<String>, MfaError> {
+    if user.totp_enabled {
+        return Err(MfaError::AlreadyEnabled);
+    }
+    let secret = user.totp_secret.as_ref().ok_or(MfaError::NotEnrolled)?;
+    let step = matching_step(&totp(user, secret)?, code).ok_or(MfaError::InvalidCode)?;
+
+    let txn = connection.begin().await?;
+    let mut model: ActiveModelUser = user.clone().into();
+    model.totp_enabled = Set(true);
+    model.totp_last_step = Set(Some(step));
+    model.update(&txn).await?;
+    let codes = replace_recovery_codes(&txn, user.id).await?;
+    txn.commit().await?;
+    Ok(codes)
+}
+
+async fn replace_recovery_codes<C: ConnectionTrait>(
+    connection: &C,
+    user_id: Uuid,
+) -> Result<Vec<String>, DbErr> {
+    EntityRecoveryCode::delete_many()
+        .filter(ColumnRecoveryCode::UserId.eq(user_id))
+        .exec(connection)
+        .await?;
+    let codes: Vec<String> = (0..RECOVERY_CODES)
+        .map(|_| generate_recovery_code())
+        .collect();
+    for code in &codes {
+        ActiveModelRecoveryCode {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user_id),
+            code_hash: Set(hash_opaque_token(normalize_recovery_code(code).as_str())),
+            used_at: Set(None),
+            created_at: Set(Some(Utc::now().naive_utc())),
+        }
+        .insert(connection)
+        .await?;
+    }
+    Ok(codes)
+}
+
+/// Check a TOTP code or, failing that, consume a recovery code.
+///
+/// A TOTP code is accepted only once, so an intercepted code can't be replayed
+/// within its validity window.
+pub async fn verify_second_factor(
+    connection: &DatabaseConnection,
+    user: &ModelUser,
+    code: &str,
+) -> Result<(), MfaError> {
+    let secret = match (&user.totp_secret, user.totp_enabled) {
+        (Some(secret), true) => secret,
+        _ => return Err(MfaError::NotEnrolled),
+    };
+    if let Some(step) = matching_step(&totp(user, secret)?, code) {
+        let result = EntityUser::update_many()
+            .col_expr(ColumnUser::TotpLastStep, Expr::value(step))
+            .filter(ColumnUser::Id.eq(user.id))
+            .filter(
+                Condition::any()
+                    .add(ColumnUser::TotpLastStep.is_null())
+                    .add(ColumnUser::TotpLastStep.lt(step)),
+            )
+            .exec(connection)
+            .await?;
+        return match result.rows_affected {
+            0 => Err(MfaError::InvalidCode),
+            _ => Ok(()),
+        };
+    }
+
+    let result = EntityRecoveryCode::update_many()
+        .col_expr(
+            ColumnRecoveryCode::UsedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(ColumnRecoveryCode::UserId.eq(user.id))
+        .filter(
+            ColumnRecoveryCode::CodeHash
+                .eq(hash_opaque_token(normalize_recovery_code(code).as_str())),
+        )
+        .filter(ColumnRecoveryCode::UsedAt.is_null())
+        .exec(connection)
+        .await?;
+    match result.rows_affected {
+        0 => Err(MfaError::InvalidCode),
+        _ => Ok(()),
+    }
+}
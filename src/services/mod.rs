@@ -0,0 +1,18 @@
+pub mod availability;
+pub mod circulation;
+pub mod fines;
+pub mod holds;
+pub mod jwt_keys;
+pub mod loan_policy;
+pub mod lockout;
+pub mod mailer;
+pub mod mfa;
+pub mod notifications;
+pub mod password_reset;
+pub mod scheduler;
+pub mod search;
+pub mod sessions;
+#[cfg(test)]
+pub mod testing;
+pub mod transports;
+pub mod verification;
@@ -0,0 +1,281 @@
+use crate::config::{Config, NotificationConfig};
+use crate::models::books::Entity as EntityBook;
+use crate::models::notifications::{
+    ActiveModel as ActiveModelNotification, Channel, Column as ColumnNotification,
+    Entity as EntityNotification, NotificationKind, NotificationStatus,
+};
+use crate::models::reservations::Model as ModelReservation;
+use crate::models::users::Entity as EntityUser;
+use crate::services::fines::format_cents;
+use crate::services::mailer::Mailer;
+use crate::services::transports::{EmailTransport, Transport, WebhookTransport};
+use chrono::{NaiveDateTime, Utc};
+use log::warn;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set,
+};
+use serde_json::{json, Value};
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+use uuid::Uuid;
+
+/// Language of users who didn't choose one, and of templates missing a translation.
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Languages notifications are written in.
+pub const SUPPORTED_LOCALES: &[&str] = &["en", "es", "fr"];
+
+/// Subject and body of `kind` in `locale`, with `{name}` placeholders.
+fn template(kind: NotificationKind, locale: &str) -> (&'static str, &'static str) {
+    use NotificationKind::*;
+    match (locale, kind) {
+        ("es", DueSoon) => (
+            "\"{title}\" vence el {due_date}",
+            "El préstamo de \"{title}\" vence el {due_date}. Renuévalo o devuélvelo a tiempo para evitar una multa.",
+        ),
+        ("es", Overdue) => (
+            "\"{title}\" está vencido",
+            "El préstamo de \"{title}\" venció el {due_date}. Devuélvelo lo antes posible, se cobra una multa por cada día de retraso.",
+        ),
+        ("es", HoldReady) => (
+            "\"{title}\" está listo para recoger",
+            "Un ejemplar de \"{title}\" está apartado para ti hasta el {pickup_by}. Después pasará a la siguiente persona en la lista.",
+        ),
+        ("es", FineAssessed) => (
+            "Se cobró una multa de {amount}",
+            "Se cobró una multa de {amount} por \"{title}\". Puedes consultar tu saldo y pagarlo en la biblioteca.",
+        ),
+        ("fr", DueSoon) => (
+            "« {title} » est à rendre le {due_date}",
+            "Le prêt de « {title} » est à rendre le {due_date}. Prolongez-le ou rendez-le à temps pour éviter une amende.",
+        ),
+        ("fr", Overdue) => (
+            "« {title} » est en retard",
+            "Le prêt de « {title} » était à rendre le {due_date}. Merci de le rendre au plus vite, une amende s'applique pour chaque jour de retard.",
+        ),
+        ("fr", HoldReady) => (
+            "« {title} » vous attend",
+            "Un exemplaire de « {title} » vous est réservé jusqu'au {pickup_by}. Passé ce délai, il ira au lecteur suivant.",
+        ),
+        ("fr", FineAssessed) => (
+            "Une amende de {amount} a été appliquée",
+            "Une amende de {amount} a été appliquée pour « {title} ». Vous pouvez consulter votre solde et la régler à la bibliothèque.",
+        ),
+        (_, DueSoon) => (
+            "\"{title}\" is due on {due_date}",
+            "Your loan of \"{title}\" is due back on {due_date}. Renew or return it in time to avoid a fine.",
+        ),
+        (_, Overdue) => (
+            "\"{title}\" is overdue",
+            "Your loan of \"{title}\" was due on {due_date}. Please return it as soon as possible, a fine accrues for every day it is late.",
+        ),
+        (_, HoldReady) => (
+            "\"{title}\" is ready for pickup",
+            "A copy of \"{title}\" is set aside for you until {pickup_by}. After that it goes to the next patron in line.",
+        ),
+        (_, FineAssessed) => (
+            "A fine of {amount} was charged",
+            "A fine of {amount} was charged for \"{title}\". You can check your balance and pay it at the library.",
+        ),
+    }
+}
+
+/// Replace the `{name}` placeholders of `text` with the values in `data`.
+fn render(text: &str, data: &Value) -> String {
+    let mut rendered = text.to_string();
+    if let Value::Object(values) = data {
+        for (name, value) in values {
+            let value = match value {
+                Value::String(value) => value.to_owned(),
+                Value::Null => String::new(),
+                other => other.to_string(),
+            };
+            rendered = rendered.replace(format!("{{{}}}", name).as_str(), value.as_str());
+        }
+    }
+    rendered
+}
+
+/// Queue `kind` in the user's language on every channel they chose, unless
+/// they opted out of it.
+///
+/// Called within the transaction of the change it's about, so the
+/// notification is only sent if that change commits.
+pub async fn notify<C: ConnectionTrait>(
+    connection: &C,
+    user_id: Uuid,
+    kind: NotificationKind,
+    data: Value,
+) -> Result<(), DbErr> {
+    let user = match EntityUser::find_by_id(user_id).one(connection).await? {
+        Some(user) if user.wants(kind) => user,
+        _ => return Ok(()),
+    };
+    let (subject, body) = template(kind, user.locale.as_str());
+    let (subject, body) = (render(subject, &data), render(body, &data));
+    for channel in user.channels() {
+        ActiveModelNotification {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user.id),
+            kind: Set(kind),
+            channel: Set(channel),
+            recipient: Set(user.email.to_owned()),
+            subject: Set(subject.to_owned()),
+            body: Set(body.to_owned()),
+            data: Set(data.clone()),
+            status: Set(NotificationStatus::Pending),
+            attempts: Set(0),
+            next_attempt_at: Set(Utc::now().naive_utc()),
+            last_error: Set(None),
+            created_at: Set(Utc::now().naive_utc()),
+            sent_at: Set(None),
+        }
+        .insert(connection)
+        .await?;
+    }
+    Ok(())
+}
+
+fn format_date(date: Option<NaiveDateTime>) -> Value {
+    date.map_or(Value::Null, |date| {
+        Value::String(date.format("%Y-%m-%d").to_string())
+    })
+}
+
+/// Queue `kind` for the borrower of a reservation, with its book and dates.
+pub async fn notify_reservation<C: ConnectionTrait>(
+    connection: &C,
+    kind: NotificationKind,
+    reservation: &ModelReservation,
+    amount_cents: Option<i64>,
+) -> Result<(), DbErr> {
+    let title = EntityBook::find_by_id(reservation.book_id)
+        .one(connection)
+        .await?
+        .map(|book| book.title)
+        .unwrap_or_default();
+    let data = json!({
+        "reservation_id": reservation.id,
+        "title": title,
+        "due_date": format_date(reservation.due_date),
+        "pickup_by": format_date(reservation.pickup_expires_at),
+        "amount": amount_cents.map(format_cents),
+    });
+    notify(connection, reservation.user_id, kind, data).await
+}
+
+/// Delivers the outbox over the transport of each channel.
+pub struct Notifier {
+    email: Arc<dyn Transport>,
+    /// Unset when no webhook is configured.
+    webhook: Option<Arc<dyn Transport>>,
+}
+
+impl Notifier {
+    pub fn new(email: Arc<dyn Transport>, webhook: Option<Arc<dyn Transport>>) -> Self {
+        Notifier { email, webhook }
+    }
+
+    fn transport(&self, channel: Channel) -> Option<&dyn Transport> {
+        match channel {
+            Channel::Email => Some(self.email.as_ref()),
+            Channel::Webhook => self.webhook.as_deref(),
+        }
+    }
+
+    /// Attempt the notifications that are due, oldest first. Failures are
+    /// retried with exponential back-off until the attempts run out.
+    /// Returns how many were sent.
+    pub async fn deliver_pending(
+        &self,
+        connection: &DatabaseConnection,
+        config: &NotificationConfig,
+    ) -> Result<u64, DbErr> {
+        let pending = EntityNotification::find()
+            .filter(ColumnNotification::Status.eq(NotificationStatus::Pending))
+            .filter(ColumnNotification::NextAttemptAt.lte(Utc::now().naive_utc()))
+            .order_by_asc(ColumnNotification::NextAttemptAt)
+            .limit(config.batch_size)
+            .all(connection)
+            .await?;
+        let mut sent = 0;
+        for notification in pending {
+            let result = match self.transport(notification.channel) {
+                Some(transport) => transport.deliver(&notification).await,
+                None => Err(io::Error::other("no webhook is configured")),
+            };
+            let attempts = notification.attempts + 1;
+            let id = notification.id;
+            let mut model: ActiveModelNotification = notification.into();
+            model.attempts = Set(attempts);
+            match result {
+                Ok(()) => {
+                    model.status = Set(NotificationStatus::Sent);
+                    model.sent_at = Set(Some(Utc::now().naive_utc()));
+                    model.last_error = Set(None);
+                    sent += 1;
+                }
+                Err(err) if attempts as u32 >= config.max_attempts => {
+                    warn!("Giving up on notification {}: {}", id, err);
+                    model.status = Set(NotificationStatus::Failed);
+                    model.last_error = Set(Some(err.to_string()));
+                }
+                Err(err) => {
+                    model.next_attempt_at =
+                        Set(Utc::now().naive_utc() + config.retry_delay(attempts as u32));
+                    model.last_error = Set(Some(err.to_string()));
+                }
+            }
+            model.update(connection).await?;
+        }
+        Ok(sent)
+    }
+}
+
+/// Build the transports selected in the configuration.
+pub fn notifier_from_config(config: &Config, mailer: Arc<dyn Mailer>) -> Notifier {
+    let webhook = config
+        .notifications
+        .webhook_url
+        .as_deref()
+        .and_then(|url| Url::parse(url).ok())
+        .map(|url| {
+            Arc::new(WebhookTransport::new(
+                url,
+                config.notifications.webhook_secret.clone(),
+                Duration::from_secs(config.notifications.webhook_timeout_seconds),
+            )) as Arc<dyn Transport>
+        });
+    Notifier::new(Arc::new(EmailTransport::new(mailer)), webhook)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_fills_placeholders_from_the_data() {
+        let data = json!({
+            "title": "Dune",
+            "renewals": 2,
+            "pickup_by": null,
+        });
+        assert_eq!(
+            render(
+                "\"{title}\" renewed {renewals} times, {title} again{pickup_by}",
+                &data
+            ),
+            "\"Dune\" renewed 2 times, Dune again"
+        );
+    }
+
+    #[test]
+    fn render_leaves_unknown_placeholders_alone() {
+        let data = json!({ "title": "Dune" });
+        assert_eq!(render("{title} by {author}", &data), "Dune by {author}");
+        assert_eq!(render("{title}", &Value::Null), "{title}");
+    }
+}
@@ -0,0 +1,146 @@
+use crate::config::{Config, PasswordConfig};
+use crate::models::password_reset_tokens::{
+    ActiveModel as ActiveModelResetToken, Column as ColumnResetToken, Entity as EntityResetToken,
+};
+use crate::models::users::{
+    ActiveModel as ActiveModelUser, Column as ColumnUser, Entity as EntityUser,
+};
+use crate::services::mailer::{Email, Mailer};
+use crate::services::sessions::revoke_all_sessions;
+use crate::utils::password::hash_password;
+use crate::utils::token::{generate_opaque_token, hash_opaque_token};
+use chrono::Utc;
+use log::warn;
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub enum ResetError {
+    Invalid,
+    Expired,
+    Hash(argon2::password_hash::Error),
+    Db(DbErr),
+}
+
+impl From<DbErr> for ResetError {
+    fn from(err: DbErr) -> Self {
+        ResetError::Db(err)
+    }
+}
+
+/// Email a reset link if the address belongs to an account.
+///
+/// Unknown addresses, and requests within the resend interval of the last
+/// one, are silently ignored so the endpoint can't be used to probe which
+/// emails are registered or to flood an inbox.
+pub async fn request_password_reset(
+    connection: &DatabaseConnection,
+    config: &Config,
+    mailer: &dyn Mailer,
+    email: &str,
+) -> Result<(), DbErr> {
+    let user = match EntityUser::find()
+        .filter(ColumnUser::Email.eq(email))
+        .one(connection)
+        .await?
+    {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    let last_sent = EntityResetToken::find()
+        .filter(ColumnResetToken::UserId.eq(user.id))
+        .order_by_desc(ColumnResetToken::CreatedAt)
+        .one(connection)
+        .await?
+        .and_then(|reset| reset.created_at);
+    let now = Utc::now();
+    if let Some(last_sent) = last_sent {
+        if last_sent > (now - config.accounts.password_reset_resend_interval()).naive_utc() {
+            return Ok(());
+        }
+    }
+
+    // Only the most recent link stays usable
+    EntityResetToken::update_many()
+        .col_expr(ColumnResetToken::UsedAt, Expr::value(now.naive_utc()))
+        .filter(ColumnResetToken::UserId.eq(user.id))
+        .filter(ColumnResetToken::UsedAt.is_null())
+        .exec(connection)
+        .await?;
+
+    let token = generate_opaque_token();
+    ActiveModelResetToken {
+        id: Set(Uuid::new_v4()),
+        user_id: Set(user.id),
+        token_hash: Set(hash_opaque_token(token.as_str())),
+        expires_at: Set((now + config.accounts.password_reset_lifetime()).naive_utc()),
+        used_at: Set(None),
+        created_at: Set(Some(now.naive_utc())),
+    }
+    .insert(connection)
+    .await?;
+
+    let email = Email {
+        to: user.email.to_owned(),
+        subject: "Reset your BookBorrow password".to_string(),
+        body: format!(
+            "Someone asked to reset the password of your BookBorrow account.\n\n\
+             Send this token along with your new password to {}, \
+             or ignore this email if it wasn't you:\n{}",
+            config.app_link("/password/reset"),
+            token
+        ),
+    };
+    if let Err(err) = mailer.send(&email).await {
+        warn!(
+            "Unable to send password reset email to {}: {}",
+            user.email, err
+        );
+    }
+    Ok(())
+}
+
+/// Consume a reset token, set the new password and sign the user out everywhere.
+pub async fn reset_password(
+    connection: &DatabaseConnection,
+    config: &PasswordConfig,
+    token: &str,
+    password: &str,
+) -> Result<(), ResetError> {
+    let hash = hash_password(config, password).map_err(ResetError::Hash)?;
+
+    let txn = connection.begin().await?;
+    let reset = EntityResetToken::find()
+        .filter(ColumnResetToken::TokenHash.eq(hash_opaque_token(token)))
+        .filter(ColumnResetToken::UsedAt.is_null())
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+        .ok_or(ResetError::Invalid)?;
+    if reset.expires_at <= Utc::now().naive_utc() {
+        return Err(ResetError::Expired);
+    }
+    let user = EntityUser::find_by_id(reset.user_id)
+        .one(&txn)
+        .await?
+        .ok_or(ResetError::Invalid)?;
+
+    let mut model: ActiveModelResetToken = reset.into();
+    model.used_at = Set(Some(Utc::now().naive_utc()));
+    model.update(&txn).await?;
+
+    let user_id = user.id;
+    let mut model: ActiveModelUser = user.into();
+    model.password = Set(hash);
+    model.password_changed_at = Set(Some(Utc::now().naive_utc()));
+    model.updated_at = Set(Some(Utc::now().naive_utc()));
+    model.update(&txn).await?;
+
+    revoke_all_sessions(&txn, user_id).await?;
+    txn.commit().await?;
+    Ok(())
+}
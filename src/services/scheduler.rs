@@ -0,0 +1,251 @@
+use crate::config::Config;
+use crate::models::email_verification_tokens::{
+    Column as ColumnVerificationToken, Entity as EntityVerificationToken,
+};
+use crate::models::job_runs::{
+    ActiveModel as ActiveModelJobRun, Column as ColumnJobRun, Entity as EntityJobRun, Job,
+    JobStatus, JobTrigger, Model as ModelJobRun,
+};
+use crate::models::notifications::NotificationKind;
+use crate::models::password_reset_tokens::{
+    Column as ColumnPasswordResetToken, Entity as EntityPasswordResetToken,
+};
+use crate::models::refresh_tokens::{Column as ColumnRefreshToken, Entity as EntityRefreshToken};
+use crate::models::reservations::{
+    ActiveModel as ActiveModelReservation, Column as ColumnReservation,
+    Entity as EntityReservation, ReservationStatus,
+};
+use crate::models::revoked_tokens::{Column as ColumnRevokedToken, Entity as EntityRevokedToken};
+use crate::services::circulation::{mark_overdue, CirculationError};
+use crate::services::holds::expire_pickups;
+use crate::services::notifications::{notify_reservation, Notifier};
+use actix_web::rt;
+use chrono::{Duration, Utc};
+use log::{debug, info, warn};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    sqlx_error_to_conn_err, sqlx_error_to_query_err, ActiveModelTrait, ColumnTrait,
+    DatabaseConnection, DbErr, EntityTrait, Iterable, QueryFilter, Set, TransactionTrait,
+};
+use sqlx::{Connection, PgConnection};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// First key of the advisory locks taken for jobs, keeping them apart from
+/// any other use of advisory locks in the database.
+const LOCK_NAMESPACE: i32 = 0x0B0B;
+
+/// Second key of the advisory lock a job runs under.
+fn lock_key(job: Job) -> i32 {
+    match job {
+        Job::MarkOverdue => 1,
+        Job::ExpireHolds => 2,
+        Job::PurgeTokens => 3,
+        Job::SendReminders => 4,
+        Job::DeliverNotifications => 5,
+    }
+}
+
+fn interval(config: &Config, job: Job) -> std::time::Duration {
+    let seconds = match job {
+        Job::MarkOverdue => config.scheduler.overdue_interval_seconds,
+        Job::ExpireHolds => config.scheduler.holds_interval_seconds,
+        Job::PurgeTokens => config.scheduler.tokens_interval_seconds,
+        Job::SendReminders => config.scheduler.reminders_interval_seconds,
+        Job::DeliverNotifications => config.scheduler.notifications_interval_seconds,
+    };
+    std::time::Duration::from_secs(seconds)
+}
+
+/// Take the job's lock for as long as `connection` stays open, unless another
+/// instance holds it.
+async fn try_lock(connection: &mut PgConnection, job: Job) -> Result<bool, DbErr> {
+    sqlx::query_scalar("SELECT pg_try_advisory_lock($1, $2)")
+        .bind(LOCK_NAMESPACE)
+        .bind(lock_key(job))
+        .fetch_one(connection)
+        .await
+        .map_err(sqlx_error_to_query_err)
+}
+
+/// Runs the periodic maintenance jobs, recording each run in `job_runs`.
+///
+/// Every instance schedules every job, but a run only goes ahead in the
+/// instance holding the job's advisory lock, so the others skip it.
+#[derive(Clone)]
+pub struct Scheduler {
+    connection: DatabaseConnection,
+    config: Arc<Config>,
+    notifier: Arc<Notifier>,
+}
+
+impl Scheduler {
+    pub fn new(
+        connection: DatabaseConnection,
+        config: Arc<Config>,
+        notifier: Arc<Notifier>,
+    ) -> Self {
+        Scheduler {
+            connection,
+            config,
+            notifier,
+        }
+    }
+
+    /// Run each job now and then again after every interval, on the current runtime.
+    pub fn start(&self) {
+        for job in Job::iter() {
+            let scheduler = self.clone();
+            let period = interval(&self.config, job);
+            rt::spawn(async move {
+                loop {
+                    match scheduler.run(job, JobTrigger::Schedule, None).await {
+                        Ok(Some(_)) => {}
+                        Ok(None) => debug!("Job {:?} is running elsewhere, skipped", job),
+                        Err(err) => warn!("Unable to run job {:?}: {}", job, err),
+                    }
+                    rt::time::sleep(period).await;
+                }
+            });
+        }
+        info!("Scheduler started");
+    }
+
+    /// Run a job unless it's already running, here or in another instance.
+    ///
+    /// Returns the finished run, or `None` when the job was skipped. A job
+    /// that fails still returns its run, marked as failed.
+    pub async fn run(
+        &self,
+        job: Job,
+        trigger: JobTrigger,
+        triggered_by: Option<Uuid>,
+    ) -> Result<Option<ModelJobRun>, DbErr> {
+        // The lock lives as long as this connection, which is kept out of the
+        // pool and closed after the run, so the lock can't outlive the run
+        let mut lock = self
+            .connection
+            .get_postgres_connection_pool()
+            .acquire()
+            .await
+            .map_err(sqlx_error_to_conn_err)?
+            .detach();
+        if !try_lock(&mut lock, job).await? {
+            lock.close().await.map_err(sqlx_error_to_conn_err)?;
+            return Ok(None);
+        }
+        // Whoever left a run of this job going no longer holds the lock, so
+        // it stopped with the run unfinished
+        EntityJobRun::update_many()
+            .col_expr(ColumnJobRun::Status, Expr::value(JobStatus::Failed))
+            .col_expr(
+                ColumnJobRun::Error,
+                Expr::value("Interrupted before finishing."),
+            )
+            .col_expr(
+                ColumnJobRun::FinishedAt,
+                Expr::value(Utc::now().naive_utc()),
+            )
+            .filter(ColumnJobRun::Job.eq(job))
+            .filter(ColumnJobRun::Status.eq(JobStatus::Running))
+            .exec(&self.connection)
+            .await?;
+
+        let run = ActiveModelJobRun {
+            id: Set(Uuid::new_v4()),
+            job: Set(job),
+            trigger: Set(trigger),
+            status: Set(JobStatus::Running),
+            affected: Set(None),
+            error: Set(None),
+            triggered_by: Set(triggered_by),
+            started_at: Set(Utc::now().naive_utc()),
+            finished_at: Set(None),
+        }
+        .insert(&self.connection)
+        .await?;
+
+        let result = self.execute(job).await;
+        let mut model: ActiveModelJobRun = run.into();
+        match result {
+            Ok(affected) => {
+                model.status = Set(JobStatus::Succeeded);
+                model.affected = Set(Some(affected as i64));
+            }
+            Err(err) => {
+                warn!("Job {:?} failed: {:?}", job, err);
+                model.status = Set(JobStatus::Failed);
+                model.error = Set(Some(format!("{:?}", err)));
+            }
+        }
+        model.finished_at = Set(Some(Utc::now().naive_utc()));
+        let run = model.update(&self.connection).await?;
+        lock.close().await.map_err(sqlx_error_to_conn_err)?;
+        Ok(Some(run))
+    }
+
+    async fn execute(&self, job: Job) -> Result<u64, CirculationError> {
+        let loans = &self.config.loans;
+        match job {
+            Job::MarkOverdue => mark_overdue(&self.connection, loans).await,
+            Job::ExpireHolds => expire_pickups(&self.connection, loans, None).await,
+            Job::PurgeTokens => Ok(self.purge_tokens().await?),
+            Job::SendReminders => Ok(self.send_reminders().await?),
+            Job::DeliverNotifications => Ok(self
+                .notifier
+                .deliver_pending(&self.connection, &self.config.notifications)
+                .await?),
+        }
+    }
+
+    async fn purge_tokens(&self) -> Result<u64, DbErr> {
+        let now = Utc::now().naive_utc();
+        let connection = &self.connection;
+        let purged = EntityRefreshToken::delete_many()
+            .filter(ColumnRefreshToken::ExpiresAt.lt(now))
+            .exec(connection)
+            .await?
+            .rows_affected
+            + EntityRevokedToken::delete_many()
+                .filter(ColumnRevokedToken::ExpiresAt.lt(now))
+                .exec(connection)
+                .await?
+                .rows_affected
+            + EntityVerificationToken::delete_many()
+                .filter(ColumnVerificationToken::ExpiresAt.lt(now))
+                .exec(connection)
+                .await?
+                .rows_affected
+            + EntityPasswordResetToken::delete_many()
+                .filter(ColumnPasswordResetToken::ExpiresAt.lt(now))
+                .exec(connection)
+                .await?
+                .rows_affected;
+        Ok(purged)
+    }
+
+    /// Notify borrowers whose loans are due within the configured days, once
+    /// per due date.
+    async fn send_reminders(&self) -> Result<u64, DbErr> {
+        let now = Utc::now().naive_utc();
+        let horizon = now + Duration::days(self.config.scheduler.reminder_days_before);
+        let loans = EntityReservation::find()
+            .filter(ColumnReservation::Status.eq(ReservationStatus::CheckedOut))
+            .filter(ColumnReservation::DueDate.gte(now))
+            .filter(ColumnReservation::DueDate.lte(horizon))
+            .filter(ColumnReservation::RemindedAt.is_null())
+            .all(&self.connection)
+            .await?;
+        let mut reminded = 0;
+        for loan in loans {
+            let txn = self.connection.begin().await?;
+            notify_reservation(&txn, NotificationKind::DueSoon, &loan, None).await?;
+            let mut model: ActiveModelReservation = loan.into();
+            model.reminded_at = Set(Some(now));
+            model.update(&txn).await?;
+            txn.commit().await?;
+            reminded += 1;
+        }
+        Ok(reminded)
+    }
+}
@@ -0,0 +1,85 @@
+use sea_orm::{
+    ConnectionTrait, DatabaseConnection, DbBackend, DbErr, FromQueryResult, Statement, Value,
+};
+use serde::Serialize;
+use uuid::Uuid;
+
+/// A book matching a catalog search, with `<mark>`ed matches in title and author.
+#[derive(Debug, FromQueryResult, Serialize)]
+pub struct BookHit {
+    pub id: Uuid,
+    pub title: String,
+    pub author: String,
+    pub year_of_publication: i32,
+    pub available: bool,
+    pub rank: f32,
+    pub title_highlight: String,
+    pub author_highlight: String,
+}
+
+/// Turn free text into a `tsquery` where every word must match, each as a prefix.
+///
+/// Only letters and digits survive, so user input can't inject `tsquery` operators.
+pub fn prefix_query(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("{}:*", term))
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" & "))
+    }
+}
+
+/// Rank books whose title or author match `query` (see [`prefix_query`]),
+/// returning one page of hits and the total number of matches.
+pub async fn search_books(
+    connection: &DatabaseConnection,
+    query: &str,
+    limit: u64,
+    offset: u64,
+) -> Result<(Vec<BookHit>, u64), DbErr> {
+    let total = connection
+        .query_one(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"
+            SELECT count(*) AS total FROM books
+            WHERE search_vector @@ to_tsquery('public.book_search', $1)
+            "#,
+            [query.into()],
+        ))
+        .await?
+        .map(|row| row.try_get::<i64>("", "total"))
+        .transpose()?
+        .unwrap_or_default();
+
+    let hits = BookHit::find_by_statement(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        SELECT b.id, b.title, b.author, b.year_of_publication,
+            EXISTS (
+                SELECT 1 FROM copies c WHERE c.book_id = b.id AND c.status = 'available'
+            ) AS available,
+            ts_rank_cd(b.search_vector, q.query) AS rank,
+            ts_headline('public.book_search', b.title, q.query,
+                'StartSel=<mark>, StopSel=</mark>, HighlightAll=true') AS title_highlight,
+            ts_headline('public.book_search', b.author, q.query,
+                'StartSel=<mark>, StopSel=</mark>, HighlightAll=true') AS author_highlight
+        FROM books b, to_tsquery('public.book_search', $1) AS q(query)
+        WHERE b.search_vector @@ q.query
+        ORDER BY rank DESC, b.title, b.id
+        LIMIT $2 OFFSET $3
+        "#,
+        [
+            query.into(),
+            Value::BigInt(Some(limit as i64)),
+            Value::BigInt(Some(offset as i64)),
+        ],
+    ))
+    .all(connection)
+    .await?;
+
+    Ok((hits, total as u64))
+}
@@ -0,0 +1,261 @@
+use crate::config::JwtConfig;
+use crate::models::refresh_tokens::{
+    ActiveModel as ActiveModelRefreshToken, Column as ColumnRefreshToken,
+    Entity as EntityRefreshToken, Model as ModelRefreshToken,
+};
+use crate::models::revoked_tokens::{
+    ActiveModel as ActiveModelRevokedToken, Column as ColumnRevokedToken,
+    Entity as EntityRevokedToken,
+};
+use crate::models::users::{Entity as EntityUser, Model as ModelUser};
+use crate::services::jwt_keys::JwtKeys;
+use crate::utils::token::{encode_token, generate_opaque_token, hash_opaque_token, TokenClaims};
+use chrono::{DateTime, Utc};
+use log::warn;
+use sea_orm::{
+    sea_query::{Expr, OnConflict},
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait,
+    QueryFilter, QuerySelect, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Access and refresh token handed to the client after login or refresh.
+#[derive(Serialize, Deserialize)]
+pub struct TokenPair {
+    pub token: String,
+    pub refresh_token: String,
+}
+
+#[derive(Debug)]
+pub enum RefreshError {
+    Invalid,
+    Expired,
+    /// A token that was already rotated has been presented again; the whole
+    /// family is revoked since either the client or an attacker holds a copy.
+    Reused,
+    Inactive,
+    Db(DbErr),
+}
+
+impl From<DbErr> for RefreshError {
+    fn from(err: DbErr) -> Self {
+        RefreshError::Db(err)
+    }
+}
+
+async fn insert_refresh_token<C: ConnectionTrait>(
+    connection: &C,
+    config: &JwtConfig,
+    user_id: Uuid,
+    family_id: Uuid,
+    mfa: bool,
+) -> Result<(ModelRefreshToken, String), DbErr> {
+    let token = generate_opaque_token();
+    let model = ActiveModelRefreshToken {
+        id: Set(Uuid::new_v4()),
+        user_id: Set(user_id),
+        family_id: Set(family_id),
+        token_hash: Set(hash_opaque_token(token.as_str())),
+        expires_at: Set((Utc::now() + config.refresh_token_lifetime()).naive_utc()),
+        revoked_at: Set(None),
+        replaced_by: Set(None),
+        mfa: Set(mfa),
+        created_at: Set(Some(Utc::now().naive_utc())),
+    }
+    .insert(connection)
+    .await?;
+    Ok((model, token))
+}
+
+/// Start a new refresh token family for a freshly authenticated user.
+pub async fn issue_session(
+    connection: &DatabaseConnection,
+    keys: &JwtKeys,
+    config: &JwtConfig,
+    user: &ModelUser,
+    mfa: bool,
+) -> Result<TokenPair, DbErr> {
+    let (_, refresh_token) =
+        insert_refresh_token(connection, config, user.id, Uuid::new_v4(), mfa).await?;
+    Ok(TokenPair {
+        token: encode_token(keys, config, user.id, user.role, mfa),
+        refresh_token,
+    })
+}
+
+/// Exchange a refresh token for a new pair, revoking the presented token.
+pub async fn rotate_session(
+    connection: &DatabaseConnection,
+    keys: &JwtKeys,
+    config: &JwtConfig,
+    refresh_token: &str,
+) -> Result<TokenPair, RefreshError> {
+    let txn = connection.begin().await?;
+    let current = EntityRefreshToken::find()
+        .filter(ColumnRefreshToken::TokenHash.eq(hash_opaque_token(refresh_token)))
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+        .ok_or(RefreshError::Invalid)?;
+
+    if current.revoked_at.is_some() {
+        warn!(
+            "Refresh token reuse detected, revoking family {} of user {}",
+            current.family_id, current.user_id
+        );
+        revoke_family(&txn, current.family_id).await?;
+        txn.commit().await?;
+        return Err(RefreshError::Reused);
+    }
+    if current.expires_at <= Utc::now().naive_utc() {
+        return Err(RefreshError::Expired);
+    }
+    let user = EntityUser::find_by_id(current.user_id)
+        .one(&txn)
+        .await?
+        .ok_or(RefreshError::Invalid)?;
+    if !user.active {
+        revoke_family(&txn, current.family_id).await?;
+        txn.commit().await?;
+        return Err(RefreshError::Inactive);
+    }
+
+    let mfa = current.mfa;
+    let (next, refresh_token) =
+        insert_refresh_token(&txn, config, user.id, current.family_id, mfa).await?;
+    let mut model: ActiveModelRefreshToken = current.into();
+    model.revoked_at = Set(Some(Utc::now().naive_utc()));
+    model.replaced_by = Set(Some(next.id));
+    model.update(&txn).await?;
+    txn.commit().await?;
+
+    Ok(TokenPair {
+        token: encode_token(keys, config, user.id, user.role, mfa),
+        refresh_token,
+    })
+}
+
+async fn revoke_family<C: ConnectionTrait>(connection: &C, family_id: Uuid) -> Result<(), DbErr> {
+    EntityRefreshToken::update_many()
+        .col_expr(
+            ColumnRefreshToken::RevokedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(ColumnRefreshToken::FamilyId.eq(family_id))
+        .filter(ColumnRefreshToken::RevokedAt.is_null())
+        .exec(connection)
+        .await?;
+    Ok(())
+}
+
+/// Revoke every refresh token descending from the same login as `refresh_token`.
+pub async fn revoke_session(
+    connection: &DatabaseConnection,
+    refresh_token: &str,
+) -> Result<bool, DbErr> {
+    match EntityRefreshToken::find()
+        .filter(ColumnRefreshToken::TokenHash.eq(hash_opaque_token(refresh_token)))
+        .one(connection)
+        .await?
+    {
+        Some(current) => {
+            revoke_family(connection, current.family_id).await?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Revoke every outstanding refresh token of the user, e.g. after a password reset.
+pub async fn revoke_all_sessions<C: ConnectionTrait>(
+    connection: &C,
+    user_id: Uuid,
+) -> Result<(), DbErr> {
+    EntityRefreshToken::update_many()
+        .col_expr(
+            ColumnRefreshToken::RevokedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(ColumnRefreshToken::UserId.eq(user_id))
+        .filter(ColumnRefreshToken::RevokedAt.is_null())
+        .exec(connection)
+        .await?;
+    Ok(())
+}
+
+/// Put an access token on the revocation list until it expires on its own.
+pub async fn revoke_access_token(
+    connection: &DatabaseConnection,
+    claims: &TokenClaims,
+) -> Result<(), DbErr> {
+    let expires_at = DateTime::<Utc>::from_timestamp(claims.exp as i64, 0)
+        .map(|date| date.naive_utc())
+        .unwrap_or_else(|| Utc::now().naive_utc());
+    let model = ActiveModelRevokedToken {
+        jti: Set(claims.jti),
+        expires_at: Set(expires_at),
+        created_at: Set(Some(Utc::now().naive_utc())),
+    };
+    match EntityRevokedToken::insert(model)
+        .on_conflict(
+            OnConflict::column(ColumnRevokedToken::Jti)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec(connection)
+        .await
+    {
+        // Logging out twice with the same token is not an error
+        Ok(_) | Err(DbErr::RecordNotInserted) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+#[derive(Debug)]
+pub enum SessionError {
+    Revoked,
+    UnknownUser,
+    Inactive(&'static str),
+    /// The password or the role changed after the token was issued.
+    Expired,
+    Db(DbErr),
+}
+
+impl From<DbErr> for SessionError {
+    fn from(err: DbErr) -> Self {
+        SessionError::Db(err)
+    }
+}
+
+/// Checks that a correctly signed access token is still honoured by the server.
+pub async fn validate_access_token(
+    connection: &DatabaseConnection,
+    claims: &TokenClaims,
+) -> Result<(), SessionError> {
+    if EntityRevokedToken::find_by_id(claims.jti)
+        .one(connection)
+        .await?
+        .is_some()
+    {
+        return Err(SessionError::Revoked);
+    }
+    let user_id = Uuid::parse_str(claims.sub.as_str()).map_err(|_| SessionError::UnknownUser)?;
+    let user = EntityUser::find_by_id(user_id)
+        .one(connection)
+        .await?
+        .ok_or(SessionError::UnknownUser)?;
+    if let Some(reason) = user.inactive_reason() {
+        return Err(SessionError::Inactive(reason));
+    }
+    // Tokens carry the role they were issued with, so a demotion must end them
+    if claims.role != user.role {
+        return Err(SessionError::Expired);
+    }
+    if let Some(changed_at) = user.password_changed_at {
+        if (claims.iat as i64) < changed_at.and_utc().timestamp() {
+            return Err(SessionError::Expired);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,142 @@
+//! Fixtures for tests that need a database.
+//!
+//! They run against `TEST_DATABASE_URL`, which must point at a database the
+//! app has already migrated. Tests using them are `#[ignore]`d, run them with
+//! `cargo test -- --ignored`.
+
+use sea_orm::{ConnectionTrait, Database, DatabaseConnection, DbBackend, Statement};
+use uuid::Uuid;
+
+/// A book with its own copies and borrowers, deleted again when dropped.
+pub struct Fixture {
+    pub db: DatabaseConnection,
+    pub book_id: Uuid,
+    pub copies: Vec<Uuid>,
+    users: Vec<Uuid>,
+    url: String,
+    /// Tags the fixture's connections in `pg_stat_activity`.
+    application_name: String,
+}
+
+impl Fixture {
+    /// Create a book with `copies` copies on the shelf.
+    pub async fn new(copies: usize) -> Fixture {
+        let url = std::env::var("TEST_DATABASE_URL")
+            .expect("TEST_DATABASE_URL must point at a migrated database");
+        let application_name = format!("fixture-{}", Uuid::new_v4());
+        let separator = if url.contains('?') { '&' } else { '?' };
+        let db = Database::connect(format!(
+            "{}{}application_name={}",
+            url, separator, application_name
+        ))
+        .await
+        .expect("Unable to connect to the test database");
+        let book_id = insert(
+            &db,
+            "INSERT INTO books (title, author, year_of_publication) \
+             VALUES ('Test book', 'Test author', 2000) RETURNING id",
+            Vec::new(),
+        )
+        .await;
+        let mut fixture = Fixture {
+            db,
+            book_id,
+            copies: Vec::new(),
+            users: Vec::new(),
+            url,
+            application_name,
+        };
+        for _ in 0..copies {
+            fixture.add_copy().await;
+        }
+        fixture
+    }
+
+    pub async fn add_copy(&mut self) -> Uuid {
+        let copy_id = insert(
+            &self.db,
+            "INSERT INTO copies (book_id, barcode) VALUES ($1, $2) RETURNING id",
+            vec![self.book_id.into(), Uuid::new_v4().to_string().into()],
+        )
+        .await;
+        self.copies.push(copy_id);
+        copy_id
+    }
+
+    /// A new active member.
+    pub async fn add_user(&mut self) -> Uuid {
+        let user_id = insert(
+            &self.db,
+            "INSERT INTO users (email, password) VALUES ($1, '') RETURNING id",
+            vec![format!("{}@test.invalid", Uuid::new_v4()).into()],
+        )
+        .await;
+        self.users.push(user_id);
+        user_id
+    }
+}
+
+impl Drop for Fixture {
+    /// Delete the fixture's rows, also when the test panicked.
+    ///
+    /// `Drop` can't await, so the deletes run on a runtime of their own, over
+    /// a connection of their own. The fixture's connections are ended first:
+    /// a transaction the test dropped only rolls back once the test's runtime
+    /// runs again, which it can't while waiting here.
+    fn drop(&mut self) {
+        let url = self.url.clone();
+        let application_name = self.application_name.clone();
+        let book_id = self.book_id;
+        let users = std::mem::take(&mut self.users);
+        let cleanup = std::thread::spawn(move || {
+            actix_web::rt::System::new().block_on(async move {
+                let db = Database::connect(&url)
+                    .await
+                    .expect("Unable to connect to the test database");
+                execute(
+                    &db,
+                    "SELECT pg_terminate_backend(pid) FROM pg_stat_activity \
+                     WHERE application_name = $1",
+                    vec![application_name.into()],
+                )
+                .await;
+                execute(
+                    &db,
+                    "DELETE FROM reservations WHERE book_id = $1",
+                    vec![book_id.into()],
+                )
+                .await;
+                execute(&db, "DELETE FROM books WHERE id = $1", vec![book_id.into()]).await;
+                for user_id in users {
+                    execute(&db, "DELETE FROM users WHERE id = $1", vec![user_id.into()]).await;
+                }
+            })
+        });
+        if cleanup.join().is_err() {
+            eprintln!("Unable to delete the rows of fixture book {}", book_id);
+        }
+    }
+}
+
+async fn execute(db: &DatabaseConnection, sql: &str, values: Vec<sea_orm::Value>) {
+    db.execute(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        sql,
+        values,
+    ))
+    .await
+    .expect("Unable to run fixture statement");
+}
+
+async fn insert(db: &DatabaseConnection, sql: &str, values: Vec<sea_orm::Value>) -> Uuid {
+    db.query_one(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        sql,
+        values,
+    ))
+    .await
+    .expect("Unable to insert fixture")
+    .expect("Insert returned no row")
+    .try_get("", "id")
+    .expect("Insert returned no id")
+}
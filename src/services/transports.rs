@@ -0,0 +1,208 @@
+use crate::models::notifications::Model as ModelNotification;
+use crate::services::mailer::{Email, Mailer};
+use crate::utils::tls::upgrade;
+use async_trait::async_trait;
+use ring::hmac;
+use sea_orm::ActiveEnum;
+use serde_json::json;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use url::{Position, Url};
+
+/// Delivery backend of one notification channel.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    async fn deliver(&self, notification: &ModelNotification) -> io::Result<()>;
+}
+
+/// Sends notifications as emails through the configured mailer, be it SMTP,
+/// files or the log.
+pub struct EmailTransport {
+    mailer: Arc<dyn Mailer>,
+}
+
+impl EmailTransport {
+    pub fn new(mailer: Arc<dyn Mailer>) -> Self {
+        EmailTransport { mailer }
+    }
+}
+
+#[async_trait]
+impl Transport for EmailTransport {
+    async fn deliver(&self, notification: &ModelNotification) -> io::Result<()> {
+        self.mailer
+            .send(&Email {
+                to: notification.recipient.to_owned(),
+                subject: notification.subject.to_owned(),
+                body: notification.body.to_owned(),
+            })
+            .await
+    }
+}
+
+/// Posts notifications as JSON to a fixed URL. With a secret, the body's
+/// HMAC-SHA256 goes in `X-BookBorrow-Signature` so receivers can check it.
+pub struct WebhookTransport {
+    url: Url,
+    secret: Option<String>,
+    timeout: Duration,
+}
+
+impl WebhookTransport {
+    pub fn new(url: Url, secret: Option<String>, timeout: Duration) -> Self {
+        WebhookTransport {
+            url,
+            secret,
+            timeout,
+        }
+    }
+
+    fn request_head(&self, event: &str, body: &[u8]) -> String {
+        let mut head = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: BookBorrow\r\n\
+             Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\
+             X-BookBorrow-Event: {}\r\n",
+            &self.url[Position::BeforePath..Position::AfterQuery],
+            &self.url[Position::BeforeHost..Position::AfterPort],
+            body.len(),
+            event
+        );
+        if let Some(secret) = &self.secret {
+            let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+            let signature = hex::encode(hmac::sign(&key, body).as_ref());
+            head.push_str(format!("X-BookBorrow-Signature: sha256={}\r\n", signature).as_str());
+        }
+        head.push_str("\r\n");
+        head
+    }
+
+    /// Send the request and return the response status.
+    async fn post(&self, event: &str, body: &[u8]) -> io::Result<u16> {
+        let host = self
+            .url
+            .host_str()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "webhook URL has no host"))?
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        let port = self.url.port_or_known_default().unwrap_or(80);
+        let stream = TcpStream::connect((host, port)).await?;
+        let head = self.request_head(event, body);
+        if self.url.scheme() == "https" {
+            exchange(upgrade(stream, host).await?, head, body).await
+        } else {
+            exchange(stream, head, body).await
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for WebhookTransport {
+    async fn deliver(&self, notification: &ModelNotification) -> io::Result<()> {
+        let body = json!({
+            "id": notification.id,
+            "kind": notification.kind,
+            "user_id": notification.user_id,
+            "recipient": notification.recipient,
+            "subject": notification.subject,
+            "body": notification.body,
+            "data": notification.data,
+            "created_at": notification.created_at,
+        })
+        .to_string();
+        let event = notification.kind.to_value();
+        let status = tokio::time::timeout(self.timeout, self.post(&event, body.as_bytes()))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "webhook timed out"))??;
+        if (200..300).contains(&status) {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!("webhook answered {}", status)))
+        }
+    }
+}
+
+async fn exchange<S>(mut stream: S, head: String, body: &[u8]) -> io::Result<u16>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body).await?;
+    stream.flush().await?;
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line).await?;
+    status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid HTTP status line {:?}", status_line.trim_end()),
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    fn webhook(url: &str, secret: Option<&str>) -> WebhookTransport {
+        WebhookTransport::new(
+            Url::parse(url).unwrap(),
+            secret.map(str::to_string),
+            Duration::from_secs(5),
+        )
+    }
+
+    #[test]
+    fn request_head_targets_the_path_and_host() {
+        let head = webhook("https://hooks.example.com:8443/in?team=1", None)
+            .request_head("hold_ready", br#"{"id":1}"#);
+        assert_eq!(
+            head,
+            "POST /in?team=1 HTTP/1.1\r\nHost: hooks.example.com:8443\r\n\
+             User-Agent: BookBorrow\r\nContent-Type: application/json\r\n\
+             Content-Length: 8\r\nConnection: close\r\nX-BookBorrow-Event: hold_ready\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn request_head_signs_the_body_with_the_secret() {
+        let head = webhook("http://hooks.example.com/", Some("shh"))
+            .request_head("overdue", br#"{"id":1}"#);
+        assert!(head.starts_with("POST / HTTP/1.1\r\nHost: hooks.example.com\r\n"));
+        assert!(head.ends_with(
+            "X-BookBorrow-Signature: \
+             sha256=541b22bf4cc4179fd220981b04548042505f3a6edb15eb44d890e05eeba33d7c\r\n\r\n"
+        ));
+    }
+
+    #[actix_web::test]
+    async fn exchange_sends_the_request_and_reads_the_status() {
+        let (client, mut peer) = tokio::io::duplex(1024);
+        let head = "POST / HTTP/1.1\r\n\r\n";
+        let server = async move {
+            let mut request = vec![0; head.len() + 4];
+            peer.read_exact(&mut request).await.unwrap();
+            peer.write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                .await
+                .unwrap();
+            request
+        };
+        let (status, request) = futures::join!(exchange(client, head.to_string(), b"body"), server);
+        assert_eq!(status.unwrap(), 204);
+        assert_eq!(request, b"POST / HTTP/1.1\r\n\r\nbody");
+    }
+
+    #[actix_web::test]
+    async fn exchange_refuses_a_garbled_status_line() {
+        let (client, mut peer) = tokio::io::duplex(1024);
+        peer.write_all(b"garbage\r\n").await.unwrap();
+        let err = exchange(client, String::new(), b"").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::config::Config;
+use crate::models::email_verification_tokens::{
+    ActiveModel as ActiveModelVerificationToken, Column as ColumnVerificationToken,
+    Entity as EntityVerificationToken,
+};
+use crate::models::users::{
+    ActiveModel as ActiveModelUser, Column as ColumnUser, Entity as EntityUser, Model as ModelUser,
+};
+use crate::services::mailer::{Email, Mailer};
+use crate::utils::token::{generate_opaque_token, hash_opaque_token};
+use chrono::Utc;
+use log::warn;
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub enum VerificationError {
+    Invalid,
+    Expired,
+    /// The account was suspended; confirming its email mustn't reactivate it.
+    Suspended,
+    Db(DbErr),
+}
+
+impl From<DbErr> for VerificationError {
+    fn from(err: DbErr) -> Self {
+        VerificationError::Db(err)
+    }
+}
+
+/// Store a new verification token for the user and email its link.
+pub async fn send_verification(
+    connection: &DatabaseConnection,
+    config: &Config,
+    mailer: &dyn Mailer,
+    user: &ModelUser,
+) -> Result<(), DbErr> {
+    let token = generate_opaque_token();
+    ActiveModelVerificationToken {
+        id: Set(Uuid::new_v4()),
+        user_id: Set(user.id),
+        token_hash: Set(hash_opaque_token(token.as_str())),
+        expires_at: Set((Utc::now() + config.accounts.email_verification_lifetime()).naive_utc()),
+        used_at: Set(None),
+        created_at: Set(Some(Utc::now().naive_utc())),
+    }
+    .insert(connection)
+    .await?;
+
+    let email = Email {
+        to: user.email.to_owned(),
+        subject: "Confirm your BookBorrow account".to_string(),
+        body: format!(
+            "Welcome to BookBorrow!\n\nFollow this link to activate your account:\n{}",
+            config.app_link(format!("/register/verify?token={}", token).as_str())
+        ),
+    };
+    if let Err(err) = mailer.send(&email).await {
+        // The account exists either way; the user can ask for a new link
+        warn!(
+            "Unable to send verification email to {}: {}",
+            user.email, err
+        );
+    }
+    Ok(())
+}
+
+/// Email a new verification link if the address belongs to an account still
+/// waiting for its confirmation, at most once per resend interval.
+///
+/// Other addresses are silently ignored so the endpoint can't be used to
+/// probe which emails are registered.
+pub async fn resend_verification(
+    connection: &DatabaseConnection,
+    config: &Config,
+    mailer: &dyn Mailer,
+    email: &str,
+) -> Result<(), DbErr> {
+    let user = match EntityUser::find()
+        .filter(ColumnUser::Email.eq(email))
+        .filter(ColumnUser::Active.eq(false))
+        .filter(ColumnUser::SuspendedAt.is_null())
+        .one(connection)
+        .await?
+    {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    let last_sent = EntityVerificationToken::find()
+        .filter(ColumnVerificationToken::UserId.eq(user.id))
+        .order_by_desc(ColumnVerificationToken::CreatedAt)
+        .one(connection)
+        .await?
+        .and_then(|verification| verification.created_at);
+    let now = Utc::now();
+    if let Some(last_sent) = last_sent {
+        if last_sent > (now - config.accounts.verification_resend_interval()).naive_utc() {
+            return Ok(());
+        }
+    }
+
+    // Only the most recent link stays usable
+    EntityVerificationToken::update_many()
+        .col_expr(
+            ColumnVerificationToken::UsedAt,
+            Expr::value(now.naive_utc()),
+        )
+        .filter(ColumnVerificationToken::UserId.eq(user.id))
+        .filter(ColumnVerificationToken::UsedAt.is_null())
+        .exec(connection)
+        .await?;
+    send_verification(connection, config, mailer, &user).await
+}
+
+/// Consume a verification token and activate its user.
+pub async fn verify_email(
+    connection: &DatabaseConnection,
+    token: &str,
+) -> Result<ModelUser, VerificationError> {
+    let txn = connection.begin().await?;
+    let verification = EntityVerificationToken::find()
+        .filter(ColumnVerificationToken::TokenHash.eq(hash_opaque_token(token)))
+        .filter(ColumnVerificationToken::UsedAt.is_null())
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+        .ok_or(VerificationError::Invalid)?;
+    if verification.expires_at <= Utc::now().naive_utc() {
+        return Err(VerificationError::Expired);
+    }
+    let user = EntityUser::find_by_id(verification.user_id)
+        .one(&txn)
+        .await?
+        .ok_or(VerificationError::Invalid)?;
+    if user.suspended_at.is_some() {
+        return Err(VerificationError::Suspended);
+    }
+
+    let mut model: ActiveModelVerificationToken = verification.into();
+    model.used_at = Set(Some(Utc::now().naive_utc()));
+    model.update(&txn).await?;
+
+    let mut model: ActiveModelUser = user.into();
+    model.active = Set(true);
+    model.updated_at = Set(Some(Utc::now().naive_utc()));
+    let user = model.update(&txn).await?;
+    txn.commit().await?;
+    Ok(user)
+}
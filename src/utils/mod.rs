@@ -1,2 +1,5 @@
-pub mod default;
+pub mod pagination;
+pub mod password;
+pub mod tls;
 pub mod token;
+pub mod validation;
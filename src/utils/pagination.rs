@@ -0,0 +1,411 @@
+use crate::errors::ApiError;
+use actix_web::{http::header, HttpRequest, HttpResponse};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::NaiveDateTime;
+use sea_orm::sea_query::{LikeExpr, Order};
+use sea_orm::{
+    ColumnTrait, ColumnType, Condition, ConnectionTrait, EntityTrait, IdenStatic, Iterable,
+    ModelTrait, PaginatorTrait, PrimaryKeyToColumn, QueryFilter, QueryOrder, QuerySelect, Select,
+    Value,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+use std::borrow::Cow;
+use uuid::Uuid;
+use validator::{ValidationError, ValidationErrors};
+
+pub const DEFAULT_PER_PAGE: u64 = 20;
+pub const MAX_PER_PAGE: u64 = 100;
+
+/// Paging and sorting parameters shared by collection endpoints.
+#[derive(Debug, Default, Deserialize)]
+pub struct PageParams {
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
+    /// Opaque `next_cursor` of a previous page, used instead of `page`.
+    pub cursor: Option<String>,
+    /// Comma separated column names, prefixed with `-` for descending order.
+    pub sort: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PageMeta {
+    pub total: u64,
+    pub per_page: u64,
+    /// Only set for offset pagination.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_pages: Option<u64>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub meta: PageMeta,
+}
+
+impl<T> Page<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            data: self.data.into_iter().map(f).collect(),
+            meta: self.meta,
+        }
+    }
+}
+
+/// Position after the last row of a page, bound to the sort it was issued for.
+#[derive(Serialize, Deserialize)]
+struct Cursor {
+    sort: String,
+    after: Vec<Json>,
+}
+
+fn field_error(errors: &mut ValidationErrors, field: &'static str, message: String) {
+    let mut err = ValidationError::new("invalid");
+    err.message = Some(Cow::from(message));
+    errors.add(field, err);
+}
+
+/// `ILIKE` pattern matching values that contain `text` literally, using the
+/// default backslash escape.
+pub fn contains(text: &str) -> LikeExpr {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    LikeExpr::new(format!("%{}%", escaped))
+}
+
+fn to_json(value: Value) -> Result<Json, ApiError> {
+    let json = match value {
+        Value::Bool(v) => v.into(),
+        Value::Int(v) => v.into(),
+        Value::BigInt(v) => v.into(),
+        Value::String(v) => v.map(|v| *v).into(),
+        Value::ChronoDateTime(v) => v
+            .map(|v| Json::String(v.format("%Y-%m-%dT%H:%M:%S%.f").to_string()))
+            .unwrap_or_default(),
+        Value::Uuid(v) => v.map(|v| v.to_string()).into(),
+        other => {
+            return Err(ApiError::Internal(format!(
+                "Unsupported cursor value: {:?}",
+                other
+            )))
+        }
+    };
+    Ok(json)
+}
+
+/// Read back a cursor value for a column; `Some(None)` stands for NULL.
+fn from_json(column_type: &ColumnType, json: &Json) -> Option<Option<Value>> {
+    if json.is_null() {
+        return Some(None);
+    }
+    let value = match column_type {
+        ColumnType::Boolean => json.as_bool().map(Value::from),
+        ColumnType::Integer => json
+            .as_i64()
+            .and_then(|v| i32::try_from(v).ok())
+            .map(Value::from),
+        ColumnType::BigInteger => json.as_i64().map(Value::from),
+        ColumnType::String(_) | ColumnType::Text => {
+            json.as_str().map(|v| Value::from(v.to_string()))
+        }
+        ColumnType::DateTime | ColumnType::Timestamp => json
+            .as_str()
+            .and_then(|v| NaiveDateTime::parse_from_str(v, "%Y-%m-%dT%H:%M:%S%.f").ok())
+            .map(Value::from),
+        ColumnType::Uuid => json
+            .as_str()
+            .and_then(|v| Uuid::parse_str(v).ok())
+            .map(Value::from),
+        _ => None,
+    };
+    value.map(Some)
+}
+
+/// Rows sorting after `value` in `order`, following Postgres' default of
+/// NULLS LAST when ascending and NULLS FIRST when descending.
+fn after<C: ColumnTrait>(column: C, order: &Order, value: &Option<Value>) -> Option<Condition> {
+    match (order, value) {
+        (Order::Desc, Some(value)) => Some(Condition::all().add(column.lt(value.clone()))),
+        (Order::Desc, None) => Some(Condition::all().add(column.is_not_null())),
+        (_, Some(value)) => Some(
+            Condition::any()
+                .add(column.gt(value.clone()))
+                .add(column.is_null()),
+        ),
+        (_, None) => None,
+    }
+}
+
+fn equals<C: ColumnTrait>(column: C, value: &Option<Value>) -> Condition {
+    match value {
+        Some(value) => Condition::all().add(column.eq(value.clone())),
+        None => Condition::all().add(column.is_null()),
+    }
+}
+
+/// Rows to skip before the 1-based `page`, refusing pages so far out that the
+/// offset wouldn't fit the database's integers.
+pub fn page_offset(page: u64, per_page: u64) -> Result<u64, ApiError> {
+    page.checked_sub(1)
+        .and_then(|skipped| skipped.checked_mul(per_page))
+        .filter(|offset| i64::try_from(*offset).is_ok())
+        .ok_or_else(|| {
+            let last = i64::MAX as u64 / per_page.max(1) + 1;
+            let mut errors = ValidationErrors::new();
+            field_error(
+                &mut errors,
+                "page",
+                format!("Must be between 1 and {}.", last),
+            );
+            errors.into()
+        })
+}
+
+/// Run `select` one page at a time, sorted by the `sortable` columns named in
+/// `params.sort` (or `default_sort`) with the primary key as tie-breaker.
+pub async fn paginate<E, C>(
+    connection: &C,
+    select: Select<E>,
+    params: &PageParams,
+    sortable: &[(&str, E::Column)],
+    default_sort: &str,
+) -> Result<Page<E::Model>, ApiError>
+where
+    E: EntityTrait,
+    E::Model: Sync,
+    C: ConnectionTrait,
+{
+    let mut errors = ValidationErrors::new();
+    let per_page = params.per_page.unwrap_or(DEFAULT_PER_PAGE);
+    if !(1..=MAX_PER_PAGE).contains(&per_page) {
+        field_error(
+            &mut errors,
+            "per_page",
+            format!("Must be between 1 and {}.", MAX_PER_PAGE),
+        );
+    }
+    if params.page == Some(0) {
+        field_error(&mut errors, "page", "Must be at least 1.".to_string());
+    }
+    if params.page.is_some() && params.cursor.is_some() {
+        field_error(
+            &mut errors,
+            "cursor",
+            "Can't be combined with page.".to_string(),
+        );
+    }
+
+    let cursor = match &params.cursor {
+        Some(cursor) => {
+            let decoded = general_purpose::URL_SAFE_NO_PAD
+                .decode(cursor)
+                .ok()
+                .and_then(|bytes| serde_json::from_slice::<Cursor>(&bytes).ok());
+            if decoded.is_none() {
+                field_error(&mut errors, "cursor", "Invalid cursor.".to_string());
+            }
+            decoded
+        }
+        None => None,
+    };
+    let sort = match (&params.sort, &cursor) {
+        (Some(sort), Some(cursor)) if *sort != cursor.sort => {
+            field_error(
+                &mut errors,
+                "cursor",
+                "Was issued for a different sort.".to_string(),
+            );
+            sort.to_owned()
+        }
+        (Some(sort), _) => sort.to_owned(),
+        (None, Some(cursor)) => cursor.sort.to_owned(),
+        (None, None) => default_sort.to_string(),
+    };
+
+    let mut keys: Vec<(E::Column, Order)> = Vec::new();
+    for field in sort.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+        let (name, order) = match field.strip_prefix('-') {
+            Some(name) => (name, Order::Desc),
+            None => (field, Order::Asc),
+        };
+        match sortable.iter().find(|(sortable, _)| *sortable == name) {
+            Some((_, column)) => keys.push((*column, order)),
+            None => {
+                let allowed: Vec<&str> = sortable.iter().map(|(name, _)| *name).collect();
+                field_error(
+                    &mut errors,
+                    "sort",
+                    format!("Can't sort by {}, use {}.", name, allowed.join(", ")),
+                );
+            }
+        }
+    }
+    for key in E::PrimaryKey::iter() {
+        let column = key.into_column();
+        if !keys.iter().any(|(c, _)| c.as_str() == column.as_str()) {
+            keys.push((column, Order::Asc));
+        }
+    }
+
+    let after_values = match &cursor {
+        Some(cursor) if cursor.after.len() == keys.len() => {
+            let values: Option<Vec<Option<Value>>> = keys
+                .iter()
+                .zip(cursor.after.iter())
+                .map(|((column, _), json)| from_json(column.def().get_column_type(), json))
+                .collect();
+            if values.is_none() {
+                field_error(&mut errors, "cursor", "Invalid cursor.".to_string());
+            }
+            values
+        }
+        Some(_) => {
+            field_error(&mut errors, "cursor", "Invalid cursor.".to_string());
+            None
+        }
+        None => None,
+    };
+    if !errors.is_empty() {
+        return Err(errors.into());
+    }
+
+    let total = select.clone().count(connection).await?;
+    let mut query = select;
+    if let Some(values) = &after_values {
+        // Keyset: strictly after the cursor row in the combined sort order
+        let mut condition = Condition::any();
+        for (i, ((column, order), value)) in keys.iter().zip(values.iter()).enumerate() {
+            if let Some(next) = after(*column, order, value) {
+                let mut branch = Condition::all();
+                for ((column, _), value) in keys.iter().zip(values.iter()).take(i) {
+                    branch = branch.add(equals(*column, value));
+                }
+                condition = condition.add(branch.add(next));
+            }
+        }
+        query = query.filter(condition);
+    }
+    for (column, order) in &keys {
+        query = query.order_by(*column, order.clone());
+    }
+    let page = match &cursor {
+        Some(_) => None,
+        None => Some(params.page.unwrap_or(1)),
+    };
+    if let Some(page) = page {
+        query = query.offset(page_offset(page, per_page)?);
+    }
+
+    // One extra row tells whether another page follows
+    let mut data = query.limit(per_page + 1).all(connection).await?;
+    let next_cursor = if data.len() as u64 > per_page {
+        data.truncate(per_page as usize);
+        let last = data.last().expect("page has rows");
+        let after = keys
+            .iter()
+            .map(|(column, _)| to_json(last.get(*column)))
+            .collect::<Result<Vec<Json>, ApiError>>()?;
+        let cursor = serde_json::to_vec(&Cursor { sort, after })
+            .map_err(|err| ApiError::Internal(err.to_string()))?;
+        Some(general_purpose::URL_SAFE_NO_PAD.encode(cursor))
+    } else {
+        None
+    };
+
+    Ok(Page {
+        data,
+        meta: PageMeta {
+            total,
+            per_page,
+            page,
+            total_pages: page.map(|_| total / per_page + u64::from(total % per_page != 0)),
+            next_cursor,
+        },
+    })
+}
+
+/// Link to the current request with some query parameters replaced or removed.
+fn link(req: &HttpRequest, set: &[(&str, Option<String>)], rel: &str) -> String {
+    let mut query: Vec<(String, String)> =
+        serde_urlencoded::from_str(req.query_string()).unwrap_or_default();
+    query.retain(|(name, _)| !set.iter().any(|(key, _)| key == name));
+    for (key, value) in set {
+        if let Some(value) = value {
+            query.push((key.to_string(), value.to_owned()));
+        }
+    }
+    let info = req.connection_info();
+    format!(
+        "<{}://{}{}?{}>; rel=\"{}\"",
+        info.scheme(),
+        info.host(),
+        req.path(),
+        serde_urlencoded::to_string(&query).unwrap_or_default(),
+        rel
+    )
+}
+
+/// Answer with the page as JSON and RFC 8288 `Link` headers to its neighbours.
+pub fn page_response<T: Serialize>(req: &HttpRequest, page: Page<T>) -> HttpResponse {
+    let meta = &page.meta;
+    let mut links = Vec::new();
+    match (meta.page, meta.total_pages) {
+        (Some(current), Some(total_pages)) => {
+            let page_link = |number: u64, rel: &str| {
+                link(
+                    req,
+                    &[("page", Some(number.to_string())), ("cursor", None)],
+                    rel,
+                )
+            };
+            links.push(page_link(1, "first"));
+            if current > 1 {
+                links.push(page_link(current - 1, "prev"));
+            }
+            if current < total_pages {
+                links.push(page_link(current + 1, "next"));
+            }
+            links.push(page_link(total_pages.max(1), "last"));
+        }
+        _ => {
+            if let Some(cursor) = &meta.next_cursor {
+                links.push(link(
+                    req,
+                    &[("cursor", Some(cursor.to_owned())), ("page", None)],
+                    "next",
+                ));
+            }
+        }
+    }
+
+    let mut response = HttpResponse::Ok();
+    if !links.is_empty() {
+        response.insert_header((header::LINK, links.join(", ")));
+    }
+    response.json(page)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_offset_skips_earlier_pages() {
+        assert_eq!(page_offset(1, 20).unwrap(), 0);
+        assert_eq!(page_offset(3, 20).unwrap(), 40);
+        let last = i64::MAX as u64 / 20 + 1;
+        assert_eq!(page_offset(last, 20).unwrap(), (last - 1) * 20);
+    }
+
+    #[test]
+    fn page_offset_refuses_pages_out_of_range() {
+        assert!(page_offset(0, 20).is_err());
+        assert!(page_offset(i64::MAX as u64 / 20 + 2, 20).is_err());
+        assert!(page_offset(u64::MAX, 100).is_err());
+    }
+}
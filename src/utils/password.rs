@@ -0,0 +1,99 @@
+use crate::config::PasswordConfig;
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
+use log::warn;
+use md5::{Digest, Md5};
+use subtle::ConstantTimeEq;
+
+/// Outcome of checking a plain text password against a stored hash.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PasswordVerification {
+    Invalid,
+    Valid,
+    /// The password matches, but the stored hash is a legacy MD5 digest or was
+    /// produced with different Argon2 parameters and should be replaced.
+    ValidNeedsRehash,
+}
+
+impl PasswordVerification {
+    pub fn is_valid(&self) -> bool {
+        !matches!(self, PasswordVerification::Invalid)
+    }
+}
+
+fn params(config: &PasswordConfig) -> Params {
+    Params::new(
+        config.memory_cost,
+        config.time_cost,
+        config.parallelism,
+        None,
+    )
+    .unwrap_or_else(|err| {
+        warn!("Invalid Argon2 parameters, using defaults: {}", err);
+        Params::default()
+    })
+}
+
+fn hasher(config: &PasswordConfig) -> Argon2<'static> {
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params(config))
+}
+
+/// Hash a password with Argon2id and a random salt, returning a PHC string.
+pub fn hash_password(
+    config: &PasswordConfig,
+    password: &str,
+) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(hasher(config)
+        .hash_password(password.as_bytes(), &salt)?
+        .to_string())
+}
+
+/// Verify a password against either an Argon2 PHC string or a legacy MD5 hex digest.
+pub fn verify_password(
+    config: &PasswordConfig,
+    password: &str,
+    stored: &str,
+) -> PasswordVerification {
+    match PasswordHash::new(stored) {
+        Ok(hash) => {
+            let argon2 = hasher(config);
+            if argon2.verify_password(password.as_bytes(), &hash).is_err() {
+                return PasswordVerification::Invalid;
+            }
+            let current = argon2.params();
+            let outdated = hash.algorithm != Algorithm::Argon2id.ident()
+                || hash.version != Some(Version::V0x13.into())
+                || Params::try_from(&hash).map_or(true, |stored| {
+                    stored.m_cost() != current.m_cost()
+                        || stored.t_cost() != current.t_cost()
+                        || stored.p_cost() != current.p_cost()
+                });
+            if outdated {
+                PasswordVerification::ValidNeedsRehash
+            } else {
+                PasswordVerification::Valid
+            }
+        }
+        Err(_) if verify_legacy_md5(password, stored) => PasswordVerification::ValidNeedsRehash,
+        Err(_) => PasswordVerification::Invalid,
+    }
+}
+
+/// Burn roughly the same amount of time as a real verification, so that
+/// unknown accounts can't be told apart from wrong passwords by timing.
+pub fn dummy_verify(config: &PasswordConfig, password: &str) {
+    let _ = hash_password(config, password);
+}
+
+fn verify_legacy_md5(password: &str, stored: &str) -> bool {
+    let mut hasher = Md5::new();
+    hasher.update(password.as_bytes());
+    let digest = format!("{:x}", hasher.finalize());
+    digest
+        .as_bytes()
+        .ct_eq(stored.to_ascii_lowercase().as_bytes())
+        .into()
+}
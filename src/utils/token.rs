@@ -1,28 +1,147 @@
-use actix_web::{error::ErrorUnauthorized, http::header::HeaderValue, Error};
+use crate::config::JwtConfig;
+use crate::errors::ApiError;
+use crate::models::users::Role;
+use crate::services::jwt_keys::JwtKeys;
+use actix_web::http::header::HeaderValue;
 use base64::{engine::general_purpose, Engine as _};
-use jsonwebtoken::{decode, DecodingKey, Validation};
-use serde::{Deserialize, Serialize};
-use std::env;
+use jsonwebtoken::{decode, decode_header, encode, Header, Validation};
+use rand::{rngs::OsRng, RngCore};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TokenClaims {
     pub sub: String,
     pub iat: usize,
     pub exp: usize,
+    pub jti: Uuid,
+    #[serde(default)]
+    pub role: Role,
+    /// Whether the login passed a second factor.
+    #[serde(default)]
+    pub mfa: bool,
 }
 
-pub fn decode_token(token: HeaderValue) -> Result<TokenClaims, Error> {
-    match general_purpose::STANDARD_NO_PAD.decode(token.to_str().unwrap().replace("Bearer ", "")) {
-        Ok(token_str) => {
-            match decode::<TokenClaims>(
-                String::from_utf8(token_str).unwrap().as_str(),
-                &DecodingKey::from_secret(env::var("JWT_SECRET").unwrap().as_ref()),
-                &Validation::default(),
-            ) {
-                Ok(data) => Ok(data.claims),
-                Err(_) => Err(ErrorUnauthorized("Invalid or missing authorization token.")),
-            }
-        }
-        Err(_) => Err(ErrorUnauthorized("Invalid or missing authorization token.")),
+/// Claims of the short-lived token returned by the first login step when a
+/// second factor is still due. It has no `jti`, so it can't pass for an
+/// access token, and access tokens lack `mfa_pending` so they can't pass for it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MfaPendingClaims {
+    pub sub: String,
+    pub iat: usize,
+    pub exp: usize,
+    pub mfa_pending: bool,
+}
+
+impl TokenClaims {
+    /// Whether the caller holds at least `role`; staff privileges also need a second factor.
+    pub fn has_role(&self, role: Role) -> bool {
+        self.role.satisfies(role) && (self.mfa || !role.is_staff())
+    }
+
+    /// Whether the token belongs to the given user or to someone holding at least `role`.
+    pub fn can_act_for(&self, user_id: Uuid, role: Role) -> bool {
+        self.sub == user_id.to_string() || self.has_role(role)
     }
+
+    /// The caller's user id, as carried in `sub`.
+    pub fn user_id(&self) -> Option<Uuid> {
+        Uuid::parse_str(self.sub.as_str()).ok()
+    }
+}
+
+/// `typ` header of access tokens; the pending-MFA token uses its own, so
+/// services validating through the JWKS can't mistake one for the other.
+const ACCESS_TOKEN_TYPE: &str = "JWT";
+const MFA_TOKEN_TYPE: &str = "mfa+jwt";
+
+/// Sign claims with the current key as a compact JWS, so any JOSE library
+/// can verify it against the JWKS.
+fn sign<T: Serialize>(keys: &JwtKeys, typ: &str, claims: &T) -> String {
+    let key = keys.signing_key().expect("JWT keys: No active key!");
+    let header = Header {
+        typ: Some(typ.to_string()),
+        alg: key.algorithm,
+        kid: Some(key.kid.to_owned()),
+        ..Default::default()
+    };
+    encode(&header, claims, &key.encoding).unwrap()
+}
+
+/// Check the signature with the key named by the token's `kid`.
+fn verify<T: DeserializeOwned>(keys: &JwtKeys, typ: &str, token: &str) -> Option<T> {
+    let header = decode_header(token).ok()?;
+    if header.typ.as_deref() != Some(typ) {
+        return None;
+    }
+    let key = keys.find(header.kid?.as_str())?;
+    decode::<T>(token, &key.decoding, &Validation::new(key.algorithm))
+        .ok()
+        .map(|data| data.claims)
+}
+
+pub fn decode_token(keys: &JwtKeys, token: HeaderValue) -> Result<TokenClaims, ApiError> {
+    token
+        .to_str()
+        .ok()
+        .and_then(|token| {
+            verify(
+                keys,
+                ACCESS_TOKEN_TYPE,
+                token.replace("Bearer ", "").as_str(),
+            )
+        })
+        .ok_or_else(|| {
+            ApiError::Unauthorized("Invalid or missing authorization token.".to_string())
+        })
+}
+
+/// Sign a new access token for the user.
+pub fn encode_token(
+    keys: &JwtKeys,
+    config: &JwtConfig,
+    user_id: Uuid,
+    role: Role,
+    mfa: bool,
+) -> String {
+    let now = chrono::Utc::now();
+    let iat = now.timestamp() as usize;
+    let exp = (now + config.access_token_lifetime()).timestamp() as usize;
+    let claims: TokenClaims = TokenClaims {
+        sub: user_id.to_string(),
+        exp,
+        iat,
+        jti: Uuid::new_v4(),
+        role,
+        mfa,
+    };
+    sign(keys, ACCESS_TOKEN_TYPE, &claims)
+}
+
+pub fn encode_mfa_token(keys: &JwtKeys, config: &JwtConfig, user_id: Uuid) -> String {
+    let now = chrono::Utc::now();
+    let claims = MfaPendingClaims {
+        sub: user_id.to_string(),
+        iat: now.timestamp() as usize,
+        exp: (now + config.mfa_pending_lifetime()).timestamp() as usize,
+        mfa_pending: true,
+    };
+    sign(keys, MFA_TOKEN_TYPE, &claims)
+}
+
+pub fn decode_mfa_token(keys: &JwtKeys, token: &str) -> Option<MfaPendingClaims> {
+    verify::<MfaPendingClaims>(keys, MFA_TOKEN_TYPE, token).filter(|claims| claims.mfa_pending)
+}
+
+/// Random, URL-safe token for single-use links and refresh tokens.
+pub fn generate_opaque_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Opaque tokens are only ever persisted as their SHA-256 digest.
+pub fn hash_opaque_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
 }
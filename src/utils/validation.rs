@@ -0,0 +1,82 @@
+use crate::config::PasswordConfig;
+use crate::services::notifications::SUPPORTED_LOCALES;
+use chrono::{Datelike, Utc};
+use std::borrow::Cow;
+use validator::ValidationError;
+
+fn error(code: &'static str, message: String) -> ValidationError {
+    let mut err = ValidationError::new(code);
+    err.message = Some(Cow::from(message));
+    err
+}
+
+/// Reject values made only of whitespace, which `length` lets through.
+pub fn not_blank(value: &str) -> Result<(), ValidationError> {
+    if value.trim().is_empty() {
+        return Err(error("blank", "Can't be blank.".to_string()));
+    }
+    Ok(())
+}
+
+/// Notifications can only be sent in the languages there are templates for.
+pub fn supported_locale(locale: &str) -> Result<(), ValidationError> {
+    if !SUPPORTED_LOCALES.contains(&locale) {
+        return Err(error(
+            "locale",
+            format!("Must be one of {}.", SUPPORTED_LOCALES.join(", ")),
+        ));
+    }
+    Ok(())
+}
+
+/// Publication years run from year 1 up to the current year.
+pub fn publication_year(year: i32) -> Result<(), ValidationError> {
+    let current = Utc::now().year();
+    if !(1..=current).contains(&year) {
+        return Err(error("year", format!("Must be between 1 and {}.", current)));
+    }
+    Ok(())
+}
+
+/// Check a new password against the configured strength rules, listing every rule it breaks.
+pub fn password_strength(password: &str, config: &PasswordConfig) -> Result<(), ValidationError> {
+    let length = password.chars().count();
+    let rules = [
+        (
+            length >= config.min_length,
+            format!("at least {} characters", config.min_length),
+        ),
+        (
+            length <= config.max_length,
+            format!("at most {} characters", config.max_length),
+        ),
+        (
+            !config.require_uppercase || password.chars().any(char::is_uppercase),
+            "an uppercase letter".to_string(),
+        ),
+        (
+            !config.require_lowercase || password.chars().any(char::is_lowercase),
+            "a lowercase letter".to_string(),
+        ),
+        (
+            !config.require_digit || password.chars().any(|c| c.is_ascii_digit()),
+            "a digit".to_string(),
+        ),
+        (
+            !config.require_symbol || password.chars().any(|c| !c.is_alphanumeric()),
+            "a symbol".to_string(),
+        ),
+    ];
+    let broken: Vec<String> = rules
+        .into_iter()
+        .filter(|(met, _)| !met)
+        .map(|(_, rule)| rule)
+        .collect();
+    if broken.is_empty() {
+        return Ok(());
+    }
+    Err(error(
+        "password_strength",
+        format!("Password must contain {}.", broken.join(", ")),
+    ))
+}